@@ -0,0 +1,143 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What a mailbox holds: messages, then possibly a request to stop once they are handled.
+pub(crate) enum Envelope<M> {
+    Message(M),
+    Stop,
+    /// Stops the given generation of a supervised actor, leaving the mailbox to the next.
+    Halt(u64),
+}
+
+/// Address of a running actor, accepting messages of type `M`. Cheap to clone and to
+/// send to other threads.
+pub struct ActorRef<M> {
+    /// Unique to the mailbox, as names need not be.
+    id: u64,
+    name: Arc<str>,
+    sender: Sender<Envelope<M>>,
+}
+
+impl<M> Clone for ActorRef<M> {
+    fn clone(&self) -> Self {
+        ActorRef { id: self.id, name: self.name.clone(), sender: self.sender.clone() }
+    }
+}
+
+impl<M> PartialEq for ActorRef<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<M> Eq for ActorRef<M> {}
+
+impl<M> fmt::Debug for ActorRef<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ActorRef({})", self.name)
+    }
+}
+
+impl<M: Send + 'static> ActorRef<M> {
+    pub(crate) fn new(name: &str, sender: Sender<Envelope<M>>) -> ActorRef<M> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        ActorRef { id: NEXT_ID.fetch_add(1, Ordering::Relaxed), name: name.into(), sender }
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Queues `message` in the actor's mailbox. Fails, returning the message, once the
+    /// actor has stopped.
+    pub fn tell(&self, message: M) -> Result<(), SendError<M>> {
+        self.sender.send(Envelope::Message(message)).map_err(|error| match error.0 {
+            Envelope::Message(message) => SendError(message),
+            _ => unreachable!("only messages are sent here"),
+        })
+    }
+
+    /// Sends the message built by `request` around a `ReplyTo`, and waits at most
+    /// `timeout` for the actor to answer through it.
+    pub fn ask<R, F>(&self, request: F, timeout: Duration) -> Result<R, AskError>
+    where
+        F: FnOnce(ReplyTo<R>) -> M,
+    {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        self.tell(request(ReplyTo { sender })).map_err(|_| AskError::Stopped)?;
+        receiver.recv_timeout(timeout).map_err(|error| match error {
+            mpsc::RecvTimeoutError::Timeout => AskError::Timeout,
+            mpsc::RecvTimeoutError::Disconnected => AskError::NoReply,
+        })
+    }
+
+    /// Asks the actor to stop once it has handled the messages already in its mailbox.
+    pub fn stop(&self) {
+        let _ = self.sender.send(Envelope::Stop);
+    }
+
+    pub(crate) fn halt(&self, generation: u64) {
+        let _ = self.sender.send(Envelope::Halt(generation));
+    }
+}
+
+/// Where to send the answer to an `ask`. Dropping it without replying fails the `ask`
+/// with `AskError::NoReply`.
+#[derive(Debug)]
+pub struct ReplyTo<R> {
+    sender: mpsc::SyncSender<R>,
+}
+
+impl<R> ReplyTo<R> {
+    /// Returns false if the asker has stopped waiting.
+    pub fn reply(self, response: R) -> bool {
+        self.sender.try_send(response).is_ok()
+    }
+}
+
+/// A message that could not be delivered because its actor has stopped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SendError<M>(pub M);
+
+impl<M> fmt::Debug for SendError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SendError {{ .. }}")
+    }
+}
+
+impl<M> fmt::Display for SendError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the actor has stopped")
+    }
+}
+
+impl<M> Error for SendError<M> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AskError {
+    /// The actor had stopped before the request was sent.
+    Stopped,
+    Timeout,
+    /// The actor dropped the `ReplyTo`, e.g. because it stopped or panicked.
+    NoReply,
+}
+
+impl fmt::Display for AskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AskError::Stopped => write!(f, "the actor has stopped"),
+            AskError::Timeout => write!(f, "the actor did not answer in time"),
+            AskError::NoReply => write!(f, "the actor dropped the request without answering"),
+        }
+    }
+}
+
+impl Error for AskError {}
@@ -0,0 +1,182 @@
+//! Actors running on threads and exchanging typed messages.
+//!
+//! Every actor owns its state and a mailbox of `Actor::Message`, and handles the
+//! messages one at a time on a thread of its own. It is only reached through an
+//! `ActorRef`, which can be cloned and sent anywhere:
+//!
+//! ```ignore
+//! let system = ActorSystem::new();
+//! let counter = system.spawn("counter", Counter::default());
+//! counter.tell(Count::Add(2)).unwrap();
+//! let total = counter.ask(Count::Total, Duration::from_secs(1)).unwrap();
+//! system.shutdown();
+//! ```
+
+mod actor_ref;
+mod registry;
+pub mod supervisor;
+mod system;
+mod timers;
+
+pub use actor_ref::{ActorRef, AskError, ReplyTo, SendError};
+pub use registry::{Registry, RegistryError};
+pub use supervisor::{Strategy, Supervisor, SupervisorMessage};
+pub use system::ActorSystem;
+pub use timers::{TimerHandle, Timers};
+
+use std::time::Duration;
+
+pub trait Actor: Send + 'static {
+    type Message: Send + 'static;
+
+    fn handle(&mut self, message: Self::Message, context: &mut Context<Self::Message>);
+
+    /// Called on the actor's thread before the first message.
+    fn started(&mut self, _context: &mut Context<Self::Message>) {}
+
+    /// Called on the actor's thread after the last message.
+    fn stopped(&mut self) {}
+}
+
+/// What an actor knows about where it runs.
+pub struct Context<M> {
+    myself: ActorRef<M>,
+    system: ActorSystem,
+    stopping: bool,
+}
+
+impl<M: Send + 'static> Context<M> {
+    fn new(myself: ActorRef<M>, system: ActorSystem) -> Context<M> {
+        Context { myself, system, stopping: false }
+    }
+
+    /// Address of the actor, to hand out to others.
+    pub fn myself(&self) -> &ActorRef<M> {
+        &self.myself
+    }
+
+    /// The system the actor runs in, e.g. to spawn other actors.
+    pub fn system(&self) -> &ActorSystem {
+        &self.system
+    }
+
+    /// Sends `message` to the actor itself once `delay` has passed, without blocking it
+    /// meanwhile.
+    pub fn send_after(&self, delay: Duration, message: M) -> TimerHandle {
+        self.system.timers().send_after(delay, &self.myself, message)
+    }
+
+    /// Stops the actor after the current message; the rest of its mailbox is dropped.
+    pub fn stop(&mut self) {
+        self.stopping = true;
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::{self, Sender};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+
+    enum Count {
+        Add(u32),
+        Total(ReplyTo<u32>),
+        Sleep(Duration),
+    }
+
+    struct Counter {
+        total: u32,
+        on_stop: Sender<u32>,
+    }
+
+    impl Actor for Counter {
+        type Message = Count;
+
+        fn handle(&mut self, message: Count, _context: &mut Context<Count>) {
+            match message {
+                Count::Add(n) => self.total += n,
+                Count::Total(reply_to) => {
+                    reply_to.reply(self.total);
+                }
+                Count::Sleep(duration) => thread::sleep(duration),
+            }
+        }
+
+        fn stopped(&mut self) {
+            let _ = self.on_stop.send(self.total);
+        }
+    }
+
+    fn counter(system: &ActorSystem) -> (ActorRef<Count>, mpsc::Receiver<u32>) {
+        let (on_stop, stopped) = mpsc::channel();
+        (system.spawn("counter", Counter { total: 0, on_stop }), stopped)
+    }
+
+    #[test]
+    fn test_ask_sees_earlier_messages() {
+        let system = ActorSystem::new();
+        let (counter, _) = counter(&system);
+        let senders: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                thread::spawn(move || (1..=10).for_each(|n| counter.tell(Count::Add(n)).unwrap()))
+            })
+            .collect();
+        senders.into_iter().for_each(|sender| sender.join().unwrap());
+        assert_eq!(Ok(220), counter.ask(Count::Total, Duration::from_secs(1)));
+        assert!(system.shutdown().is_empty());
+    }
+
+    #[test]
+    fn test_ask_times_out() {
+        let system = ActorSystem::new();
+        let (counter, _) = counter(&system);
+        counter.tell(Count::Sleep(Duration::from_millis(200))).unwrap();
+        assert_eq!(Err(AskError::Timeout), counter.ask(Count::Total, Duration::from_millis(10)));
+        system.shutdown();
+    }
+
+    #[test]
+    fn test_stopped_actors_are_joined() {
+        struct Faulty;
+
+        impl Actor for Faulty {
+            type Message = ();
+
+            fn handle(&mut self, _: (), _context: &mut Context<()>) {
+                panic!("injected failure");
+            }
+        }
+
+        let system = ActorSystem::new();
+        let (counter, stopped) = counter(&system);
+        let faulty = system.spawn("faulty", Faulty);
+        counter.stop();
+        faulty.tell(()).unwrap();
+        assert!(stopped.recv_timeout(Duration::from_secs(5)).is_ok());
+        while !system.actors().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        system.spawn("counter", Counter { total: 0, on_stop: mpsc::channel().0 });
+        assert_eq!(vec!["counter".to_string()], system.actors());
+        assert_eq!(vec!["faulty".to_string()], system.shutdown());
+    }
+
+    #[test]
+    fn test_shutdown_drains_mailboxes() {
+        let system = ActorSystem::new();
+        let (counter, stopped) = counter(&system);
+        counter.tell(Count::Sleep(Duration::from_millis(20))).unwrap();
+        counter.tell(Count::Add(5)).unwrap();
+        system.shutdown();
+
+        assert_eq!(Ok(5), stopped.try_recv());
+        assert!(system.actors().is_empty());
+        assert!(matches!(counter.tell(Count::Add(1)), Err(SendError(Count::Add(1)))));
+        assert_eq!(Err(AskError::Stopped), counter.ask(Count::Total, Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,328 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::actors::ActorRef;
+
+/// Actors of a system, by name and by topic. Names and subscriptions go away when their
+/// actor terminates: a supervised actor that is restarted keeps them. An actor that has
+/// terminated cannot be registered or subscribed any more.
+///
+/// Addresses are stored whatever their message type, and looked up or published to
+/// with the type they were registered with.
+#[derive(Clone, Default)]
+pub struct Registry {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    names: HashMap<String, Registered>,
+    topics: HashMap<String, Vec<Member>>,
+    /// Mailboxes of the actors that have terminated.
+    terminated: HashSet<u64>,
+    /// Names whose actor has terminated, until they are registered again.
+    vacated: HashSet<String>,
+    /// Children by supervisor, terminated along with it.
+    children: HashMap<u64, Vec<u64>>,
+}
+
+struct Registered {
+    id: u64,
+    address: Box<dyn Any + Send>,
+    /// Notify the watchers, once the actor has terminated.
+    watchers: Vec<Box<dyn FnOnce() + Send>>,
+}
+
+struct Member {
+    id: u64,
+    address: Box<dyn Any + Send>,
+}
+
+impl Registry {
+    pub fn register<M: Send + 'static>(&self, name: &str, address: &ActorRef<M>) -> Result<(), RegistryError> {
+        let mut inner = self.lock();
+        if inner.names.contains_key(name) {
+            return Err(RegistryError::NameTaken(name.to_owned()));
+        }
+        if inner.terminated.contains(&address.id()) {
+            return Err(RegistryError::Terminated(name.to_owned()));
+        }
+        let registered = Registered { id: address.id(), address: Box::new(address.clone()), watchers: Vec::new() };
+        inner.names.insert(name.to_owned(), registered);
+        inner.vacated.remove(name);
+        Ok(())
+    }
+
+    /// Frees `name`. Its watchers are dropped without being notified.
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut inner = self.lock();
+        inner.vacated.remove(name);
+        inner.names.remove(name).is_some()
+    }
+
+    /// The actor registered under `name`, if it takes messages of type `M`.
+    pub fn lookup<M: Send + 'static>(&self, name: &str) -> Option<ActorRef<M>> {
+        self.lock().names.get(name)?.address.downcast_ref::<ActorRef<M>>().cloned()
+    }
+
+    /// Sends `message` to `watcher` when the actor registered under `name` terminates, or
+    /// right away if it has terminated already.
+    pub fn watch<M: Send + 'static>(&self, name: &str, watcher: &ActorRef<M>, message: M) -> Result<(), RegistryError> {
+        let mut inner = self.lock();
+        if let Some(registered) = inner.names.get_mut(name) {
+            let watcher = watcher.clone();
+            registered.watchers.push(Box::new(move || {
+                let _ = watcher.tell(message);
+            }));
+            return Ok(());
+        }
+        if !inner.vacated.contains(name) {
+            return Err(RegistryError::NotRegistered(name.to_owned()));
+        }
+        drop(inner);
+        let _ = watcher.tell(message);
+        Ok(())
+    }
+
+    /// Adds `address` to the members of `topic`, unless it is one already or has
+    /// terminated.
+    pub fn subscribe<M: Send + 'static>(&self, topic: &str, address: &ActorRef<M>) {
+        let mut inner = self.lock();
+        if inner.terminated.contains(&address.id()) {
+            return;
+        }
+        let members = inner.topics.entry(topic.to_owned()).or_default();
+        if members.iter().all(|member| member.id != address.id()) {
+            members.push(Member { id: address.id(), address: Box::new(address.clone()) });
+        }
+    }
+
+    pub fn unsubscribe<M: Send + 'static>(&self, topic: &str, address: &ActorRef<M>) {
+        if let Some(members) = self.lock().topics.get_mut(topic) {
+            members.retain(|member| member.id != address.id());
+        }
+    }
+
+    /// The members of `topic` taking messages of type `M`, in the order they subscribed.
+    pub fn members<M: Send + 'static>(&self, topic: &str) -> Vec<ActorRef<M>> {
+        let inner = self.lock();
+        let members = inner.topics.get(topic).map(Vec::as_slice).unwrap_or_default();
+        members.iter().filter_map(|member| member.address.downcast_ref::<ActorRef<M>>().cloned()).collect()
+    }
+
+    /// Sends a copy of `message` to every member of `topic` taking messages of type `M`,
+    /// and returns how many there were.
+    pub fn publish<M: Clone + Send + 'static>(&self, topic: &str, message: M) -> usize {
+        let members = self.members::<M>(topic);
+        members.iter().filter(|member| member.tell(message.clone()).is_ok()).count()
+    }
+
+    /// Makes the actor with mailbox `child` terminate along with the supervisor with
+    /// mailbox `supervisor`.
+    pub(crate) fn supervised(&self, supervisor: u64, child: u64) {
+        let mut inner = self.lock();
+        let children = inner.children.entry(supervisor).or_default();
+        if !children.contains(&child) {
+            children.push(child);
+        }
+    }
+
+    /// Forgets the actor with mailbox `id`, and the children it supervises, and notifies
+    /// their watchers.
+    pub(crate) fn terminated(&self, id: u64) {
+        let mut watchers = Vec::new();
+        {
+            let mut inner = self.lock();
+            let mut ids = vec![id];
+            while let Some(id) = ids.pop() {
+                inner.terminated.insert(id);
+                let mut vacated = Vec::new();
+                inner.names.retain(|name, registered| {
+                    if registered.id == id {
+                        watchers.append(&mut registered.watchers);
+                        vacated.push(name.clone());
+                    }
+                    registered.id != id
+                });
+                inner.vacated.extend(vacated);
+                for members in inner.topics.values_mut() {
+                    members.retain(|member| member.id != id);
+                }
+                ids.extend(inner.children.remove(&id).unwrap_or_default());
+            }
+        }
+        for notify in watchers {
+            notify();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    NameTaken(String),
+    NotRegistered(String),
+    /// The actor to register under the name has terminated.
+    Terminated(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::NameTaken(name) => write!(f, "an actor is already registered as {}", name),
+            RegistryError::NotRegistered(name) => write!(f, "no actor is registered as {}", name),
+            RegistryError::Terminated(name) => write!(f, "cannot register {}: the actor has terminated", name),
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::{self, Sender};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::actors::{Actor, ActorSystem, Context, Strategy, Supervisor};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Note {
+        Text(&'static str),
+        Terminated(&'static str),
+        Fail,
+    }
+
+    /// Forwards its notes to the test, tagged with its name.
+    struct Listener {
+        name: &'static str,
+        notes: Sender<(&'static str, Note)>,
+    }
+
+    impl Actor for Listener {
+        type Message = Note;
+
+        fn handle(&mut self, note: Note, _context: &mut Context<Note>) {
+            if note == Note::Fail {
+                panic!("injected failure");
+            }
+            self.notes.send((self.name, note)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_name_and_type() {
+        let system = ActorSystem::new();
+        let (notes, received) = mpsc::channel();
+        let alice = system.spawn("alice", Listener { name: "alice", notes });
+        let registry = system.registry();
+        registry.register("alice", &alice).unwrap();
+        assert_eq!(Err(RegistryError::NameTaken("alice".into())), registry.register("alice", &alice));
+
+        assert_eq!(Some(alice.clone()), registry.lookup::<Note>("alice"));
+        assert!(registry.lookup::<String>("alice").is_none());
+        assert!(registry.lookup::<Note>("toby").is_none());
+        registry.lookup::<Note>("alice").unwrap().tell(Note::Text("hello")).unwrap();
+        assert_eq!(Ok(("alice", Note::Text("hello"))), received.recv_timeout(TIMEOUT));
+
+        assert!(registry.unregister("alice"));
+        assert!(registry.lookup::<Note>("alice").is_none());
+        system.shutdown();
+    }
+
+    #[test]
+    fn test_watchers_are_told_of_termination() {
+        let system = ActorSystem::new();
+        let (notes, received) = mpsc::channel();
+        let alice = system.spawn("alice", Listener { name: "alice", notes: notes.clone() });
+        let toby = system.spawn("toby", Listener { name: "toby", notes });
+        let registry = system.registry();
+        registry.register("alice", &alice).unwrap();
+        registry.subscribe("news", &alice);
+        registry.watch("alice", &toby, Note::Terminated("alice")).unwrap();
+
+        alice.stop();
+        assert_eq!(Ok(("toby", Note::Terminated("alice"))), received.recv_timeout(TIMEOUT));
+        assert!(registry.lookup::<Note>("alice").is_none());
+        assert!(registry.members::<Note>("news").is_empty());
+
+        // Too late to register, but not to watch.
+        assert_eq!(Err(RegistryError::Terminated("alice".into())), registry.register("alice", &alice));
+        registry.subscribe("news", &alice);
+        assert!(registry.members::<Note>("news").is_empty());
+        registry.watch("alice", &toby, Note::Terminated("alice again")).unwrap();
+        assert_eq!(Ok(("toby", Note::Terminated("alice again"))), received.recv_timeout(TIMEOUT));
+        assert_eq!(Err(RegistryError::NotRegistered("bob".into())), registry.watch("bob", &toby, Note::Fail));
+
+        registry.register("alice", &toby).unwrap();
+        assert_eq!(Some(toby.clone()), registry.lookup::<Note>("alice"));
+        system.shutdown();
+    }
+
+    #[test]
+    fn test_restarted_nested_child_keeps_its_name() {
+        let system = ActorSystem::new();
+        let (notes, received) = mpsc::channel();
+        let mut inner = Supervisor::new(Strategy::OneForOne).max_restarts(0, TIMEOUT);
+        let worker = inner.child("worker", move || Listener { name: "worker", notes: notes.clone() });
+        let mut outer = Supervisor::new(Strategy::OneForOne);
+        outer.supervisor("inner", inner);
+        system.spawn("outer", outer);
+        let registry = system.registry();
+        registry.register("worker", &worker).unwrap();
+        registry.subscribe("news", &worker);
+
+        // The inner supervisor gives up, and the outer one restarts it with the worker.
+        worker.tell(Note::Fail).unwrap();
+        worker.tell(Note::Text("back")).unwrap();
+        assert_eq!(Ok(("worker", Note::Text("back"))), received.recv_timeout(TIMEOUT));
+        assert_eq!(Some(worker.clone()), registry.lookup::<Note>("worker"));
+        assert_eq!(vec![worker.clone()], registry.members::<Note>("news"));
+        assert_eq!(Err(RegistryError::NameTaken("worker".into())), registry.register("worker", &worker));
+
+        // Terminated with the outer supervisor.
+        system.shutdown();
+        assert!(registry.lookup::<Note>("worker").is_none());
+        assert_eq!(Err(RegistryError::Terminated("again".into())), registry.register("again", &worker));
+    }
+
+    #[test]
+    fn test_publish_reaches_subscribers() {
+        let system = ActorSystem::new();
+        let (notes, received) = mpsc::channel();
+        let mut supervisor = Supervisor::new(Strategy::OneForOne);
+        let listeners: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| {
+                let notes = notes.clone();
+                supervisor.child(name, move || Listener { name, notes: notes.clone() })
+            })
+            .collect();
+        system.spawn("supervisor", supervisor);
+        let registry = system.registry();
+        for listener in &listeners {
+            registry.subscribe("news", listener);
+        }
+        registry.subscribe("news", &listeners[0]);
+        registry.unsubscribe("news", &listeners[2]);
+        assert_eq!(listeners[..2].to_vec(), registry.members::<Note>("news"));
+
+        // Restarting a subscriber keeps its subscriptions.
+        listeners[1].tell(Note::Fail).unwrap();
+        assert_eq!(2, registry.publish("news", Note::Text("extra")));
+        assert_eq!(0, registry.publish("news", "of another type"));
+        let mut delivered: Vec<_> = (0..2).map(|_| received.recv_timeout(TIMEOUT).unwrap()).collect();
+        delivered.sort_by_key(|(name, _)| *name);
+        assert_eq!(vec![("a", Note::Text("extra")), ("b", Note::Text("extra"))], delivered);
+        system.shutdown();
+    }
+}
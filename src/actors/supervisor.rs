@@ -0,0 +1,487 @@
+//! Supervisors restarting the actors that panic.
+//!
+//! A `Supervisor` is an actor owning child actors. It starts them when it starts, and
+//! when one of them panics it restarts it, and possibly its siblings, according to its
+//! `Strategy`. A restarted actor is a new instance built by the factory given for the
+//! child, reading the same mailbox: its `ActorRef` stays valid, and it handles the
+//! messages queued after the one that failed.
+//!
+//! The siblings restarted along with a child first handle the messages already in their
+//! mailbox; the supervisor keeps handling its own messages meanwhile, and starts the new
+//! instances once all the old ones have exited.
+//!
+//! Children that stop normally are not restarted: their mailbox is closed, so that sending
+//! to them fails, and they terminate as far as the `Registry` is concerned. The others
+//! terminate along with the supervisor: a supervisor restarted by its own keeps its
+//! children registered. When there are more than `max_restarts`
+//! restarts within the period, the supervisor gives up: it stops its children and panics
+//! in turn, so that its own supervisor, if any, restarts it along with all its children.
+
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::actors::actor_ref::Envelope;
+use crate::actors::system::run;
+use crate::actors::{Actor, ActorRef, Context, Registry, ReplyTo};
+
+/// Which children are restarted when one of them panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Only the child that panicked.
+    OneForOne,
+    /// All the running children.
+    OneForAll,
+    /// The child that panicked and the running children added after it.
+    RestForOne,
+}
+
+pub enum SupervisorMessage {
+    /// Number of restarts of children so far.
+    Restarts(ReplyTo<usize>),
+    Exited(ChildExit),
+}
+
+/// Sent by a child to its supervisor when it stops.
+pub struct ChildExit {
+    child: usize,
+    generation: u64,
+    panicked: bool,
+}
+
+pub struct Supervisor {
+    strategy: Strategy,
+    max_restarts: usize,
+    period: Duration,
+    children: Vec<Box<dyn Supervised>>,
+    /// When the recent restarts happened, for the restart intensity.
+    recent: VecDeque<Instant>,
+    restarts: usize,
+    /// Children to start again once they have all exited.
+    restarting: Vec<usize>,
+    /// Of the system, once started.
+    registry: Option<Registry>,
+}
+
+impl Supervisor {
+    /// A supervisor allowing 3 restarts within 5 seconds.
+    pub fn new(strategy: Strategy) -> Supervisor {
+        Supervisor {
+            strategy,
+            max_restarts: 3,
+            period: Duration::from_secs(5),
+            children: Vec::new(),
+            recent: VecDeque::new(),
+            restarts: 0,
+            restarting: Vec::new(),
+            registry: None,
+        }
+    }
+
+    /// Gives up after more than `restarts` restarts within `period`.
+    pub fn max_restarts(mut self, restarts: usize, period: Duration) -> Supervisor {
+        self.max_restarts = restarts;
+        self.period = period;
+        self
+    }
+
+    /// Adds a child built by `factory`, now and on every restart. Children are started in
+    /// the order they are added, and stopped in the reverse order.
+    pub fn child<A, F>(&mut self, name: &str, mut factory: F) -> ActorRef<A::Message>
+    where
+        A: Actor,
+        F: FnMut() -> A + Send + 'static,
+    {
+        self.add(name, Box::new(move |_| factory()))
+    }
+
+    /// Adds a supervisor as a child. Restarting it restarts all of its children.
+    pub fn supervisor(&mut self, name: &str, supervisor: Supervisor) -> ActorRef<SupervisorMessage> {
+        let mut supervisor = Some(supervisor);
+        self.add(
+            name,
+            Box::new(move |previous: Option<Supervisor>| {
+                previous.or_else(|| supervisor.take()).expect("the supervisor is returned by its thread")
+            }),
+        )
+    }
+
+    fn add<A: Actor>(&mut self, name: &str, renew: Renew<A>) -> ActorRef<A::Message> {
+        let (sender, receiver) = mpsc::channel();
+        let address = ActorRef::new(name, sender);
+        self.children.push(Box::new(Child {
+            name: name.to_owned(),
+            address: address.clone(),
+            mailbox: Some(Arc::new(Mutex::new(receiver))),
+            previous: Arc::new(Mutex::new(None)),
+            renew,
+            thread: None,
+            generation: 0,
+        }));
+        address
+    }
+
+    fn restart(&mut self, failed: usize, context: &Context<SupervisorMessage>) {
+        let now = Instant::now();
+        self.recent.push_back(now);
+        while self.recent.front().is_some_and(|&restart| now.duration_since(restart) > self.period) {
+            self.recent.pop_front();
+        }
+        if self.recent.len() > self.max_restarts {
+            self.stop_children();
+            panic!(
+                "supervisor {} gave up after {} restarts within {:?}",
+                context.myself().name(),
+                self.recent.len(),
+                self.period
+            );
+        }
+
+        self.restarts += 1;
+        let restarted = match self.strategy {
+            Strategy::OneForOne => failed..failed + 1,
+            Strategy::OneForAll => 0..self.children.len(),
+            Strategy::RestForOne => failed..self.children.len(),
+        };
+        for child in restarted.rev() {
+            if self.children[child].is_running() {
+                self.children[child].halt();
+            } else if child != failed {
+                continue;
+            }
+            if !self.restarting.contains(&child) {
+                self.restarting.push(child);
+            }
+        }
+        self.resume(context);
+    }
+
+    /// Starts the children being restarted, in order, once none of them is running.
+    fn resume(&mut self, context: &Context<SupervisorMessage>) {
+        if self.restarting.iter().any(|&child| self.children[child].is_running()) {
+            return;
+        }
+        self.restarting.sort_unstable();
+        for child in std::mem::take(&mut self.restarting) {
+            self.children[child].start(child, context);
+        }
+    }
+
+    /// Stops the children, last first, and waits for them. They are restarted if the
+    /// supervisor is.
+    fn stop_children(&mut self) {
+        let stopped: Vec<usize> = (0..self.children.len())
+            .rev()
+            .filter(|&child| self.children[child].is_running() || self.restarting.contains(&child))
+            .collect();
+        for &child in &stopped {
+            if self.children[child].is_running() {
+                self.children[child].halt();
+            }
+        }
+        for child in stopped {
+            self.children[child].join();
+        }
+        self.restarting.clear();
+    }
+
+    fn terminated(&self, child: usize) {
+        if let Some(registry) = &self.registry {
+            registry.terminated(self.children[child].id());
+        }
+    }
+}
+
+impl Actor for Supervisor {
+    type Message = SupervisorMessage;
+
+    fn handle(&mut self, message: SupervisorMessage, context: &mut Context<SupervisorMessage>) {
+        match message {
+            SupervisorMessage::Restarts(reply_to) => {
+                reply_to.reply(self.restarts);
+            }
+            SupervisorMessage::Exited(exit) => {
+                let child = &mut self.children[exit.child];
+                if exit.generation != child.generation() {
+                    // From a run already waited for by `stop_children`.
+                    return;
+                }
+                child.join();
+                if self.restarting.contains(&exit.child) {
+                    self.resume(context);
+                } else if exit.panicked {
+                    self.restart(exit.child, context);
+                } else {
+                    child.close();
+                    self.terminated(exit.child);
+                }
+            }
+        }
+    }
+
+    fn started(&mut self, context: &mut Context<SupervisorMessage>) {
+        let registry = context.system().registry();
+        for child in &self.children {
+            registry.supervised(context.myself().id(), child.id());
+        }
+        self.registry = Some(registry.clone());
+        self.recent.clear();
+        for (index, child) in self.children.iter_mut().enumerate() {
+            if !child.is_closed() {
+                child.start(index, context);
+            }
+        }
+    }
+
+    fn stopped(&mut self) {
+        self.stop_children();
+    }
+}
+
+/// Gives the instance of a child to run next, from the previous one if it survived.
+type Renew<A> = Box<dyn FnMut(Option<A>) -> A + Send>;
+
+/// The receiving end of a child's mailbox, handed from one instance to the next.
+type Mailbox<M> = Arc<Mutex<Receiver<Envelope<M>>>>;
+
+/// A child, whatever its message type.
+trait Supervised: Send {
+    fn start(&mut self, index: usize, context: &Context<SupervisorMessage>);
+
+    /// Asks the child to stop once it has handled the messages already in its mailbox,
+    /// after which it sends `Exited`.
+    fn halt(&self);
+
+    /// Waits for the thread of a child that exited or was halted.
+    fn join(&mut self);
+
+    /// Drops the mailbox of a child that will not run again, so that sending to it fails.
+    fn close(&mut self);
+
+    fn is_running(&self) -> bool;
+    fn is_closed(&self) -> bool;
+    fn generation(&self) -> u64;
+    fn id(&self) -> u64;
+}
+
+struct Child<A: Actor> {
+    name: String,
+    address: ActorRef<A::Message>,
+    /// Shared by the successive instances; `None` once closed.
+    mailbox: Option<Mailbox<A::Message>>,
+    /// The instance of the last run, only reused by supervisors.
+    previous: Arc<Mutex<Option<A>>>,
+    renew: Renew<A>,
+    thread: Option<JoinHandle<()>>,
+    generation: u64,
+}
+
+impl<A: Actor> Supervised for Child<A> {
+    fn start(&mut self, index: usize, context: &Context<SupervisorMessage>) {
+        self.generation += 1;
+        let mut actor = (self.renew)(lock(&self.previous).take());
+        let mut child_context = Context::new(self.address.clone(), context.system().clone());
+        let mailbox = self.mailbox.clone().expect("closed children are not started");
+        let previous = self.previous.clone();
+        let (supervisor, generation) = (context.myself().clone(), self.generation);
+        let thread = thread::Builder::new()
+            .name(self.name.clone())
+            .spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    run(&mut actor, &lock(&mailbox), &mut child_context, generation)
+                }));
+                *lock(&previous) = Some(actor);
+                let exit = ChildExit { child: index, generation, panicked: result.is_err() };
+                let _ = supervisor.tell(SupervisorMessage::Exited(exit));
+            })
+            .expect("cannot spawn an actor thread");
+        self.thread = Some(thread);
+    }
+
+    fn halt(&self) {
+        self.address.halt(self.generation);
+    }
+
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            // A panic was caught on the thread already.
+            let _ = thread.join();
+        }
+    }
+
+    fn close(&mut self) {
+        self.mailbox = None;
+    }
+
+    fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.mailbox.is_none()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn id(&self) -> u64 {
+        self.address.id()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actors::{ActorSystem, AskError};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    enum Cell {
+        Set(u32),
+        Get(ReplyTo<u32>),
+        Fail,
+        Stop,
+        Sleep(Duration),
+        /// Asks the supervisor for its number of restarts.
+        Restarts(ActorRef<SupervisorMessage>, ReplyTo<Result<usize, AskError>>),
+    }
+
+    struct Register(u32);
+
+    impl Actor for Register {
+        type Message = Cell;
+
+        fn handle(&mut self, message: Cell, context: &mut Context<Cell>) {
+            match message {
+                Cell::Set(value) => self.0 = value,
+                Cell::Get(reply_to) => {
+                    reply_to.reply(self.0);
+                }
+                Cell::Fail => panic!("injected failure"),
+                Cell::Stop => context.stop(),
+                Cell::Sleep(duration) => thread::sleep(duration),
+                Cell::Restarts(supervisor, reply_to) => {
+                    reply_to.reply(supervisor.ask(SupervisorMessage::Restarts, TIMEOUT));
+                }
+            }
+        }
+    }
+
+    fn get(register: &ActorRef<Cell>) -> Result<u32, AskError> {
+        register.ask(Cell::Get, TIMEOUT)
+    }
+
+    /// Starts a supervisor of registers a, b and c, all set to 1.
+    fn registers(system: &ActorSystem, supervisor: Supervisor) -> (ActorRef<SupervisorMessage>, Vec<ActorRef<Cell>>) {
+        let mut supervisor = supervisor;
+        let registers: Vec<_> = ["a", "b", "c"].iter().map(|name| supervisor.child(name, || Register(0))).collect();
+        let supervisor = system.spawn("supervisor", supervisor);
+        for register in &registers {
+            register.tell(Cell::Set(1)).unwrap();
+        }
+        (supervisor, registers)
+    }
+
+    fn values(registers: &[ActorRef<Cell>]) -> Vec<u32> {
+        registers.iter().map(|register| get(register).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_strategies_restart_their_children() {
+        let cases = [(Strategy::OneForOne, [1, 0, 1]), (Strategy::OneForAll, [0, 0, 0]), (Strategy::RestForOne, [1, 0, 0])];
+        for (strategy, expected) in cases {
+            let system = ActorSystem::new();
+            let (supervisor, registers) = registers(&system, Supervisor::new(strategy));
+            registers[1].tell(Cell::Fail).unwrap();
+            // Queued after the failure, so handled by the new instance.
+            registers[1].tell(Cell::Set(0)).unwrap();
+            assert_eq!(Ok(0), get(&registers[1]));
+
+            assert_eq!(expected.to_vec(), values(&registers), "{:?}", strategy);
+            assert_eq!(Ok(1), supervisor.ask(SupervisorMessage::Restarts, TIMEOUT));
+            assert!(system.shutdown().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_supervisor_answers_while_restarting() {
+        let system = ActorSystem::new();
+        let (supervisor, registers) = registers(&system, Supervisor::new(Strategy::OneForAll));
+        // Still in a's mailbox when the supervisor halts it to restart b.
+        registers[0].tell(Cell::Sleep(Duration::from_millis(50))).unwrap();
+        let asked = {
+            let (a, supervisor) = (registers[0].clone(), supervisor.clone());
+            thread::spawn(move || a.ask(|reply_to| Cell::Restarts(supervisor, reply_to), 2 * TIMEOUT))
+        };
+        registers[1].tell(Cell::Fail).unwrap();
+
+        assert_eq!(Ok(Ok(1)), asked.join().unwrap());
+        assert_eq!(vec![0, 0, 0], values(&registers));
+        assert!(system.shutdown().is_empty());
+    }
+
+    #[test]
+    fn test_stopped_child_closes_its_mailbox() {
+        let system = ActorSystem::new();
+        let (supervisor, registers) = registers(&system, Supervisor::new(Strategy::OneForAll));
+        registers[0].tell(Cell::Stop).unwrap();
+        while registers[0].tell(Cell::Set(2)).is_ok() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(Err(AskError::Stopped), get(&registers[0]));
+
+        // Not restarted with its siblings.
+        registers[1].tell(Cell::Fail).unwrap();
+        assert_eq!(Ok(0), get(&registers[1]));
+        assert_eq!(Err(AskError::Stopped), get(&registers[0]));
+        assert_eq!(Ok(1), supervisor.ask(SupervisorMessage::Restarts, TIMEOUT));
+        assert!(system.shutdown().is_empty());
+    }
+
+    #[test]
+    fn test_supervisor_gives_up() {
+        let system = ActorSystem::new();
+        let (_, registers) = registers(&system, Supervisor::new(Strategy::OneForOne).max_restarts(2, TIMEOUT));
+        for _ in 0..2 {
+            registers[0].tell(Cell::Fail).unwrap();
+            assert_eq!(Ok(0), get(&registers[0]));
+        }
+        registers[0].tell(Cell::Fail).unwrap();
+
+        // The children go with the supervisor.
+        while get(&registers[2]).is_ok() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(registers[2].tell(Cell::Set(2)).is_err());
+        assert_eq!(vec!["supervisor".to_string()], system.shutdown());
+    }
+
+    #[test]
+    fn test_nested_supervisor_restarts_its_children() {
+        let system = ActorSystem::new();
+        let mut inner = Supervisor::new(Strategy::OneForOne).max_restarts(0, TIMEOUT);
+        let worker = inner.child("worker", || Register(0));
+        let mut outer = Supervisor::new(Strategy::OneForOne);
+        let inner = outer.supervisor("inner", inner);
+        let sibling = outer.child("sibling", || Register(0));
+        let outer = system.spawn("outer", outer);
+
+        worker.tell(Cell::Set(5)).unwrap();
+        sibling.tell(Cell::Set(5)).unwrap();
+        worker.tell(Cell::Fail).unwrap();
+        assert_eq!(Ok(0), get(&worker));
+        assert_eq!(Ok(5), get(&sibling));
+        assert_eq!(Ok(1), outer.ask(SupervisorMessage::Restarts, TIMEOUT));
+        assert_eq!(Ok(0), inner.ask(SupervisorMessage::Restarts, TIMEOUT));
+        assert!(system.shutdown().is_empty());
+    }
+}
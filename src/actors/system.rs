@@ -0,0 +1,140 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::actors::actor_ref::{ActorRef, Envelope};
+use crate::actors::{Actor, Context, Registry, Timers};
+
+/// A spawned actor, as the system tracks it.
+struct Running {
+    name: String,
+    stop: Box<dyn Fn() + Send>,
+    thread: JoinHandle<()>,
+}
+
+/// Runs every actor on a thread of its own. Clones share the same actors, so a
+/// handle can be given to actors spawning others.
+#[derive(Clone, Default)]
+pub struct ActorSystem {
+    running: Arc<Mutex<Vec<Running>>>,
+    /// Names of the actors that panicked, once their thread is joined.
+    panicked: Arc<Mutex<Vec<String>>>,
+    registry: Registry,
+    timers: Timers,
+}
+
+impl ActorSystem {
+    pub fn new() -> ActorSystem {
+        ActorSystem::default()
+    }
+
+    /// Starts `actor` on a new thread and returns its address. The thread runs until
+    /// the actor is stopped, by itself, through `ActorRef::stop`, or by `shutdown`.
+    pub fn spawn<A: Actor>(&self, name: &str, actor: A) -> ActorRef<A::Message> {
+        let (sender, receiver) = mpsc::channel();
+        let address = ActorRef::new(name, sender);
+        let context = Context::new(address.clone(), self.clone());
+        let thread = thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || {
+                let (mut actor, mut context) = (actor, context);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| run(&mut actor, &receiver, &mut context, 0)));
+                context.system().registry().terminated(context.myself().id());
+                if let Err(panic) = result {
+                    panic::resume_unwind(panic);
+                }
+            })
+            .expect("cannot spawn an actor thread");
+        let stopper = address.clone();
+        self.join_stopped();
+        self.lock().push(Running { name: name.to_owned(), stop: Box::new(move || stopper.stop()), thread });
+        address
+    }
+
+    /// A system whose timers use `timers`, e.g. with a virtual clock.
+    pub fn with_timers(timers: Timers) -> ActorSystem {
+        ActorSystem { timers, ..ActorSystem::default() }
+    }
+
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Names of the actors still running.
+    pub fn actors(&self) -> Vec<String> {
+        self.join_stopped();
+        self.lock().iter().map(|running| running.name.clone()).collect()
+    }
+
+    /// Joins the threads of the actors that have stopped, so that a system spawning
+    /// short-lived actors does not grow.
+    fn join_stopped(&self) {
+        let stopped: Vec<Running> = {
+            let mut running = self.lock();
+            let (stopped, still_running) =
+                std::mem::take(&mut *running).into_iter().partition(|running| running.thread.is_finished());
+            *running = still_running;
+            stopped
+        };
+        for actor in stopped {
+            if actor.thread.join().is_err() {
+                lock(&self.panicked).push(actor.name);
+            }
+        }
+    }
+
+    /// Cancels the pending timers, then stops every actor once it has handled the
+    /// messages already in its mailbox, and waits for their threads, including those of
+    /// actors spawned meanwhile. Returns the names of the actors that panicked, including
+    /// those that had stopped already. Must not be called from an actor.
+    pub fn shutdown(&self) -> Vec<String> {
+        self.timers.shutdown();
+        let mut panicked = std::mem::take(&mut *lock(&self.panicked));
+        loop {
+            let running = std::mem::take(&mut *self.lock());
+            if running.is_empty() {
+                return panicked;
+            }
+            for actor in &running {
+                (actor.stop)();
+            }
+            for actor in running {
+                if actor.thread.join().is_err() {
+                    panicked.push(actor.name);
+                }
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Running>> {
+        lock(&self.running)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Handles the messages of `mailbox` until the actor stops. `generation` tells the
+/// `Halt` meant for this run of the actor from those left over from previous ones.
+pub(super) fn run<A: Actor>(
+    actor: &mut A,
+    mailbox: &Receiver<Envelope<A::Message>>,
+    context: &mut Context<A::Message>,
+    generation: u64,
+) {
+    actor.started(context);
+    while !context.stopping {
+        match mailbox.recv() {
+            Ok(Envelope::Message(message)) => actor.handle(message, context),
+            Ok(Envelope::Halt(halted)) if halted != generation => {}
+            Ok(Envelope::Stop) | Ok(Envelope::Halt(_)) | Err(_) => break,
+        }
+    }
+    actor.stopped();
+}
@@ -0,0 +1,369 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::actors::ActorRef;
+
+/// Delivers messages to actors after a delay or periodically, so that actors never
+/// have to sleep on their own thread.
+///
+/// With a real clock, the timers are fired by a thread started with the first of them,
+/// which ends with `shutdown` or once every `Timers` is dropped. With a virtual clock time
+/// only passes through `advance`, which fires the timers due on the calling thread, making
+/// delays testable without waiting. A timer whose message panics is dropped.
+#[derive(Clone)]
+pub struct Timers {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when a timer is added or the timers shut down.
+    changed: Condvar,
+}
+
+enum Clock {
+    Real(Instant),
+    Virtual(Duration),
+}
+
+struct State {
+    clock: Clock,
+    /// Due times by timer id, cancelled timers included.
+    due: BinaryHeap<Reverse<(Duration, u64)>>,
+    timers: HashMap<u64, Timer>,
+    next_id: u64,
+    thread: Option<JoinHandle<()>>,
+    shut_down: bool,
+}
+
+/// Sends the message of a timer. Returns false once the actor has stopped.
+type Fire = Box<dyn FnMut() -> bool + Send>;
+
+struct Timer {
+    /// Taken out while the timer fires.
+    fire: Option<Fire>,
+    period: Option<Duration>,
+}
+
+/// Longest wait of the timer thread, after which it checks that the timers are still in use.
+const IDLE_WAIT: Duration = Duration::from_millis(100);
+
+impl State {
+    fn now(&self) -> Duration {
+        match self.clock {
+            Clock::Real(start) => start.elapsed(),
+            Clock::Virtual(now) => now,
+        }
+    }
+
+    /// Moves a virtual clock forward to `time`, unless it is there already.
+    fn set_virtual_clock(&mut self, time: Duration) {
+        if let Clock::Virtual(ref mut now) = self.clock {
+            *now = (*now).max(time);
+        }
+    }
+
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Timers::new()
+    }
+}
+
+impl Timers {
+    pub fn new() -> Timers {
+        Timers::with_clock(Clock::Real(Instant::now()))
+    }
+
+    /// Timers whose clock starts at zero and only moves with `advance`.
+    pub fn with_virtual_clock() -> Timers {
+        Timers::with_clock(Clock::Virtual(Duration::ZERO))
+    }
+
+    fn with_clock(clock: Clock) -> Timers {
+        let state = State {
+            clock,
+            due: BinaryHeap::new(),
+            timers: HashMap::new(),
+            next_id: 0,
+            thread: None,
+            shut_down: false,
+        };
+        Timers { shared: Arc::new(Shared { state: Mutex::new(state), changed: Condvar::new() }) }
+    }
+
+    /// Time since the timers were created.
+    pub fn now(&self) -> Duration {
+        self.lock().now()
+    }
+
+    /// Sends `message` to `address` once `delay` has passed.
+    pub fn send_after<M: Send + 'static>(&self, delay: Duration, address: &ActorRef<M>, message: M) -> TimerHandle {
+        let (address, mut message) = (address.clone(), Some(message));
+        let fire = move || message.take().is_some_and(|message| address.tell(message).is_ok());
+        self.add(delay, None, Box::new(fire))
+    }
+
+    /// Sends the message built by `message` to `address` every `period`, starting one
+    /// period from now, until cancelled or the actor stops.
+    pub fn send_interval<M, F>(&self, period: Duration, address: &ActorRef<M>, mut message: F) -> TimerHandle
+    where
+        M: Send + 'static,
+        F: FnMut() -> M + Send + 'static,
+    {
+        assert!(!period.is_zero(), "the period of a timer must be positive");
+        let address = address.clone();
+        self.add(period, Some(period), Box::new(move || address.tell(message()).is_ok()))
+    }
+
+    fn add(&self, delay: Duration, period: Option<Duration>, fire: Fire) -> TimerHandle {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        if !state.shut_down {
+            let due = state.now() + delay;
+            state.due.push(Reverse((due, id)));
+            state.timers.insert(id, Timer { fire: Some(fire), period });
+            if matches!(state.clock, Clock::Real(_)) && state.thread.is_none() {
+                let shared = Arc::downgrade(&self.shared);
+                state.thread = Some(thread::Builder::new()
+                    .name("timers".to_owned())
+                    .spawn(move || fire_on_time(shared))
+                    .expect("cannot spawn the timer thread"));
+            }
+            self.shared.changed.notify_one();
+        }
+        TimerHandle { id, shared: Arc::downgrade(&self.shared) }
+    }
+
+    /// Moves a virtual clock forward by `duration`, firing the timers due meanwhile.
+    pub fn advance(&self, duration: Duration) {
+        let Clock::Virtual(now) = self.lock().clock else {
+            panic!("only a virtual clock can be advanced");
+        };
+        let until = now + duration;
+        self.shared.fire_due(until);
+        self.shared.lock().set_virtual_clock(until);
+    }
+
+    /// Cancels the pending timers and stops the timer thread. Timers added afterwards
+    /// never fire.
+    pub fn shutdown(&self) {
+        let thread = {
+            let mut state = self.lock();
+            state.shut_down = true;
+            state.timers.clear();
+            state.due.clear();
+            state.thread.take()
+        };
+        self.shared.changed.notify_one();
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared.lock()
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Fires the timers due by `now`, in order of due time then creation. They are called
+    /// without the lock held, so that they may use the timers in turn.
+    fn fire_due(&self, now: Duration) {
+        loop {
+            let (due, id, mut fire) = {
+                let mut state = self.lock();
+                let Some(&Reverse((due, id))) = state.due.peek() else {
+                    return;
+                };
+                if due > now {
+                    return;
+                }
+                state.due.pop();
+                let Some(fire) = state.timers.get_mut(&id).and_then(|timer| timer.fire.take()) else {
+                    continue;
+                };
+                state.set_virtual_clock(due);
+                (due, id, fire)
+            };
+            let delivered = panic::catch_unwind(AssertUnwindSafe(&mut fire)).unwrap_or(false);
+
+            let mut state = self.lock();
+            let state = &mut *state;
+            // Gone if cancelled or shut down meanwhile.
+            let Some(timer) = state.timers.get_mut(&id) else {
+                continue;
+            };
+            match timer.period {
+                Some(period) if delivered => {
+                    timer.fire = Some(fire);
+                    state.due.push(Reverse((due + period, id)));
+                }
+                _ => {
+                    state.timers.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+/// Runs the timer thread, until the timers shut down or are dropped.
+fn fire_on_time(shared: Weak<Shared>) {
+    while let Some(shared) = shared.upgrade() {
+        let now = shared.lock().now();
+        shared.fire_due(now);
+        let state = shared.lock();
+        if state.shut_down {
+            return;
+        }
+        let timeout = match state.due.peek() {
+            Some(&Reverse((due, _))) => due.saturating_sub(state.now()).min(IDLE_WAIT),
+            None => IDLE_WAIT,
+        };
+        drop(shared.changed.wait_timeout(state, timeout));
+    }
+}
+
+/// A pending timer. Dropping the handle does not cancel the timer.
+#[derive(Debug, Clone)]
+pub struct TimerHandle {
+    id: u64,
+    shared: Weak<Shared>,
+}
+
+impl TimerHandle {
+    /// Returns false if the timer had already fired for the last time or been cancelled.
+    pub fn cancel(&self) -> bool {
+        self.shared.upgrade().is_some_and(|shared| shared.lock().timers.remove(&self.id).is_some())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actors::{Actor, ActorSystem, Context, ReplyTo};
+
+    enum Log {
+        Entry(&'static str),
+        /// Asks the actor to add `Entry(name)` to itself after `delay`.
+        Later(&'static str, Duration),
+        Read(ReplyTo<Vec<&'static str>>),
+    }
+
+    #[derive(Default)]
+    struct Logger(Vec<&'static str>);
+
+    impl Actor for Logger {
+        type Message = Log;
+
+        fn handle(&mut self, message: Log, context: &mut Context<Log>) {
+            match message {
+                Log::Entry(entry) => self.0.push(entry),
+                Log::Later(entry, delay) => {
+                    context.send_after(delay, Log::Entry(entry));
+                }
+                Log::Read(reply_to) => {
+                    reply_to.reply(self.0.clone());
+                }
+            }
+        }
+    }
+
+    fn read(logger: &ActorRef<Log>) -> Vec<&'static str> {
+        logger.ask(Log::Read, Duration::from_secs(5)).unwrap()
+    }
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_virtual_clock_fires_in_order() {
+        let timers = Timers::with_virtual_clock();
+        let system = ActorSystem::with_timers(timers.clone());
+        let logger = system.spawn("logger", Logger::default());
+        timers.send_after(seconds(3), &logger, Log::Entry("b"));
+        timers.send_after(seconds(1), &logger, Log::Entry("a"));
+        timers.send_after(seconds(3), &logger, Log::Entry("c"));
+        timers.send_after(seconds(2), &logger, Log::Entry("cancelled")).cancel();
+
+        timers.advance(seconds(2));
+        assert_eq!(vec!["a"], read(&logger));
+        timers.advance(seconds(1));
+        assert_eq!(vec!["a", "b", "c"], read(&logger));
+        assert_eq!(seconds(3), timers.now());
+        system.shutdown();
+    }
+
+    #[test]
+    fn test_interval_until_cancelled() {
+        let timers = Timers::with_virtual_clock();
+        let system = ActorSystem::with_timers(timers.clone());
+        let logger = system.spawn("logger", Logger::default());
+        let ticks = timers.send_interval(seconds(2), &logger, || Log::Entry("tick"));
+        timers.advance(seconds(7));
+        assert_eq!(3, read(&logger).len());
+
+        assert!(ticks.cancel());
+        timers.advance(seconds(10));
+        assert_eq!(3, read(&logger).len());
+        assert!(!ticks.cancel());
+        system.shutdown();
+    }
+
+    #[test]
+    fn test_messages_may_use_the_timers() {
+        let timers = Timers::with_virtual_clock();
+        let system = ActorSystem::with_timers(timers.clone());
+        let logger = system.spawn("logger", Logger::default());
+        let clock = timers.clone();
+        timers.send_interval(seconds(2), &logger, move || Log::Entry(if clock.now() < seconds(5) { "early" } else { "late" }));
+        timers.send_interval(seconds(1), &logger, || panic!("injected failure"));
+        timers.send_after(seconds(3), &logger, Log::Entry("after"));
+
+        timers.advance(seconds(7));
+        assert_eq!(vec!["early", "after", "early", "late"], read(&logger));
+        system.shutdown();
+    }
+
+    #[test]
+    fn test_thread_ends_when_the_timers_are_dropped() {
+        let system = ActorSystem::new();
+        let logger = system.spawn("logger", Logger::default());
+        let timers = Timers::new();
+        let handle = timers.send_after(seconds(60), &logger, Log::Entry("never"));
+        drop(timers);
+        while handle.shared.strong_count() > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!handle.cancel());
+        system.shutdown();
+    }
+
+    #[test]
+    fn test_delayed_message_does_not_block_the_actor() {
+        let system = ActorSystem::new();
+        let logger = system.spawn("logger", Logger::default());
+        logger.tell(Log::Later("late", Duration::from_millis(30))).unwrap();
+        logger.tell(Log::Entry("early")).unwrap();
+        assert_eq!(vec!["early"], read(&logger));
+
+        while read(&logger).len() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(vec!["early", "late"], read(&logger));
+        assert!(system.timers().now() >= Duration::from_millis(30));
+        system.shutdown();
+    }
+}
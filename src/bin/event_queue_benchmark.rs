@@ -0,0 +1,79 @@
+//! Hold-model benchmark of the event queue implementations.
+//!
+//! The queue is filled with `size` events, then each hold operation pops the next event
+//! and schedules a new one an exponentially distributed time later, so the queue size
+//! stays constant. Usage: `event_queue_benchmark [size ...]`.
+
+use std::num::NonZeroUsize;
+use std::time::Instant;
+
+use rand::Rng;
+use rust_lab::event_sim::{AnyQueue, Event, EventQueue, QueueKind};
+
+const HOLD_OPERATIONS: usize = 1_000_000;
+
+struct Hold {
+    timestamp: f64,
+}
+
+impl Event for Hold {
+    type TyEventType = ();
+    type TyTime = f64;
+
+    fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    fn set_timestamp(&mut self, timestamp: f64) {
+        self.timestamp = timestamp;
+    }
+
+    fn event_type(&self) {}
+
+    fn event_id(&self) -> String {
+        String::new()
+    }
+
+    fn destination(&self) -> String {
+        String::new()
+    }
+}
+
+fn exponential<R: Rng>(rng: &mut R) -> f64 {
+    -(1.0 - rng.gen::<f64>()).ln()
+}
+
+/// Returns the mean time of a hold operation in nanoseconds, and the final simulation time
+/// so that runs can be checked against each other.
+fn hold_model(kind: QueueKind, size: usize) -> (f64, f64) {
+    let mut queue: AnyQueue<Hold> = AnyQueue::with_seed(kind, 42);
+    for _ in 0..size {
+        let timestamp = exponential(queue.rng());
+        queue.add(Hold { timestamp });
+    }
+
+    let start = Instant::now();
+    let mut now = 0.0;
+    for _ in 0..HOLD_OPERATIONS {
+        now = queue.pop().expect("queue holds events").timestamp;
+        let timestamp = now + exponential(queue.rng());
+        queue.add(Hold { timestamp });
+    }
+    let elapsed = start.elapsed();
+    (elapsed.as_nanos() as f64 / HOLD_OPERATIONS as f64, now)
+}
+
+fn main() {
+    let sizes: Vec<usize> = std::env::args().skip(1)
+        .map(|arg| arg.parse::<NonZeroUsize>().expect("sizes must be positive integers").get())
+        .collect();
+    let sizes = if sizes.is_empty() { vec![1_000, 10_000, 100_000, 1_000_000] } else { sizes };
+
+    println!("{:>10} {:>12} {:>12} {:>16}", "size", "queue", "ns/hold", "final time");
+    for size in sizes {
+        for kind in QueueKind::ALL {
+            let (nanos, now) = hold_model(kind, size);
+            println!("{:>10} {:>12} {:>12.1} {:>16.3}", size, kind.name(), nanos, now);
+        }
+    }
+}
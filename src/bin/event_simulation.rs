@@ -1,90 +1,23 @@
-
-use std::cmp::{Ordering, Reverse};
-use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::any::Any;
+use std::collections::VecDeque;
 use std::error::Error;
-use uuid::Uuid;
-use rust_lab::event_sim::{Event, Actor, EventQueue};
-
-#[derive(Debug, Copy, Clone)]
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use rust_lab::event_sim::{Actor, EventQueue, Message, SimpleQueue, Simulation};
+use rust_lab::event_sim::stats::Statistics;
+use rust_lab::event_sim::trace::TraceWriter;
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 enum EventType {
     Tick,
     Request,
     RequestComplete,
+    Response,
 }
 
 
-#[derive(Debug)]
-struct EventData {
-    timestamp: f32,
-    event_type: EventType,
-    event_id: String,
-    destination: String,
-}
-
-impl PartialEq for EventData {
-    fn eq(&self, other: &Self) -> bool {
-        self.timestamp.eq(&other.timestamp)
-    }
-}
-
-impl Eq for EventData {}
-
-impl PartialOrd for EventData {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for EventData {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let result = Reverse(self.timestamp).partial_cmp(&Reverse(other.timestamp));
-
-        if let Some(order) = result {
-            if let Ordering::Equal = order {
-                let this_char = self.event_id.chars().next().unwrap();
-                let other_char = other.event_id.chars().next().unwrap();
-                return this_char.cmp(&other_char);
-            } else {
-                return order;
-            }
-        } else {
-            panic!("Timestamp cannot be NaN.");
-        }
-    }
-}
-
-
-impl EventData {
-    fn new(timestamp: f32, event_type: EventType, destination: String) -> EventData {
-        EventData {
-            timestamp,
-            event_type,
-            event_id: Uuid::new_v4().to_string(),
-            destination
-        }
-    }
-}
-
-impl Event for EventData {
-    type TyEventType = EventType;
-
-    fn timestamp(&self) -> f32 {
-        self.timestamp
-    }
-
-    fn event_type(&self) -> Self::TyEventType {
-        self.event_type
-    }
-
-    fn event_id(&self) -> String {
-        self.event_id.clone()
-    }
-
-    fn destination(&self) -> String {
-        self.destination.clone()
-    }
-}
-
+type EventData = Message<EventType>;
 
 enum ServerState {
     Idle,
@@ -93,46 +26,22 @@ enum ServerState {
     // Off,
 }
 
-struct SimpleQueue<E> {
-    queue: BinaryHeap<E>
-}
-
-impl<E: Ord + Event> SimpleQueue<E> {
-    fn new() -> SimpleQueue<E> {
-        SimpleQueue {
-            queue: BinaryHeap::new()
-        }
-    }
-}
-
-impl<E: Ord + Event> EventQueue for SimpleQueue<E> {
-    type TyEvent = E;
-    fn add(&mut self, event: E) {
-        self.queue.push(event);
-    }
-
-    fn pop(&mut self) -> Option<E> {
-        self.queue.pop()
-    }
-
-    fn is_empty(&self) -> bool {
-        self.queue.is_empty()
-    }
-}
-
-
 struct RequestGenerator {
     address_id: String,
-    timestamp: f32,
-    max_timestamp: f32
+    server_id: String,
+    timestamp: f64,
+    max_timestamp: f64,
+    stats: Statistics,
 }
 
 impl RequestGenerator {
-    fn new(address_id: String, init_timestamp: f32, max_timestamp: f32) -> RequestGenerator {
+    fn new(address_id: String, server_id: String, init_timestamp: f64, max_timestamp: f64, stats: Statistics) -> RequestGenerator {
         RequestGenerator {
             address_id,
+            server_id,
             timestamp: init_timestamp,
-            max_timestamp
+            max_timestamp,
+            stats,
         }
     }
 }
@@ -147,17 +56,25 @@ impl Actor for RequestGenerator {
 
     fn handle_event(&mut self, event: Self::TyEvent, event_queue: &mut Self::TyQueue) {
         self.timestamp = event.timestamp;
-        let next_timestamp = self.timestamp + 1.0;
-        
-        if next_timestamp <= self.max_timestamp {
-            event_queue.add(
-                EventData::new(next_timestamp, EventType::Tick, self.address_id.clone())
-            );
-        }
 
-        event_queue.add(
-            EventData::new(self.timestamp, EventType::Request, "Server_1".to_owned())
-        );
+        match event.payload {
+            EventType::Tick => {
+                let next_timestamp = self.timestamp + 1.0;
+                if next_timestamp <= self.max_timestamp {
+                    let tick = EventData::new(next_timestamp, &self.address_id, &self.address_id, EventType::Tick, event_queue.message_ids());
+                    event_queue.add(tick);
+                }
+
+                let request = EventData::new(self.timestamp, &self.address_id, &self.server_id, EventType::Request, event_queue.message_ids());
+                event_queue.add(request);
+            }
+            EventType::Response => {
+                let latency = event.latency(self.timestamp);
+                self.stats.tally("request.latency", latency);
+                println!("time: {}, request {} answered after {}", self.timestamp, event.correlation_id, latency);
+            }
+            _ => {}
+        }
     }
 }
 
@@ -166,20 +83,31 @@ struct Server {
     address_id: String,
     state: ServerState,
     queue: VecDeque<EventData>,
-    timestamp: f32,
+    /// Request being served.
+    in_service: Option<EventData>,
+    timestamp: f64,
 }
 
 
 impl Server {
-    fn new(address_id: String, init_timestamp: f32) -> Server {
+    fn new(address_id: String, init_timestamp: f64) -> Server {
         Server {
             address_id,
             state: ServerState::Idle,
             queue: VecDeque::<EventData>::new(),
+            in_service: None,
             timestamp: init_timestamp,
-            
+
         }
     }
+
+    fn start_service(&mut self, request: EventData, event_queue: &mut SimpleQueue<EventData>) {
+        let completion_time = self.timestamp + 10.;
+        let next_event = request.follow_up(completion_time, &self.address_id, EventType::RequestComplete, event_queue.message_ids());
+        event_queue.add(next_event);
+        self.in_service = Some(request);
+        self.state = ServerState::Busy;
+    }
 }
 
 
@@ -194,41 +122,26 @@ impl Actor for Server {
     fn handle_event(&mut self, event: Self::TyEvent, event_queue: &mut Self::TyQueue) {
         self.timestamp = event.timestamp;
 
-
-        match self.state {
-            ServerState::Idle => {
-                if let EventType::Request = event.event_type {
-                    let completion_time = self.timestamp + 10.;
-                    let next_event = EventData::new(
-                        completion_time,
-                        EventType::RequestComplete,
-                        self.address_id.clone()
-                    );
-                    event_queue.add(next_event);
-                    self.state = ServerState::Busy;
-                }
-
-            }
-            ServerState::Busy => {
-                if let EventType::RequestComplete = event.event_type {
-                    if let Some(queued_event) = self.queue.pop_front() {
-                        let completion_time = self.timestamp + 10.;
-                        let next_event = EventData::new(
-                            completion_time,
-                            EventType::RequestComplete,
-                            self.address_id.clone()
-                        );
-                        event_queue.add(next_event);
-                        self.state = ServerState::Busy;
-                    } else {
-                        self.state = ServerState::Idle;   
-                    }
-                } else {
-                    self.queue.push_back(event);
+        match (&self.state, event.payload) {
+            (ServerState::Idle, EventType::Request) => self.start_service(event, event_queue),
+            (ServerState::Busy, EventType::Request) => self.queue.push_back(event),
+            (ServerState::Busy, EventType::RequestComplete) => {
+                let request = self.in_service.take().expect("a busy server has a request in service");
+                let response = request.reply(self.timestamp, EventType::Response, event_queue.message_ids());
+                event_queue.add(response);
+
+                match self.queue.pop_front() {
+                    Some(queued_event) => self.start_service(queued_event, event_queue),
+                    None => self.state = ServerState::Idle,
                 }
             }
+            _ => {}
         }
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
 
@@ -240,37 +153,40 @@ impl Actor for Server {
 fn main() -> Result<(), Box<dyn Error>> {
     let start_sim_timestamp = 0.0;
     let end_sim_timestamp = 60.;
-    let mut event_queue = SimpleQueue::<EventData>::new();
-    let actors: Vec<Box<dyn Actor<TyEvent=EventData, TyQueue=SimpleQueue<EventData>>>> = vec![
-      Box::new(RequestGenerator::new("RequestGenerator".to_owned(), start_sim_timestamp, end_sim_timestamp / 2.)),
-      Box::new(Server::new("Server_1".to_owned(), start_sim_timestamp)),
-    ];
-
-    let mut name_to_actors = HashMap::new();
-    for item in actors.into_iter() {
-        name_to_actors.insert(item.name(), item);
-    }
+    let seed = 42;
+    let stats = Statistics::new();
+    stats.keep_values("request.latency");
+    let mut simulation = Simulation::new(SimpleQueue::<EventData>::with_seed(seed), start_sim_timestamp);
+    simulation.add_actor(Box::new(RequestGenerator::new(
+        "RequestGenerator".to_owned(),
+        "Server_1".to_owned(),
+        start_sim_timestamp,
+        end_sim_timestamp / 2.,
+        stats.clone(),
+    )));
+    simulation.add_actor(Box::new(Server::new("Server_1".to_owned(), start_sim_timestamp)));
 
     // Add seed messages.
-    event_queue.add(EventData::new(
+    let seed_event = EventData::new(
       start_sim_timestamp,
+      "RequestGenerator",
+      "RequestGenerator",
       EventType::Tick,
-      "RequestGenerator".to_owned()
-    ));
-
-    let mut count = 0;
-
-    while !event_queue.is_empty() {
-        if let Some(event) = event_queue.pop() {
-            println!("event: {:?}", event);
+      simulation.queue_mut().message_ids()
+    );
+    simulation.schedule(seed_event)?;
 
-            let actor = name_to_actors.get_mut(&event.destination);
-            if let Some(dest) = actor {
-                dest.handle_event(event, &mut event_queue);
-            }
-        }
-        count += 1;
+    // Optionally record every delivered event, as JSON lines or Arrow IPC (`.arrow`).
+    if let Some(trace_path) = std::env::args().nth(1) {
+        simulation.record_trace(Box::new(TraceWriter::create(Path::new(&trace_path))?));
     }
 
+    simulation.run_until(end_sim_timestamp)?;
+    simulation.finish_trace()?;
+    let server: &Server = simulation.actor_as("Server_1").expect("the server is registered");
+    println!("time: {}, events processed: {}, requests still queued: {}",
+             simulation.now(), simulation.events_processed(), server.queue.len() + server.in_service.iter().count());
+    stats.report(simulation.now()).write_csv(io::stdout())?;
+
     Ok(())
-}
\ No newline at end of file
+}
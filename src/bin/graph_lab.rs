@@ -1,13 +1,12 @@
 
-use std::fs::File;
-use std::io::{self, BufRead, BufWriter, Write};
 use std::path::Path;
 use std::cmp::min;
 use std::ptr::write;
+use rust_lab::graph::AdjList;
+use rust_lab::graph::dot::{Attributes, DotGraph};
+use rust_lab::graph::formats::{read_graph_file, GraphFormat};
 
 
-type AdjList = Vec<Vec<usize>>;
-
 #[derive(Eq, PartialEq)]
 struct Edge {
     start: usize,
@@ -66,41 +65,35 @@ impl Graph {
 
     fn generate_visual(&self, suffix: &str, highlighted_edges: Vec<Edge>) {
         let output_file = format!("/home/ryan/workspace/tmp/graph_data/output_{}.txt", suffix);
-        let mut writer =
-            BufWriter::new(File::create(output_file).expect("Failed to create the output file"));
 
-        writer.write("strict Graph {\n".as_bytes()).expect("Failed to write the output");
-        writer.write("\tnode[shape=\"circle\", style=\"filled\", fillcolor=\"white\"];\n".as_bytes())
-            .expect("Failed to write the output");
+        let mut graph = DotGraph::graph();
+        graph.strict(true)
+            .node_defaults(Attributes::new().set("shape", "circle").set("style", "filled").set("fillcolor", "white"));
 
         // Create nodes
         for i in 1..=self.n {
-            let content = format!("\tx{k}[label=\"{k}\", fillcolor=\"{color}\"];\n", k=i, color=self.colors[i]);
-            writer.write(content.as_bytes()).expect("Failed to write node data.");
+            graph.node(format!("x{}", i), Attributes::new().set("label", i).set("fillcolor", &self.colors[i]));
         }
 
         // Create edges
         for u in 1..=self.n {
             for &v in self.neighbors[u].iter() {
-                let content = format!("\tx{} -- x{} [penwidth=\"{}\"];\n", u, v, Graph::WIDTH_NORMAL_EDGE);
-                writer.write(content.as_bytes()).expect("Failed to write edge data.");
+                graph.edge(format!("x{}", u), format!("x{}", v), Attributes::new().set("penwidth", Graph::WIDTH_NORMAL_EDGE));
             }
         }
 
         for edge in self.visited_edges.iter() {
-            let content = format!("\tx{} -- x{} [penwidth=\"{}\"];\n", edge.start, edge.end, Graph::WIDTH_HIGHLIGHTED_EDGE);
-            writer.write(content.as_bytes()).expect("Failed to write edge data");
+            graph.edge(format!("x{}", edge.start), format!("x{}", edge.end),
+                       Attributes::new().set("penwidth", Graph::WIDTH_HIGHLIGHTED_EDGE));
         }
 
         for Edge{start:u, end:v} in highlighted_edges {
-            let content = format!("\tx{} -- x{} [penwidth=\"{}\", color=\"{}\"];\n", u, v,
-                                  Graph::WIDTH_HIGHLIGHTED_EDGE,
-                                  Graph::COLOR_HIGHLIGHTED_EDGE
-            );
-            writer.write(content.as_bytes()).expect("Failed to write highlighted edge data.");
+            graph.edge(format!("x{}", u), format!("x{}", v), Attributes::new()
+                .set("penwidth", Graph::WIDTH_HIGHLIGHTED_EDGE)
+                .set("color", Graph::COLOR_HIGHLIGHTED_EDGE));
         }
 
-        writer.write("}".as_bytes()).expect("Failed to write to the output file.");
+        graph.write_to_file(Path::new(&output_file)).expect("Failed to write the output file.");
     }
 
     fn build_dfs_tree(&mut self, root: usize) {
@@ -161,22 +154,11 @@ impl Graph {
 
 
 fn main() {
-    let file = File::open("/home/ryan/workspace/tmp/graph_data/graph_5.txt")
-        .expect("Failed to open the file.");
-
-    let mut lines = io::BufReader::new(file).lines();
-    let first_line = lines.next().unwrap().unwrap();
-
-    let n = first_line.parse().unwrap();
-    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); n+1];
-    for i in 1..=n {
-        let line = lines.next().unwrap().expect("Failed to read the line");
-        let parts = line.split(",");
-        let node = &mut neighbors[i];
-        for item in parts {
-            node.push(item.parse().unwrap())
-        }
-    }
+    let path = Path::new("/home/ryan/workspace/tmp/graph_data/graph_5.txt");
+    let neighbors = match read_graph_file(path, GraphFormat::Adjacency) {
+        Ok(graph) => graph.to_adj_list(),
+        Err(error) => panic!("Failed to read {}: {}", path.display(), error),
+    };
 
     let mut graph = Graph::from_adjacent_matrix(neighbors);
     graph.build_dfs_tree(1);
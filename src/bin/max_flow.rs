@@ -1,9 +1,8 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::fs::File;
-use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::rc::Rc;
+use rust_lab::graph::dot::{Attributes, DotGraph, Subgraph};
 
 
 const HL_NODE_COLOR: &str = "#def8fb";
@@ -123,26 +122,24 @@ impl Graph {
         let suffix = format!("{}.{}", self.visual_info.iteration, self.visual_info.bfs_step);
         let output_file = self.output_dir.join(format!("output_{}.txt", suffix));
 
-        let mut writer =
-            BufWriter::new(File::create(output_file).expect("Failed to create the output file"));
+        let mut graph = DotGraph::digraph();
+        graph.strict(true)
+            .attr("rankdir", "LR")
+            .node_defaults(Attributes::new().set("shape", "circle").set("style", "filled").set("fillcolor", "white"));
 
-        writer.write("strict digraph {\n".as_bytes()).expect("Failed to write the output");
-        writer.write("\tnode[shape=\"circle\", style=\"filled\", fillcolor=\"white\"];\n".as_bytes())
-            .expect("Failed to write the output");
-        writer.write("rankdir=LR;\n".as_bytes()).unwrap();
-        writer.write("{rank=same; x2;x3;x4}\n".as_bytes()).unwrap();
-        writer.write("{rank=same; x5;x6;x7}\n".as_bytes()).unwrap();
+        for rank in [[2, 3, 4], [5, 6, 7]] {
+            let mut subgraph = Subgraph::anonymous();
+            subgraph.attr("rank", "same");
+            for node in rank {
+                subgraph.node(format!("x{}", node), Attributes::new());
+            }
+            graph.subgraph(subgraph);
+        }
 
         // Create nodes
         for i in 1..=self.n {
-            let mut color = "white";
-
-            if self.visual_info.hl_nodes.contains_key(&i) {
-                color = self.visual_info.hl_nodes.get(&i).unwrap();
-            }
-
-            let content = format!("\tx{k}[label=\"{k}\", fillcolor=\"{color}\"];\n", k=i, color=color);
-            writer.write(content.as_bytes()).expect("Failed to write node data.");
+            let color = self.visual_info.hl_nodes.get(&i).unwrap_or(&"white");
+            graph.node(format!("x{}", i), Attributes::new().set("label", i).set("fillcolor", color));
         }
 
         // Create edges
@@ -152,13 +149,12 @@ impl Graph {
                     let end = v.edge.borrow().end;
                     let color = self.visual_info.hl_edges.get(&(start, end)).unwrap_or(&"black");
                     let label = format!("{}[{}]", v.edge.borrow().capacity, v.edge.borrow().flow);
-                    let content = format!("\tx{} -> x{} [label=\"{}\", color=\"{}\"];\n", start, end, label, color);
-                    writer.write(content.as_bytes()).expect("Failed to write edge data.");
+                    graph.edge(format!("x{}", start), format!("x{}", end), Attributes::new().set("label", label).set("color", color));
                 }
             }
         }
 
-        writer.write("}".as_bytes()).expect("Failed to write to the output file.");
+        graph.write_to_file(&output_file).expect("Failed to write the output file.");
     }
 
     fn max_flow(&mut self, source: usize, sink: usize) -> f64 {
@@ -0,0 +1,51 @@
+//! The request/server model of `event_simulation`, written as processes: each request is
+//! a process that waits for the server, holds it for the service time and releases it.
+
+use std::error::Error;
+use std::io;
+
+use rust_lab::event_sim::process::{Process, ProcessEvent, ProcessModel};
+use rust_lab::event_sim::resources::Resource;
+use rust_lab::event_sim::stats::Statistics;
+use rust_lab::event_sim::SimpleQueue;
+
+const SERVICE_TIME: f64 = 10.;
+
+async fn request(process: Process, server: Resource, stats: Statistics) {
+    let created = process.now();
+    process.request(&server).await;
+    process.hold(SERVICE_TIME).await;
+    process.release(&server);
+    let latency = process.now() - created;
+    stats.tally("request.latency", latency);
+    println!("time: {}, {} answered after {}", process.now(), process.name(), latency);
+}
+
+async fn generator(process: Process, server: Resource, stats: Statistics, max_timestamp: f64) {
+    let mut count = 0;
+    while process.now() <= max_timestamp {
+        let (server, stats) = (server.clone(), stats.clone());
+        process.spawn(&format!("request {}", count), move |request_process| request(request_process, server, stats));
+        count += 1;
+        process.hold(1.0).await;
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let end_sim_timestamp = 60.;
+    let stats = Statistics::new();
+    stats.keep_values("request.latency");
+    let mut model = ProcessModel::<SimpleQueue<ProcessEvent>>::new();
+    let server = model.resource("Server_1", 1).stats(&stats);
+    {
+        let (server, stats) = (server.clone(), stats.clone());
+        model.process("RequestGenerator", 0.0, move |process| generator(process, server, stats, end_sim_timestamp / 2.));
+    }
+
+    let mut simulation = model.build(SimpleQueue::with_seed(42));
+    simulation.run_until(end_sim_timestamp)?;
+    println!("time: {}, events processed: {}, requests still queued: {}",
+             simulation.now(), simulation.events_processed(), server.queue_length() + server.in_use());
+    stats.report(simulation.now()).write_csv(io::stdout())?;
+    Ok(())
+}
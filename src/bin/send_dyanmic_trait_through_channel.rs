@@ -1,117 +1,103 @@
-use std::sync::mpsc::{Sender, Receiver};
-use std::sync::{Arc, mpsc, Mutex};
-use std::{thread, time};
-use std::any::Any;
-
-// Resources:
-// https://stackoverflow.com/questions/33687447/how-to-get-a-reference-to-a-concrete-type-from-a-trait-object
-// https://doc.rust-lang.org/std/any/index.html
-
-
-
-type MsgSender = Sender<Box<dyn Message + Send>>;
-
-trait Message {
-    fn get_message_id(&self) -> i64;
-    fn get_sender(&self) -> Option<MsgSender>;
-
-    fn as_any(&self) -> &dyn Any;
+use std::time::Duration;
+use std::thread;
+
+use rust_lab::actors::{Actor, ActorRef, ActorSystem, Context, ReplyTo, Strategy, Supervisor};
+
+/// What Toby and Alice say to each other. The mailboxes are typed, so no downcasting
+/// is needed to tell the messages apart.
+enum Message {
+    /// Asks Toby to start talking to the actor registered as `peer`, and to answer with
+    /// the id of his goodbye.
+    Start { peer: &'static str, done: ReplyTo<i64> },
+    /// `sender` is the name the greeter is registered under.
+    Hello { message_id: i64, sender: &'static str },
+    Goodbye { message_id: i64 },
+    /// Sent by Toby to himself, to say goodbye to `peer` a second later.
+    Leave { peer: &'static str },
 }
 
+/// Replies take a second, without keeping the actor from handling other messages.
+const REPLY_DELAY: Duration = Duration::from_secs(1);
 
-struct HelloMessage {
-    message_id: i64,
-    sender: Option<MsgSender>
+fn lookup(context: &Context<Message>, name: &str) -> ActorRef<Message> {
+    context.system().registry().lookup(name).unwrap_or_else(|| panic!("{} is not registered", name))
 }
 
-struct GoodbyeMessage {
+struct Toby {
     message_id: i64,
-    sender: Option<MsgSender>
+    done: Option<ReplyTo<i64>>,
 }
 
+impl Actor for Toby {
+    type Message = Message;
 
-impl Message for HelloMessage {
-    fn get_message_id(&self) -> i64 {
-        self.message_id
-    }
-
-    fn get_sender(&self) -> Option<MsgSender> {
-        self.sender.clone()
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
+    fn handle(&mut self, message: Message, context: &mut Context<Message>) {
+        match message {
+            Message::Start { peer, done } => {
+                self.done = Some(done);
+                lookup(context, peer).tell(Message::Hello { message_id: 10, sender: "toby" }).unwrap();
+            }
+            Message::Hello { message_id, sender } => {
+                println!("[Toby] Received an HelloMessage: {}", message_id);
+                self.message_id += 1;
+                if self.message_id < 110 {
+                    let hello = Message::Hello { message_id: self.message_id, sender: "toby" };
+                    context.system().timers().send_after(REPLY_DELAY, &lookup(context, sender), hello);
+                } else {
+                    context.send_after(REPLY_DELAY, Message::Leave { peer: sender });
+                }
+            }
+            Message::Leave { peer } => {
+                lookup(context, peer).tell(Message::Goodbye { message_id: self.message_id }).unwrap();
+                if let Some(done) = self.done.take() {
+                    done.reply(self.message_id);
+                }
+            }
+            Message::Goodbye { message_id } => println!("[Toby] Received an GoodbyeMessage: {}", message_id),
+        }
     }
 }
 
-impl Message for GoodbyeMessage {
-    fn get_message_id(&self) -> i64 {
-        self.message_id
-    }
-
-    fn get_sender(&self) -> Option<MsgSender> {
-        self.sender.clone()
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+struct Alice {
+    message_id: i64,
 }
 
+impl Actor for Alice {
+    type Message = Message;
 
-
-fn main() {
-    let (sender_toby, receiver_toby): (MsgSender, Receiver<Box<dyn Message + Send>>) = mpsc::channel();
-    let address_toby = sender_toby.clone();
-    let handler_toby = thread::spawn(move || {
-        let mut message_id = 100;
-        while let Ok(msg) = receiver_toby.recv() {
-            if let Some(m) = msg.as_any().downcast_ref::<HelloMessage>() {
-                println!("[Toby] Received an HelloMessage: {}", m.get_message_id());
-                if let Some(sender) = m.get_sender() {
-                    message_id += 1;
-                    thread::sleep(time::Duration::from_secs(1));
-                    if message_id < 110 {
-                        sender.send(Box::new(HelloMessage{message_id: message_id, sender: Some(address_toby.clone())})).unwrap();
-                    } else {
-                        sender.send(Box::new(GoodbyeMessage{message_id: message_id, sender: Some(address_toby.clone())})).unwrap();
-                    }
-                }
-            }
-
-            if let Some(m) = msg.as_any().downcast_ref::<GoodbyeMessage>() {
-                println!("[Toby] Received an GoodbyeMessage: {}", m.get_message_id());
+    fn handle(&mut self, message: Message, context: &mut Context<Message>) {
+        match message {
+            Message::Hello { message_id, sender } => {
+                println!("[Alice] Received an HelloMessage: {}", message_id);
+                self.message_id += 1;
+                let hello = Message::Hello { message_id: self.message_id, sender: "alice" };
+                context.system().timers().send_after(REPLY_DELAY, &lookup(context, sender), hello);
             }
+            Message::Goodbye { message_id } => println!("[Alice] Received an GoodbyeMessage: {}", message_id),
+            Message::Start { .. } | Message::Leave { .. } => println!("[Alice] Only Toby starts and ends conversations."),
         }
-    });
-
-    let (sender_alice, receiver_alice): (MsgSender, Receiver<Box<dyn Message + Send>>) = mpsc::channel();
-    let address_alice = sender_alice.clone();
-    let handler_alice = thread::spawn(move || {
-        let mut message_id = 600;
-        while let Ok(msg) = receiver_alice.recv() {
-            if let Some(m) = msg.as_any().downcast_ref::<HelloMessage>() {
-                println!("[Alice] Received an HelloMessage: {}", m.get_message_id());
-                if let Some(sender) = m.get_sender() {
-                    message_id += 1;
-                    thread::sleep(time::Duration::from_secs(1));
-                    sender.send(Box::new(HelloMessage{message_id: message_id, sender: Some(address_alice.clone())})).unwrap();
-                }
-            }
+    }
+}
 
-            if let Some(m) = msg.as_any().downcast_ref::<GoodbyeMessage>() {
-                println!("[Alice] Received an GoodbyeMessage: {}", m.get_message_id());
-            }
-        }
-    });
 
+fn main() {
+    // Should either of them panic, it is restarted rather than leaving the other waiting.
+    let mut supervisor = Supervisor::new(Strategy::OneForOne);
+    let toby = supervisor.child("toby", || Toby { message_id: 100, done: None });
+    let alice = supervisor.child("alice", || Alice { message_id: 600 });
+    let system = ActorSystem::new();
+    system.registry().register("toby", &toby).unwrap();
+    system.registry().register("alice", &alice).unwrap();
+    system.spawn("supervisor", supervisor);
 
     println!("Wait 3 seconds.");
-    thread::sleep(time::Duration::from_secs(3));
+    thread::sleep(Duration::from_secs(3));
 
-    let start_msg = Box::new(HelloMessage{message_id: 10, sender: Some(sender_alice.clone())});
-    sender_toby.send(start_msg).unwrap();
+    match toby.ask(|done| Message::Start { peer: "alice", done }, Duration::from_secs(60)) {
+        Ok(message_id) => println!("Toby said goodbye with message {}.", message_id),
+        Err(error) => println!("The conversation did not end: {}", error),
+    }
 
-    handler_toby.join().unwrap();
-    handler_alice.join().unwrap();
-}
\ No newline at end of file
+    // Alice handles the goodbye still in her mailbox before stopping.
+    system.shutdown();
+}
@@ -0,0 +1,54 @@
+//! Parameter sweep of an M/M/c queue with independent replications.
+//!
+//! Usage: `simulation_experiment [replications] [output.csv]`. Without an output file the
+//! aggregated statistics are written to stdout.
+
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+use rust_lab::event_sim::experiment::{Configuration, Experiment, ParameterGrid};
+use rust_lab::event_sim::queueing::{Distribution, Network, Sink, Source, Station};
+use rust_lab::event_sim::stats::{Statistics, StatsReport};
+use rust_lab::event_sim::SimpleQueue;
+
+const WARM_UP: f64 = 1_000.0;
+const RUN_LENGTH: f64 = 20_000.0;
+
+fn model(configuration: &Configuration, seed: u64) -> StatsReport {
+    let statistics = Statistics::new();
+    let arrivals = Distribution::Exponential { rate: configuration.value("arrival_rate") };
+    let service = Distribution::Exponential { rate: 1.0 / configuration.value("service_time") };
+    let servers = configuration.value("servers") as usize;
+
+    let mut simulation = Network::new()
+        .source(Source::new("arrivals", arrivals, "server"))
+        .station(Station::new("server", servers, service).to("exit").stats(&statistics))
+        .sink(Sink::new("exit").stats(&statistics))
+        .build(SimpleQueue::with_seed(seed));
+
+    simulation.run_until(WARM_UP).expect("the model only schedules future events");
+    statistics.reset(WARM_UP);
+    simulation.run_until(WARM_UP + RUN_LENGTH).expect("the model only schedules future events");
+    statistics.report(simulation.now())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let replications = match args.next() {
+        Some(arg) => arg.parse()?,
+        None => 10,
+    };
+
+    let grid = ParameterGrid::new()
+        .axis("arrival_rate", vec![0.5, 0.8, 0.95])
+        .axis("service_time", vec![0.5, 1.0])
+        .axis("servers", vec![1.0, 2.0]);
+    let results = Experiment::new(replications, 1).run(&grid, model);
+
+    match args.next() {
+        Some(path) => results.write_csv_file(Path::new(&path))?,
+        None => results.write_csv(io::stdout())?,
+    }
+    Ok(())
+}
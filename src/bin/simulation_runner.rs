@@ -0,0 +1,50 @@
+//! Runs a queueing network described in a JSON or TOML file; see
+//! `rust_lab::event_sim::config` for the format.
+//!
+//! Usage: `simulation_runner model.toml [statistics.csv]`. Without an output file the
+//! statistics are written to stdout.
+
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+
+use rust_lab::event_sim::config::ModelConfig;
+use rust_lab::event_sim::stats::Statistics;
+
+fn run(model_path: &str, output: Option<String>) -> Result<(), Box<dyn Error>> {
+    let model = ModelConfig::load(Path::new(model_path))?;
+    let statistics = Statistics::new();
+    let mut simulation = model.build(&statistics)?;
+
+    simulation.run_until(model.warm_up)?;
+    statistics.reset(model.warm_up);
+    simulation.run_until(model.end_time)?;
+    eprintln!("time: {}, events processed: {}", simulation.now(), simulation.events_processed());
+    let diagnostics = simulation.diagnostics();
+    if !diagnostics.is_clean() {
+        eprintln!("{}", diagnostics);
+    }
+
+    let report = statistics.report(simulation.now());
+    match output {
+        Some(path) => report.write_csv_file(Path::new(&path))?,
+        None => report.write_csv(io::stdout())?,
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(model_path) = args.next() else {
+        eprintln!("usage: simulation_runner <model.toml|model.json> [statistics.csv]");
+        return ExitCode::FAILURE;
+    };
+    match run(&model_path, args.next()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
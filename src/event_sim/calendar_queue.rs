@@ -0,0 +1,285 @@
+use rand::SeedableRng;
+
+use crate::event_sim::checkpoint::{QueueState, RestorableQueue};
+use crate::event_sim::queue::{Key, PendingEvents};
+use crate::event_sim::{Event, EventHandle, EventQueue, MessageIds, SimRng, SimTime, TimeOf};
+
+const MIN_BUCKETS: usize = 2;
+
+/// Number of upcoming events sampled to estimate the bucket width on resize.
+const WIDTH_SAMPLE: usize = 25;
+
+/// Calendar queue (R. Brown, 1988).
+///
+/// Keys are hashed by timestamp into a circular array of "day" buckets of equal width;
+/// one pass over the array is a "year". Each bucket is kept sorted, so when the bucket
+/// width matches the event density both insertion and removal take O(1) on average. The
+/// number of buckets doubles or halves with the number of pending events, and the width
+/// is re-estimated from the spacing of upcoming events at each resize.
+///
+/// Delivery order is identical to `SimpleQueue`.
+pub struct CalendarQueue<E: Event> {
+    /// Each bucket is sorted so that its next key is last.
+    buckets: Vec<Vec<Key<E::TyTime>>>,
+    width: f64,
+    /// Day of the last delivered event, and the bucket it hashes to. No pending event is
+    /// earlier than the current day.
+    current_day: i64,
+    current: usize,
+    /// Keys stored in the buckets, including stale ones.
+    key_count: usize,
+    pending: PendingEvents<E>,
+    rng: SimRng,
+    message_ids: MessageIds,
+}
+
+impl<E: Event> CalendarQueue<E> {
+    pub fn new() -> CalendarQueue<E> {
+        CalendarQueue::with_rng(SimRng::from_entropy())
+    }
+
+    pub fn with_seed(seed: u64) -> CalendarQueue<E> {
+        CalendarQueue::with_rng(SimRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng(rng: SimRng) -> CalendarQueue<E> {
+        CalendarQueue {
+            buckets: (0..MIN_BUCKETS).map(|_| Vec::new()).collect(),
+            width: 1.0,
+            current_day: 0,
+            current: 0,
+            key_count: 0,
+            pending: PendingEvents::new(),
+            message_ids: MessageIds::from_rng(&rng),
+            rng,
+        }
+    }
+
+    fn day(&self, time: f64) -> i64 {
+        (time / self.width).floor() as i64
+    }
+
+    fn bucket_of_day(&self, day: i64) -> usize {
+        day.rem_euclid(self.buckets.len() as i64) as usize
+    }
+
+    /// Makes the day of `time` the current position of the calendar.
+    fn move_to(&mut self, time: f64) {
+        self.current_day = self.day(time);
+        self.current = self.bucket_of_day(self.current_day);
+    }
+
+    fn insert_key(&mut self, key: Key<E::TyTime>) {
+        let day = self.day(key.timestamp.to_f64());
+        // Keys earlier than the current day would only be found a year later.
+        if day < self.current_day {
+            self.move_to(key.timestamp.to_f64());
+        }
+
+        let index = self.bucket_of_day(day);
+        let bucket = &mut self.buckets[index];
+        let position = bucket.partition_point(|other| *other < key);
+        bucket.insert(position, key);
+        self.key_count += 1;
+        self.resize_if_needed();
+    }
+
+    fn discard_stale(&mut self, bucket: usize) {
+        while let Some(key) = self.buckets[bucket].last() {
+            if self.pending.is_live(key) {
+                break;
+            }
+            self.buckets[bucket].pop();
+            self.key_count -= 1;
+        }
+    }
+
+    /// Finds the bucket holding the next key and moves the calendar to it.
+    fn locate_next(&mut self) -> Option<usize> {
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        let bucket_count = self.buckets.len();
+        for offset in 0..bucket_count {
+            let bucket = (self.current + offset) % bucket_count;
+            let day = self.current_day + offset as i64;
+            self.discard_stale(bucket);
+            if let Some(key) = self.buckets[bucket].last() {
+                if self.day(key.timestamp.to_f64()) <= day {
+                    self.current = bucket;
+                    self.current_day = day;
+                    return Some(bucket);
+                }
+            }
+        }
+
+        // Nothing due within a year: the next key is the smallest head of all buckets.
+        let mut next: Option<usize> = None;
+        for bucket in 0..bucket_count {
+            self.discard_stale(bucket);
+            if let Some(key) = self.buckets[bucket].last() {
+                if next.is_none_or(|best| *key > *self.buckets[best].last().expect("bucket is not empty")) {
+                    next = Some(bucket);
+                }
+            }
+        }
+        let bucket = next?;
+        let time = self.buckets[bucket].last().expect("bucket is not empty").timestamp.to_f64();
+        self.move_to(time);
+        Some(bucket)
+    }
+
+    fn resize_if_needed(&mut self) {
+        let bucket_count = self.buckets.len();
+        if self.key_count > 2 * bucket_count {
+            self.resize(2 * bucket_count);
+        } else if bucket_count > MIN_BUCKETS && 2 * self.key_count < bucket_count {
+            self.resize(bucket_count / 2);
+        }
+    }
+
+    /// Rebuilds the calendar with `bucket_count` buckets, dropping stale keys.
+    fn resize(&mut self, bucket_count: usize) {
+        let mut keys: Vec<Key<E::TyTime>> = self.buckets.iter_mut()
+            .flat_map(std::mem::take)
+            .filter(|key| self.pending.is_live(key))
+            .collect();
+        // Earliest first.
+        keys.sort_unstable_by(|a, b| b.cmp(a));
+        let last_time = self.current_day as f64 * self.width;
+
+        // Brown's heuristic: three times the average separation of upcoming events.
+        let sample: Vec<f64> = keys.iter().take(WIDTH_SAMPLE).map(|key| key.timestamp.to_f64()).collect();
+        if sample.len() > 1 {
+            let separation = (sample[sample.len() - 1] - sample[0]) / (sample.len() - 1) as f64;
+            if separation > 0.0 && separation.is_finite() {
+                self.width = 3.0 * separation;
+            }
+        }
+
+        self.buckets = (0..bucket_count).map(|_| Vec::new()).collect();
+        self.key_count = keys.len();
+        match keys.first() {
+            Some(key) => self.move_to(key.timestamp.to_f64()),
+            None => self.move_to(last_time),
+        }
+        // Buckets end with their earliest key, so fill them latest first.
+        for key in keys.into_iter().rev() {
+            let bucket = self.bucket_of_day(self.day(key.timestamp.to_f64()));
+            self.buckets[bucket].push(key);
+        }
+    }
+}
+
+impl<E: Event> Default for CalendarQueue<E> {
+    fn default() -> Self {
+        CalendarQueue::new()
+    }
+}
+
+impl<E: Event> EventQueue for CalendarQueue<E> {
+    type TyEvent = E;
+
+    fn add(&mut self, event: E) -> EventHandle {
+        let (handle, key) = self.pending.add(event);
+        if let Some(key) = key {
+            self.insert_key(key);
+        }
+        handle
+    }
+
+    fn pop(&mut self) -> Option<E> {
+        if let Some(event) = self.pending.take_unordered() {
+            return Some(event);
+        }
+        let bucket = self.locate_next()?;
+        let key = self.buckets[bucket].pop().expect("located bucket is not empty");
+        self.key_count -= 1;
+        let event = self.pending.take(&key);
+        self.resize_if_needed();
+        event
+    }
+
+    fn peek_timestamp(&mut self) -> Option<TimeOf<Self>> {
+        if let Some(timestamp) = self.pending.unordered_timestamp() {
+            return Some(timestamp);
+        }
+        let bucket = self.locate_next()?;
+        self.buckets[bucket].last().map(|key| key.timestamp)
+    }
+
+    fn cancel(&mut self, handle: EventHandle) -> Option<E> {
+        let event = self.pending.cancel(handle)?;
+        if self.key_count > 2 * self.pending.len() + 64 {
+            self.resize(self.buckets.len());
+        }
+        Some(event)
+    }
+
+    fn reschedule(&mut self, handle: EventHandle, timestamp: TimeOf<Self>) -> bool {
+        match self.pending.reschedule(handle, timestamp) {
+            Some(key) => {
+                if let Some(key) = key {
+                    self.insert_key(key);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn rng(&mut self) -> &mut SimRng {
+        &mut self.rng
+    }
+
+    fn message_ids(&mut self) -> &mut MessageIds {
+        &mut self.message_ids
+    }
+}
+
+
+impl<E: Event + Clone> RestorableQueue for CalendarQueue<E> {
+    fn save_state(&self) -> QueueState<E> {
+        self.pending.save(&self.rng, &self.message_ids)
+    }
+
+    fn restore_state(&mut self, state: QueueState<E>) {
+        *self = CalendarQueue::with_rng(state.rng.clone());
+        self.message_ids = state.message_ids.clone();
+        for key in self.pending.restore(state) {
+            self.insert_key(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event_sim::queue::test::{drain, random_workload, Job};
+    use crate::event_sim::SimpleQueue;
+
+    #[test]
+    fn test_same_order_as_simple_queue() {
+        for seed in 0..3 {
+            let expected = random_workload(&mut SimpleQueue::with_seed(0), seed);
+            assert_eq!(expected, random_workload(&mut CalendarQueue::with_seed(0), seed));
+        }
+    }
+
+    #[test]
+    fn test_resize_keeps_events() {
+        let mut queue = CalendarQueue::with_seed(0);
+        for i in (0..1000).rev() {
+            queue.add(Job::new(i as f64 * 0.5, &i.to_string()));
+        }
+        assert!(queue.buckets.len() >= 256);
+        let expected: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+        assert_eq!(expected, drain(&mut queue));
+        assert_eq!(MIN_BUCKETS, queue.buckets.len());
+    }
+}
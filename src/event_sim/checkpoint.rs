@@ -0,0 +1,357 @@
+//! Checkpoints of a running simulation.
+//!
+//! A `Checkpoint` holds the clock, the pending events with their handles and ordering,
+//! the random number generator of the queue, and the state of every actor implementing
+//! `Snapshot`. To resume, the model is built again with fresh actors and
+//! `Simulation::restore` loads the checkpoint into it; the run then continues exactly as
+//! the original one would have.
+//!
+//! Only what is listed above is saved. `Simulation::checkpoint` fails for an actor without
+//! a snapshot unless `Actor::is_stateless` says it carries no state. Stop conditions,
+//! traces, dead-letter handlers and `Statistics` are left as the rebuilt model sets them up.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::event_sim::{EventHandle, EventQueue, MessageIds, SimRng};
+
+/// An actor state that can be saved in a checkpoint. Actors expose it through
+/// `Actor::as_snapshot`:
+///
+/// ```ignore
+/// fn as_snapshot(&mut self) -> Option<&mut dyn DynSnapshot> {
+///     Some(self)
+/// }
+/// ```
+pub trait Snapshot {
+    type State: Serialize + DeserializeOwned;
+
+    fn snapshot(&self) -> Self::State;
+    fn restore(&mut self, state: Self::State);
+}
+
+/// Object-safe form of `Snapshot`, implemented for every `Snapshot` type.
+pub trait DynSnapshot {
+    fn save(&self) -> Result<Value, serde_json::Error>;
+    fn load(&mut self, state: Value) -> Result<(), serde_json::Error>;
+}
+
+impl<T: Snapshot> DynSnapshot for T {
+    fn save(&self) -> Result<Value, serde_json::Error> {
+        serde_json::to_value(self.snapshot())
+    }
+
+    fn load(&mut self, state: Value) -> Result<(), serde_json::Error> {
+        self.restore(serde_json::from_value(state)?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedEvent<E> {
+    pub handle: EventHandle,
+    /// The queue's global sequence number, assigned when the event was added or last
+    /// rescheduled. Breaks ties between equal timestamps and priorities.
+    pub sequence: u64,
+    pub event: E,
+}
+
+/// Content of an event queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueState<E> {
+    /// By sequence number.
+    pub events: Vec<SavedEvent<E>>,
+    pub next_sequence: u64,
+    pub rng: SimRng,
+    pub message_ids: MessageIds,
+}
+
+/// An event queue whose content can be saved and restored.
+pub trait RestorableQueue: EventQueue {
+    fn save_state(&self) -> QueueState<Self::TyEvent>;
+
+    /// Replaces the content of the queue, including its random number generator and
+    /// message ids.
+    fn restore_state(&mut self, state: QueueState<Self::TyEvent>);
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint<E, T> {
+    pub clock: T,
+    pub events_processed: usize,
+    pub queue: QueueState<E>,
+    /// States of the actors implementing `Snapshot`, by name.
+    pub actors: BTreeMap<String, Value>,
+    /// Counters of `Simulation::diagnostics`.
+    pub dead_letters: BTreeMap<String, usize>,
+    pub received: BTreeMap<String, usize>,
+}
+
+impl<E: Serialize, T: Serialize> Checkpoint<E, T> {
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), CheckpointError> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn write_file(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_json(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<E: DeserializeOwned, T: DeserializeOwned> Checkpoint<E, T> {
+    pub fn read_json<R: Read>(reader: R) -> Result<Checkpoint<E, T>, CheckpointError> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn read_file(path: &Path) -> Result<Checkpoint<E, T>, CheckpointError> {
+        Checkpoint::read_json(BufReader::new(File::open(path)?))
+    }
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The checkpoint has the state of an actor the model does not register.
+    UnknownActor(String),
+    /// An actor with state does not implement `Snapshot`, or the checkpoint has the state
+    /// of an actor that does not.
+    NoSnapshot(String),
+    /// The checkpoint lacks the state of an actor implementing `Snapshot`.
+    MissingActor(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(error) => write!(f, "checkpoint I/O error: {}", error),
+            CheckpointError::Json(error) => write!(f, "checkpoint JSON error: {}", error),
+            CheckpointError::UnknownActor(name) => write!(f, "checkpoint has the state of unknown actor {}", name),
+            CheckpointError::NoSnapshot(name) => write!(f, "actor {} cannot save or restore its state", name),
+            CheckpointError::MissingActor(name) => write!(f, "checkpoint lacks the state of actor {}", name),
+        }
+    }
+}
+
+impl Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        CheckpointError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(error: serde_json::Error) -> Self {
+        CheckpointError::Json(error)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::marker::PhantomData;
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::event_sim::{Actor, AnyQueue, Message, QueueKind, Simulation};
+
+    type Ping = Message<u32>;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct RelayState {
+        received: Vec<(f64, u32)>,
+        /// Pending alarm, restarted by every ping.
+        alarm: Option<EventHandle>,
+        alarms: u32,
+    }
+
+    /// Forwards pings to random peers after random delays drawn from the queue, and
+    /// raises an alarm when it has not received any for a while.
+    struct Relay<Q> {
+        name: String,
+        peers: Vec<String>,
+        state: RelayState,
+        _queue: PhantomData<fn() -> Q>,
+    }
+
+    impl<Q> Relay<Q> {
+        fn new(name: &str, peers: &[&str]) -> Relay<Q> {
+            Relay {
+                name: name.to_owned(),
+                peers: peers.iter().map(|peer| peer.to_string()).collect(),
+                state: RelayState { received: Vec::new(), alarm: None, alarms: 0 },
+                _queue: PhantomData,
+            }
+        }
+    }
+
+    impl<Q> Snapshot for Relay<Q> {
+        type State = RelayState;
+
+        fn snapshot(&self) -> RelayState {
+            self.state.clone()
+        }
+
+        fn restore(&mut self, state: RelayState) {
+            self.state = state;
+        }
+    }
+
+    const ALARM: u32 = u32::MAX;
+
+    impl<Q: EventQueue<TyEvent = Ping> + 'static> Actor for Relay<Q> {
+        type TyEvent = Ping;
+        type TyQueue = Q;
+
+        fn handle_event(&mut self, event: Ping, queue: &mut Q) {
+            if event.payload == ALARM {
+                self.state.alarms += 1;
+                self.state.alarm = None;
+                return;
+            }
+            self.state.received.push((event.timestamp, event.payload));
+            if let Some(alarm) = self.state.alarm.take() {
+                queue.cancel(alarm);
+            }
+            let alarm = event.follow_up(event.timestamp + 3.0, &self.name, ALARM, queue.message_ids());
+            self.state.alarm = Some(queue.add(alarm));
+
+            let peer = self.peers[queue.rng().gen_range(0..self.peers.len())].clone();
+            let delay = queue.rng().gen_range(0.0..4.0);
+            let hops = event.payload + 1;
+            let forward = event.follow_up(event.timestamp + delay, &peer, hops, queue.message_ids());
+            queue.add(forward);
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn as_snapshot(&mut self) -> Option<&mut dyn DynSnapshot> {
+            Some(self)
+        }
+
+        fn as_any(&self) -> Option<&dyn std::any::Any> {
+            Some(self)
+        }
+    }
+
+    const RELAYS: [&str; 3] = ["a", "b", "c"];
+
+    fn model(kind: QueueKind) -> Simulation<AnyQueue<Ping>> {
+        let mut simulation = Simulation::new(AnyQueue::with_seed(kind, 5), 0.0);
+        for name in RELAYS {
+            simulation.add_actor(Box::new(Relay::new(name, &RELAYS)));
+        }
+        simulation
+    }
+
+    fn start(simulation: &mut Simulation<AnyQueue<Ping>>) {
+        for name in RELAYS {
+            let ping = Ping::new(0.0, "test", name, 0, simulation.queue_mut().message_ids());
+            simulation.schedule(ping).unwrap();
+        }
+    }
+
+    fn states(simulation: &Simulation<AnyQueue<Ping>>) -> Vec<RelayState> {
+        RELAYS.iter().map(|name| simulation.actor_as::<Relay<AnyQueue<Ping>>>(name).unwrap().state.clone()).collect()
+    }
+
+    #[test]
+    fn test_restored_run_continues_identically() {
+        for kind in QueueKind::ALL {
+            let mut uninterrupted = model(kind);
+            start(&mut uninterrupted);
+            uninterrupted.run_until(200.0).unwrap();
+
+            let mut first_half = model(kind);
+            start(&mut first_half);
+            first_half.run_until(100.0).unwrap();
+            let mut file = Vec::new();
+            first_half.checkpoint().unwrap().write_json(&mut file).unwrap();
+            drop(first_half);
+
+            let mut resumed = model(kind);
+            resumed.restore(Checkpoint::read_json(&file[..]).unwrap()).unwrap();
+            assert_eq!(100.0, resumed.now());
+            resumed.run_until(200.0).unwrap();
+
+            assert_eq!(states(&uninterrupted), states(&resumed), "{}", kind.name());
+            assert_eq!(uninterrupted.events_processed(), resumed.events_processed());
+            assert!(states(&resumed)[0].alarms > 0 && states(&resumed)[0].received.len() > 50);
+        }
+    }
+
+    #[test]
+    fn test_restore_needs_the_same_actors() {
+        let mut simulation = model(QueueKind::BinaryHeap);
+        start(&mut simulation);
+        simulation.run_until(10.0).unwrap();
+        let checkpoint = simulation.checkpoint().unwrap();
+
+        let mut smaller = Simulation::new(AnyQueue::with_seed(QueueKind::BinaryHeap, 5), 0.0);
+        smaller.add_actor(Box::new(Relay::new("a", &RELAYS)));
+        match smaller.restore(checkpoint.clone()) {
+            Err(CheckpointError::UnknownActor(name)) => assert_eq!("b", name),
+            _ => panic!("the checkpoint has actors the model does not register"),
+        }
+
+        let mut larger = model(QueueKind::BinaryHeap);
+        larger.add_actor(Box::new(Relay::new("d", &RELAYS)));
+        match larger.restore(checkpoint) {
+            Err(CheckpointError::MissingActor(name)) => assert_eq!("d", name),
+            _ => panic!("the checkpoint lacks an actor the model registers"),
+        }
+    }
+
+    struct Counter {
+        name: String,
+        count: u32,
+        stateless: bool,
+    }
+
+    impl Actor for Counter {
+        type TyEvent = Ping;
+        type TyQueue = AnyQueue<Ping>;
+
+        fn handle_event(&mut self, _event: Ping, _queue: &mut AnyQueue<Ping>) {
+            self.count += 1;
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn is_stateless(&self) -> bool {
+            self.stateless
+        }
+    }
+
+    #[test]
+    fn test_actors_without_snapshot_need_to_be_stateless() {
+        let mut simulation = model(QueueKind::BinaryHeap);
+        simulation.add_actor(Box::new(Counter { name: "counter".to_owned(), count: 0, stateless: false }));
+        match simulation.checkpoint() {
+            Err(CheckpointError::NoSnapshot(name)) => assert_eq!("counter", name),
+            _ => panic!("the counter's state would be lost"),
+        }
+
+        let mut simulation = model(QueueKind::BinaryHeap);
+        simulation.add_actor(Box::new(Counter { name: "counter".to_owned(), count: 0, stateless: true }));
+        let checkpoint = simulation.checkpoint().unwrap();
+        assert_eq!(RELAYS.len(), checkpoint.actors.len());
+    }
+}
@@ -0,0 +1,488 @@
+//! Queueing network models described in JSON or TOML files.
+//!
+//! A model lists its actors, each with a `type`, a unique `name` and the parameters of
+//! that type, plus the settings of the run:
+//!
+//! ```toml
+//! seed = 42
+//! end_time = 10000.0
+//! warm_up = 1000.0          # optional, statistics are reset at that time
+//! queue = "ladder"          # optional, a `QueueKind` name
+//!
+//! [[actors]]
+//! type = "source"
+//! name = "arrivals"
+//! interarrival = { exponential = { rate = 0.9 } }
+//! to = "server"
+//!
+//! [[actors]]
+//! type = "station"
+//! name = "server"
+//! servers = 1
+//! service = { exponential = { rate = 1.0 } }
+//! buffer = { capacity = 20, overflow = "drop" }
+//! to = "exit"
+//!
+//! [[actors]]
+//! type = "sink"
+//! name = "exit"
+//! ```
+//!
+//! The actor types are `source` (`interarrival`, `to`, optional `class` and `limit`),
+//! `station` (`servers`, `service`, optional `to`, `discipline` and `buffer`), `router`
+//! (`routes`, a list of `{ to, weight }`) and `sink`. Distributions are written
+//! `{ exponential = { rate = 2.0 } }`, `{ deterministic = 0.5 }` or
+//! `{ empirical = [1.0, 2.0] }`.
+//!
+//! `ModelConfig::validate` reports every problem of a model at once: unknown actor types,
+//! invalid parameters, duplicate names and routes to actors that do not exist.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::event_sim::queueing::{Discipline, Distribution, Network, NetworkEvent, Overflow, Router, Sink, Source, Station};
+use crate::event_sim::stats::Statistics;
+use crate::event_sim::{AnyQueue, QueueKind, Simulation};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelConfig {
+    #[serde(default)]
+    pub seed: u64,
+    pub end_time: f64,
+    #[serde(default)]
+    pub warm_up: f64,
+    /// Name of the `QueueKind`; the binary heap by default.
+    #[serde(default)]
+    pub queue: Option<String>,
+    pub actors: Vec<ActorConfig>,
+}
+
+/// An actor as written in the file; its parameters are only checked by `validate`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorConfig {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(flatten)]
+    pub parameters: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SourceParameters {
+    interarrival: Distribution,
+    to: String,
+    #[serde(default)]
+    class: i32,
+    limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BufferParameters {
+    capacity: usize,
+    overflow: Overflow,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StationParameters {
+    servers: usize,
+    service: Distribution,
+    to: Option<String>,
+    discipline: Option<Discipline>,
+    buffer: Option<BufferParameters>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteParameters {
+    to: String,
+    weight: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouterParameters {
+    routes: Vec<RouteParameters>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SinkParameters {}
+
+/// Parameters of an actor, checked against its type.
+#[derive(Debug, Clone, PartialEq)]
+enum ActorSpec {
+    Source(SourceParameters),
+    Station(StationParameters),
+    Router(RouterParameters),
+    Sink,
+}
+
+impl ActorSpec {
+    fn destinations(&self) -> Vec<&str> {
+        match self {
+            ActorSpec::Source(source) => vec![&source.to],
+            ActorSpec::Station(station) => station.to.iter().map(String::as_str).collect(),
+            ActorSpec::Router(router) => router.routes.iter().map(|route| route.to.as_str()).collect(),
+            ActorSpec::Sink => Vec::new(),
+        }
+    }
+
+    /// Values the builders would reject.
+    fn check(&self) -> Result<(), String> {
+        match self {
+            ActorSpec::Source(source) => check_distribution("interarrival", &source.interarrival),
+            ActorSpec::Station(station) if station.servers == 0 => Err("a station needs at least one server".to_owned()),
+            ActorSpec::Station(station) => check_distribution("service", &station.service),
+            ActorSpec::Router(router) if router.routes.is_empty() => Err("a router needs at least one route".to_owned()),
+            ActorSpec::Router(router) => match router.routes.iter().find(|route| !(route.weight >= 0.0 && route.weight.is_finite())) {
+                Some(route) => Err(format!("the weight of the route to {} is not a non-negative number", route.to)),
+                None if router.routes.iter().all(|route| route.weight == 0.0) => Err("the route weights add up to zero".to_owned()),
+                None => Ok(()),
+            },
+            ActorSpec::Sink => Ok(()),
+        }
+    }
+}
+
+fn check_distribution(parameter: &str, distribution: &Distribution) -> Result<(), String> {
+    let valid = match distribution {
+        Distribution::Exponential { rate } => *rate > 0.0 && rate.is_finite(),
+        Distribution::Deterministic(value) => *value >= 0.0 && value.is_finite(),
+        Distribution::Empirical(values) => !values.is_empty() && values.iter().all(|value| *value >= 0.0 && value.is_finite()),
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(format!("invalid {} distribution {:?}", parameter, distribution)),
+    }
+}
+
+/// A problem found by `ModelConfig::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    UnknownActorType { actor: String, kind: String },
+    InvalidParameters { actor: String, message: String },
+    DuplicateActor { actor: String },
+    UnknownDestination { actor: String, destination: String },
+    UnknownQueue { queue: String },
+    InvalidRun { message: String },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::UnknownActorType { actor, kind } => write!(f, "actor {} has unknown type {:?}", actor, kind),
+            ValidationError::InvalidParameters { actor, message } => write!(f, "actor {}: {}", actor, message),
+            ValidationError::DuplicateActor { actor } => write!(f, "several actors are named {}", actor),
+            ValidationError::UnknownDestination { actor, destination } =>
+                write!(f, "actor {} sends to {}, which does not exist", actor, destination),
+            ValidationError::UnknownQueue { queue } => write!(f, "unknown queue {:?}", queue),
+            ValidationError::InvalidRun { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// A file that is not valid JSON or TOML, or does not describe a model.
+    Parse(String),
+    Invalid(Vec<ValidationError>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "model I/O error: {}", error),
+            ConfigError::Parse(message) => write!(f, "cannot parse model: {}", message),
+            ConfigError::Invalid(errors) => {
+                write!(f, "invalid model:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+type Queue = AnyQueue<NetworkEvent>;
+
+impl ModelConfig {
+    pub fn from_json(text: &str) -> Result<ModelConfig, ConfigError> {
+        serde_json::from_str(text).map_err(|error| ConfigError::Parse(error.to_string()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<ModelConfig, ConfigError> {
+        toml::from_str(text).map_err(|error| ConfigError::Parse(error.to_string()))
+    }
+
+    /// Reads a `.toml` file as TOML and any other file as JSON.
+    pub fn load(path: &Path) -> Result<ModelConfig, ConfigError> {
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => ModelConfig::from_toml(&text),
+            _ => ModelConfig::from_json(&text),
+        }
+    }
+
+    pub fn queue_kind(&self) -> Option<QueueKind> {
+        match self.queue.as_deref() {
+            Some(name) => QueueKind::from_name(name),
+            None => Some(QueueKind::BinaryHeap),
+        }
+    }
+
+    fn specs(&self) -> (Vec<(&str, ActorSpec)>, Vec<ValidationError>) {
+        let mut specs = Vec::new();
+        let mut errors = Vec::new();
+        for actor in self.actors.iter() {
+            let parameters = Value::Object(actor.parameters.clone());
+            let spec = match actor.kind.as_str() {
+                "source" => serde_json::from_value(parameters).map(ActorSpec::Source),
+                "station" => serde_json::from_value(parameters).map(ActorSpec::Station),
+                "router" => serde_json::from_value(parameters).map(ActorSpec::Router),
+                "sink" => serde_json::from_value::<SinkParameters>(parameters).map(|_| ActorSpec::Sink),
+                kind => {
+                    errors.push(ValidationError::UnknownActorType { actor: actor.name.clone(), kind: kind.to_owned() });
+                    continue;
+                }
+            };
+            match spec.map_err(|error| error.to_string()).and_then(|spec| spec.check().map(|_| spec)) {
+                Ok(spec) => specs.push((actor.name.as_str(), spec)),
+                Err(message) => errors.push(ValidationError::InvalidParameters { actor: actor.name.clone(), message }),
+            }
+        }
+        (specs, errors)
+    }
+
+    /// Every problem of the model; empty if `build` will succeed.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let (specs, mut errors) = self.specs();
+        if !self.end_time.is_finite() {
+            errors.push(ValidationError::InvalidRun { message: format!("the end time {} is not finite", self.end_time) });
+        }
+        if !(0.0 <= self.warm_up && self.warm_up <= self.end_time) {
+            errors.push(ValidationError::InvalidRun {
+                message: format!("the warm-up {} is not between 0 and the end time {}", self.warm_up, self.end_time),
+            });
+        }
+        if self.queue_kind().is_none() {
+            errors.push(ValidationError::UnknownQueue { queue: self.queue.clone().unwrap_or_default() });
+        }
+        for (index, actor) in self.actors.iter().enumerate() {
+            let first = self.actors.iter().position(|other| other.name == actor.name);
+            if first == Some(index) && self.actors[index + 1..].iter().any(|other| other.name == actor.name) {
+                errors.push(ValidationError::DuplicateActor { actor: actor.name.clone() });
+            }
+        }
+        for (name, spec) in specs.iter() {
+            for destination in spec.destinations() {
+                if !self.actors.iter().any(|actor| actor.name == destination) {
+                    errors.push(ValidationError::UnknownDestination {
+                        actor: name.to_string(),
+                        destination: destination.to_owned(),
+                    });
+                }
+            }
+        }
+        errors
+    }
+
+    /// Builds the network, recording the statistics of its stations and sinks in `stats`.
+    pub fn build(&self, stats: &Statistics) -> Result<Simulation<Queue>, ConfigError> {
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+        let mut network = Network::new();
+        for (name, spec) in self.specs().0 {
+            network = match spec {
+                ActorSpec::Source(parameters) => {
+                    let mut source = Source::new(name, parameters.interarrival, &parameters.to).class(parameters.class);
+                    if let Some(limit) = parameters.limit {
+                        source = source.limit(limit);
+                    }
+                    network.source(source)
+                }
+                ActorSpec::Station(parameters) => {
+                    let mut station = Station::new(name, parameters.servers, parameters.service).stats(stats);
+                    if let Some(destination) = parameters.to {
+                        station = station.to(&destination);
+                    }
+                    if let Some(discipline) = parameters.discipline {
+                        station = station.discipline(discipline);
+                    }
+                    if let Some(buffer) = parameters.buffer {
+                        station = station.buffer(buffer.capacity, buffer.overflow);
+                    }
+                    network.station(station)
+                }
+                ActorSpec::Router(parameters) => {
+                    let router = parameters.routes.iter()
+                        .fold(Router::new(name), |router, route| router.route(&route.to, route.weight));
+                    network.router(router)
+                }
+                ActorSpec::Sink => network.sink(Sink::new(name).stats(stats)),
+            };
+        }
+        let kind = self.queue_kind().expect("the queue was validated");
+        Ok(network.build(AnyQueue::with_seed(kind, self.seed)))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TANDEM: &str = r#"
+        seed = 7
+        end_time = 2000.0
+        warm_up = 100.0
+        queue = "calendar"
+
+        [[actors]]
+        type = "source"
+        name = "arrivals"
+        interarrival = { exponential = { rate = 0.5 } }
+        to = "split"
+
+        [[actors]]
+        type = "router"
+        name = "split"
+        routes = [{ to = "fast", weight = 3.0 }, { to = "slow", weight = 1.0 }]
+
+        [[actors]]
+        type = "station"
+        name = "fast"
+        servers = 1
+        service = { deterministic = 0.5 }
+        to = "exit"
+
+        [[actors]]
+        type = "station"
+        name = "slow"
+        servers = 2
+        service = { empirical = [1.0, 3.0] }
+        discipline = "lifo"
+        buffer = { capacity = 5, overflow = "drop" }
+        to = "exit"
+
+        [[actors]]
+        type = "sink"
+        name = "exit"
+    "#;
+
+    #[test]
+    fn test_toml_and_json_models() {
+        let model = ModelConfig::from_toml(TANDEM).unwrap();
+        assert_eq!(Some(QueueKind::Calendar), model.queue_kind());
+        assert_eq!(Vec::<ValidationError>::new(), model.validate());
+
+        // The same model written as JSON.
+        let json = serde_json::to_string(&model).unwrap();
+        assert_eq!(model, ModelConfig::from_json(&json).unwrap());
+
+        let stats = Statistics::new();
+        let mut simulation = model.build(&stats).unwrap();
+        simulation.run_until(model.end_time).unwrap();
+        let report = stats.report(simulation.now());
+        let departures = report.row("exit.departures").unwrap().count;
+        let arrivals = report.row("fast.arrivals").unwrap().count + report.row("slow.arrivals").unwrap().count;
+        assert!(departures > 900 && departures <= arrivals);
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let model = ModelConfig::from_json(r#"{
+            "end_time": 10.0,
+            "warm_up": 20.0,
+            "queue": "splay",
+            "actors": [
+                {"type": "source", "name": "arrivals", "interarrival": {"deterministic": 1.0}, "to": "server"},
+                {"type": "station", "name": "server", "servers": 0, "service": {"deterministic": 1.0}},
+                {"type": "station", "name": "server", "servers": 1, "service": {"gamma": 2.0}},
+                {"type": "router", "name": "split", "routes": [{"to": "nowhere", "weight": 1.0}]},
+                {"type": "sink", "name": "exit", "capacity": 3},
+                {"type": "teleporter", "name": "beam"}
+            ]
+        }"#).unwrap();
+
+        let errors = model.validate();
+        assert_eq!(8, errors.len(), "{:?}", errors);
+        assert!(errors.contains(&ValidationError::UnknownActorType { actor: "beam".to_owned(), kind: "teleporter".to_owned() }));
+        assert!(errors.contains(&ValidationError::UnknownDestination { actor: "split".to_owned(), destination: "nowhere".to_owned() }));
+        assert!(errors.contains(&ValidationError::DuplicateActor { actor: "server".to_owned() }));
+        assert!(errors.contains(&ValidationError::UnknownQueue { queue: "splay".to_owned() }));
+        let invalid: Vec<&str> = errors.iter()
+            .filter_map(|error| match error {
+                ValidationError::InvalidParameters { actor, .. } => Some(actor.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec!["server", "server", "exit"], invalid);
+
+        match model.build(&Statistics::new()) {
+            Err(ConfigError::Invalid(build_errors)) => assert_eq!(errors, build_errors),
+            _ => panic!("an invalid model must not build"),
+        }
+        assert!(matches!(ModelConfig::from_toml("end_time = "), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn test_limits() {
+        let model = ModelConfig::from_toml(r#"
+            end_time = inf
+
+            [[actors]]
+            type = "router"
+            name = "split"
+            routes = [{ to = "exit", weight = 0.0 }, { to = "exit", weight = 0.0 }]
+
+            [[actors]]
+            type = "router"
+            name = "typo"
+            routes = [{ to = "exit", weigth = 1.0 }]
+
+            [[actors]]
+            type = "station"
+            name = "server"
+            servers = 1
+            service = { deterministic = 1.0 }
+            buffer = { capacity = 5, overflow = "drop", size = 3 }
+
+            [[actors]]
+            type = "sink"
+            name = "exit"
+        "#).unwrap();
+
+        let errors = model.validate();
+        assert_eq!(4, errors.len(), "{:?}", errors);
+        assert!(errors.contains(&ValidationError::InvalidRun { message: "the end time inf is not finite".to_owned() }));
+        assert!(errors.contains(&ValidationError::InvalidParameters {
+            actor: "split".to_owned(),
+            message: "the route weights add up to zero".to_owned(),
+        }));
+        for actor in ["typo", "server"] {
+            assert!(errors.iter().any(|error| matches!(error, ValidationError::InvalidParameters { actor: name, .. } if name == actor)));
+        }
+        assert!(check_distribution("service", &Distribution::Empirical(vec![1.0, f64::INFINITY])).is_err());
+    }
+}
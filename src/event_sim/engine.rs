@@ -0,0 +1,697 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::event_sim::checkpoint::{Checkpoint, CheckpointError, RestorableQueue};
+use crate::event_sim::trace::{TraceError, TraceSink};
+use crate::event_sim::{Actor, Event, EventHandle, EventQueue, SimError, TimeOf};
+
+pub type BoxedActor<Q> = Box<dyn Actor<TyEvent = <Q as EventQueue>::TyEvent, TyQueue = Q>>;
+
+type StopCondition<E> = Box<dyn FnMut(&E) -> bool>;
+
+/// Receives the events addressed to unknown actors, with the queue so that it can
+/// redirect them.
+pub type DeadLetterHandler<Q> = Box<dyn FnMut(<Q as EventQueue>::TyEvent, &mut Q)>;
+
+/// What went unnoticed during a run: see `Simulation::diagnostics`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics<T> {
+    pub events_processed: usize,
+    /// Number of undeliverable events by destination.
+    pub dead_letters: BTreeMap<String, usize>,
+    /// Registered actors that have not received any event, by name.
+    pub idle_actors: Vec<String>,
+    /// Time at which the last `run_until` found the queue empty before its end time; reset
+    /// by every `run_until`.
+    pub queue_empty_at: Option<T>,
+}
+
+impl<T> Diagnostics<T> {
+    pub fn dead_letter_count(&self) -> usize {
+        self.dead_letters.values().sum()
+    }
+
+    /// True if there is nothing to report.
+    pub fn is_clean(&self) -> bool {
+        self.dead_letters.is_empty() && self.idle_actors.is_empty() && self.queue_empty_at.is_none()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Display for Diagnostics<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} events processed", self.events_processed)?;
+        for (destination, count) in self.dead_letters.iter() {
+            write!(f, "\n{} events sent to unknown actor {}", count, destination)?;
+        }
+        for actor in self.idle_actors.iter() {
+            write!(f, "\nactor {} received no events", actor)?;
+        }
+        if let Some(time) = self.queue_empty_at.as_ref() {
+            write!(f, "\nthe event queue emptied at {:?}, before the end of the run", time)?;
+        }
+        Ok(())
+    }
+}
+
+/// Discrete-event simulation engine.
+///
+/// The engine owns the clock, the event queue and the actors. Each step pops the next
+/// event, advances the clock to its timestamp and hands it to the actor named by
+/// `Event::destination`. Events addressed to unknown actors are dead letters: they are
+/// counted and passed to the handler set with `on_dead_letter`, if any, or reported as
+/// `SimError::UndeliverableEvent` after `fail_on_dead_letter`.
+///
+/// The clock never moves backwards: scheduling an event before the current time, either
+/// through `schedule` or by an actor adding it to the queue, is reported as a
+/// `SimError::EventInPast`.
+pub struct Simulation<Q: EventQueue> {
+    clock: TimeOf<Q>,
+    queue: Q,
+    actors: HashMap<String, BoxedActor<Q>>,
+    stop_condition: Option<StopCondition<Q::TyEvent>>,
+    stopped: bool,
+    events_processed: usize,
+    trace: Option<Box<dyn TraceSink<Q::TyEvent>>>,
+    trace_error: Option<TraceError>,
+    dead_letter_handler: Option<DeadLetterHandler<Q>>,
+    fail_on_dead_letter: bool,
+    dead_letters: BTreeMap<String, usize>,
+    /// Number of events received by each actor.
+    received: HashMap<String, usize>,
+    queue_empty_at: Option<TimeOf<Q>>,
+}
+
+impl<Q: EventQueue> Simulation<Q> {
+    pub fn new(queue: Q, start_time: TimeOf<Q>) -> Simulation<Q> {
+        Simulation {
+            clock: start_time,
+            queue,
+            actors: HashMap::new(),
+            stop_condition: None,
+            stopped: false,
+            events_processed: 0,
+            trace: None,
+            trace_error: None,
+            dead_letter_handler: None,
+            fail_on_dead_letter: false,
+            dead_letters: BTreeMap::new(),
+            received: HashMap::new(),
+            queue_empty_at: None,
+        }
+    }
+
+    /// Registers an actor under `Actor::name`, returning the actor it replaces, if any.
+    pub fn add_actor(&mut self, actor: BoxedActor<Q>) -> Option<BoxedActor<Q>> {
+        self.actors.insert(actor.name(), actor)
+    }
+
+    pub fn actor(&self, name: &str) -> Option<&BoxedActor<Q>> {
+        self.actors.get(name)
+    }
+
+    /// The actor registered under `name`, if it has type `T`. See `Actor::as_any`.
+    pub fn actor_as<T: 'static>(&self, name: &str) -> Option<&T> {
+        self.actors.get(name)?.as_any()?.downcast_ref()
+    }
+
+    /// Adds an event to the queue, typically to seed the simulation.
+    pub fn schedule(&mut self, event: Q::TyEvent) -> Result<EventHandle, SimError<TimeOf<Q>>> {
+        self.check_timestamp(&event)?;
+        Ok(self.queue.add(event))
+    }
+
+    /// Cancels a pending event. See `EventQueue::cancel`.
+    pub fn cancel(&mut self, handle: EventHandle) -> Option<Q::TyEvent> {
+        self.queue.cancel(handle)
+    }
+
+    /// Moves a pending event to `timestamp`, which must not be before the clock.
+    /// See `EventQueue::reschedule`.
+    pub fn reschedule(&mut self, handle: EventHandle, timestamp: TimeOf<Q>) -> Result<bool, SimError<TimeOf<Q>>> {
+        let event_id = format!("{:?}", handle);
+        match timestamp.partial_cmp(&self.clock) {
+            None => Err(SimError::InvalidTimestamp { timestamp, event_id }),
+            Some(Ordering::Less) => Err(SimError::EventInPast { now: self.clock, timestamp, event_id }),
+            Some(_) => Ok(self.queue.reschedule(handle, timestamp)),
+        }
+    }
+
+    /// Stops the run after delivering the first event for which `condition` returns true.
+    pub fn stop_when<F>(&mut self, condition: F)
+    where
+        F: FnMut(&Q::TyEvent) -> bool + 'static,
+    {
+        self.stop_condition = Some(Box::new(condition));
+    }
+
+    /// Records every event delivered from now on, before it is handed to its actor.
+    ///
+    /// A failure to record stops the recording without interrupting the run; it is
+    /// reported by `finish_trace`.
+    pub fn record_trace(&mut self, trace: Box<dyn TraceSink<Q::TyEvent>>) {
+        self.trace = Some(trace);
+    }
+
+    /// Flushes and detaches the trace, returning the first error met while recording.
+    pub fn finish_trace(&mut self) -> Result<(), TraceError> {
+        let result = match self.trace.take() {
+            Some(mut trace) => trace.finish(),
+            None => Ok(()),
+        };
+        match self.trace_error.take() {
+            Some(error) => Err(error),
+            None => result,
+        }
+    }
+
+    /// Passes every event addressed to an unknown actor to `handler`.
+    pub fn on_dead_letter<F>(&mut self, handler: F)
+    where
+        F: FnMut(Q::TyEvent, &mut Q) + 'static,
+    {
+        self.dead_letter_handler = Some(Box::new(handler));
+    }
+
+    /// Makes `step` fail with `SimError::UndeliverableEvent` on the first event addressed
+    /// to an unknown actor, instead of passing it to the dead-letter handler.
+    pub fn fail_on_dead_letter(&mut self, fail: bool) {
+        self.fail_on_dead_letter = fail;
+    }
+
+    /// Number of events addressed to unknown actors so far.
+    pub fn dead_letters(&self) -> usize {
+        self.dead_letters.values().sum()
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics<TimeOf<Q>> {
+        let mut idle_actors: Vec<String> = self.actors.keys()
+            .filter(|name| !self.received.contains_key(*name))
+            .cloned()
+            .collect();
+        idle_actors.sort();
+        Diagnostics {
+            events_processed: self.events_processed,
+            dead_letters: self.dead_letters.clone(),
+            idle_actors,
+            queue_empty_at: self.queue_empty_at,
+        }
+    }
+
+    pub fn now(&self) -> TimeOf<Q> {
+        self.clock
+    }
+
+    pub fn events_processed(&self) -> usize {
+        self.events_processed
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn queue(&self) -> &Q {
+        &self.queue
+    }
+
+    pub fn queue_mut(&mut self) -> &mut Q {
+        &mut self.queue
+    }
+
+    fn check_timestamp(&self, event: &Q::TyEvent) -> Result<(), SimError<TimeOf<Q>>> {
+        let timestamp = event.timestamp();
+        match timestamp.partial_cmp(&self.clock) {
+            None => Err(SimError::InvalidTimestamp { timestamp, event_id: event.event_id() }),
+            Some(Ordering::Less) => Err(SimError::EventInPast {
+                now: self.clock,
+                timestamp,
+                event_id: event.event_id(),
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Delivers the next event. Returns false if the queue is empty or the stop condition
+    /// has been met.
+    ///
+    /// An event timestamped before the clock is discarded and reported as an error, like
+    /// an undeliverable event after `fail_on_dead_letter`; the run may be resumed
+    /// afterwards.
+    pub fn step(&mut self) -> Result<bool, SimError<TimeOf<Q>>> {
+        if self.stopped {
+            return Ok(false);
+        }
+        let event = match self.queue.pop() {
+            Some(event) => event,
+            None => return Ok(false),
+        };
+
+        self.check_timestamp(&event)?;
+        self.clock = event.timestamp();
+        if let Some(condition) = self.stop_condition.as_mut() {
+            self.stopped = condition(&event);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            if let Err(error) = trace.record(self.events_processed as u64, &event) {
+                self.trace = None;
+                self.trace_error = Some(error);
+            }
+        }
+
+        let destination = event.destination();
+        match self.actors.get_mut(&destination) {
+            Some(actor) => {
+                *self.received.entry(destination).or_default() += 1;
+                actor.handle_event(event, &mut self.queue);
+            }
+            None => {
+                *self.dead_letters.entry(destination.clone()).or_default() += 1;
+                if self.fail_on_dead_letter {
+                    return Err(SimError::UndeliverableEvent { now: self.clock, destination, event_id: event.event_id() });
+                }
+                if let Some(handler) = self.dead_letter_handler.as_mut() {
+                    handler(event, &mut self.queue);
+                }
+            }
+        }
+        self.events_processed += 1;
+        Ok(true)
+    }
+
+    /// Delivers every event with a timestamp up to and including `end_time`, then advances
+    /// the clock to `end_time`. Returns the number of events delivered.
+    ///
+    /// Events whose timestamp cannot be ordered are delivered first, so that `step`
+    /// reports them.
+    pub fn run_until(&mut self, end_time: TimeOf<Q>) -> Result<usize, SimError<TimeOf<Q>>> {
+        self.queue_empty_at = None;
+        let mut count = 0;
+        while !self.stopped
+            && self.queue.peek_timestamp().is_some_and(|t| t.partial_cmp(&end_time) != Some(Ordering::Greater))
+        {
+            self.step()?;
+            count += 1;
+        }
+        if !self.stopped && self.clock < end_time {
+            if self.queue.is_empty() {
+                self.queue_empty_at = Some(self.clock);
+            }
+            self.clock = end_time;
+        }
+        Ok(count)
+    }
+
+    /// Delivers at most `n` events. Returns the number of events delivered.
+    pub fn run_n(&mut self, n: usize) -> Result<usize, SimError<TimeOf<Q>>> {
+        let mut count = 0;
+        while count < n && self.step()? {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Runs until the queue is empty or the stop condition is met.
+    pub fn run(&mut self) -> Result<usize, SimError<TimeOf<Q>>> {
+        let mut count = 0;
+        while self.step()? {
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+
+impl<Q> Simulation<Q>
+where
+    Q: RestorableQueue,
+    Q::TyEvent: Clone,
+{
+    /// Saves the state of the run; see the `checkpoint` module.
+    pub fn checkpoint(&mut self) -> Result<Checkpoint<Q::TyEvent, TimeOf<Q>>, CheckpointError> {
+        let mut actors = BTreeMap::new();
+        for (name, actor) in self.actors.iter_mut() {
+            if let Some(snapshot) = actor.as_snapshot() {
+                actors.insert(name.clone(), snapshot.save()?);
+            } else if !actor.is_stateless() {
+                return Err(CheckpointError::NoSnapshot(name.clone()));
+            }
+        }
+        Ok(Checkpoint {
+            clock: self.clock,
+            events_processed: self.events_processed,
+            queue: self.queue.save_state(),
+            actors,
+            dead_letters: self.dead_letters.clone(),
+            received: self.received.iter().map(|(name, count)| (name.clone(), *count)).collect(),
+        })
+    }
+
+    /// Resumes a run from `checkpoint`, in a simulation built like the one that saved it.
+    /// Pending events added to this simulation are discarded. After an error, the
+    /// simulation may be partially restored.
+    pub fn restore(&mut self, checkpoint: Checkpoint<Q::TyEvent, TimeOf<Q>>) -> Result<(), CheckpointError> {
+        for (name, actor) in self.actors.iter_mut() {
+            if actor.as_snapshot().is_some() && !checkpoint.actors.contains_key(name) {
+                return Err(CheckpointError::MissingActor(name.clone()));
+            }
+        }
+        for (name, state) in checkpoint.actors {
+            let actor = self.actors.get_mut(&name).ok_or_else(|| CheckpointError::UnknownActor(name.clone()))?;
+            actor.as_snapshot().ok_or_else(|| CheckpointError::NoSnapshot(name.clone()))?.load(state)?;
+        }
+        self.clock = checkpoint.clock;
+        self.events_processed = checkpoint.events_processed;
+        self.queue.restore_state(checkpoint.queue);
+        self.dead_letters = checkpoint.dead_letters;
+        self.received = checkpoint.received.into_iter().collect();
+        self.queue_empty_at = None;
+        self.stopped = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use chrono::TimeDelta;
+    use rand::Rng;
+
+    use super::*;
+    use crate::event_sim::{SimTime, SimpleQueue};
+
+    #[derive(Debug)]
+    struct Ping<T> {
+        timestamp: T,
+        destination: String,
+    }
+
+    impl<T: SimTime> Event for Ping<T> {
+        type TyEventType = ();
+        type TyTime = T;
+
+        fn timestamp(&self) -> T {
+            self.timestamp
+        }
+
+        fn set_timestamp(&mut self, timestamp: T) {
+            self.timestamp = timestamp;
+        }
+
+        fn event_type(&self) {}
+
+        fn event_id(&self) -> String {
+            format!("{:?}", self.timestamp)
+        }
+
+        fn destination(&self) -> String {
+            self.destination.clone()
+        }
+    }
+
+    /// Sends a ping to `peer` `delay` after receiving one, and logs receive times.
+    struct Player<T> {
+        name: String,
+        peer: String,
+        delay: T,
+        received: Rc<RefCell<Vec<(String, T)>>>,
+    }
+
+    impl<T: SimTime> Actor for Player<T> {
+        type TyEvent = Ping<T>;
+        type TyQueue = SimpleQueue<Ping<T>>;
+
+        fn handle_event(&mut self, event: Ping<T>, event_queue: &mut SimpleQueue<Ping<T>>) {
+            self.received.borrow_mut().push((self.name.clone(), event.timestamp));
+            event_queue.add(Ping { timestamp: event.timestamp + self.delay, destination: self.peer.clone() });
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+    }
+
+    type Log<T> = Rc<RefCell<Vec<(String, T)>>>;
+
+    fn ping_pong<T: SimTime + 'static>(start: T, delay: T) -> (Simulation<SimpleQueue<Ping<T>>>, Log<T>) {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let mut simulation = Simulation::new(SimpleQueue::with_seed(0), start);
+        for (name, peer) in [("A", "B"), ("B", "A")] {
+            simulation.add_actor(Box::new(Player {
+                name: name.to_owned(),
+                peer: peer.to_owned(),
+                delay,
+                received: received.clone(),
+            }));
+        }
+        simulation.schedule(Ping { timestamp: start, destination: "A".to_owned() }).unwrap();
+        (simulation, received)
+    }
+
+    #[test]
+    fn test_step() {
+        let (mut simulation, received) = ping_pong(0.0, 1.0);
+        assert!(simulation.step().unwrap());
+        assert!(simulation.step().unwrap());
+        assert_eq!(vec![("A".to_owned(), 0.0), ("B".to_owned(), 1.0)], *received.borrow());
+        assert_eq!(1.0, simulation.now());
+        assert_eq!(2, simulation.events_processed());
+    }
+
+    #[test]
+    fn test_run_until() {
+        let (mut simulation, received) = ping_pong(0.0, 1.0);
+        assert_eq!(5, simulation.run_until(4.5).unwrap());
+        assert_eq!(4.5, simulation.now());
+        assert_eq!(("A".to_owned(), 4.0), received.borrow()[4]);
+        assert_eq!(Some(5.0), simulation.queue_mut().peek_timestamp());
+    }
+
+    #[test]
+    fn test_run_n() {
+        let (mut simulation, _) = ping_pong(0.0, 1.0);
+        assert_eq!(3, simulation.run_n(3).unwrap());
+        assert_eq!(2.0, simulation.now());
+    }
+
+    #[test]
+    fn test_stop_condition() {
+        let (mut simulation, received) = ping_pong(0.0, 1.0);
+        simulation.stop_when(|event| event.timestamp >= 3.0);
+        assert_eq!(4, simulation.run().unwrap());
+        assert!(simulation.is_stopped());
+        assert!(!simulation.step().unwrap());
+        assert_eq!(4, received.borrow().len());
+    }
+
+    #[test]
+    fn test_unknown_destination_is_dropped() {
+        let mut simulation: Simulation<SimpleQueue<Ping<f64>>> = Simulation::new(SimpleQueue::with_seed(0), 0.0);
+        simulation.schedule(Ping { timestamp: 2.0, destination: "nobody".to_owned() }).unwrap();
+        assert_eq!(1, simulation.run().unwrap());
+        assert_eq!(2.0, simulation.now());
+        assert_eq!(1, simulation.dead_letters());
+    }
+
+    #[test]
+    fn test_dead_letter_handler_redirects_events() {
+        let (mut simulation, received) = ping_pong(0.0, 1.0);
+        let dead_letters = Rc::new(RefCell::new(Vec::new()));
+        let handled = dead_letters.clone();
+        simulation.on_dead_letter(move |event: Ping<f64>, queue: &mut SimpleQueue<Ping<f64>>| {
+            handled.borrow_mut().push(event.destination.clone());
+            queue.add(Ping { timestamp: event.timestamp + 0.5, destination: "B".to_owned() });
+        });
+        simulation.schedule(Ping { timestamp: 0.2, destination: "C".to_owned() }).unwrap();
+        simulation.run_until(1.0).unwrap();
+
+        assert_eq!(vec!["C".to_owned()], *dead_letters.borrow());
+        assert_eq!(vec![("A".to_owned(), 0.0), ("B".to_owned(), 0.7), ("B".to_owned(), 1.0)], *received.borrow());
+        assert_eq!(4, simulation.events_processed());
+
+        simulation.fail_on_dead_letter(true);
+        simulation.schedule(Ping { timestamp: 1.5, destination: "C".to_owned() }).unwrap();
+        let error = simulation.run_until(2.0).unwrap_err();
+        assert_eq!(SimError::UndeliverableEvent { now: 1.5, destination: "C".to_owned(), event_id: "1.5".to_owned() }, error);
+        assert_eq!(2, simulation.diagnostics().dead_letters["C"]);
+        assert_eq!(1, dead_letters.borrow().len());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let (mut simulation, _) = ping_pong(0.0, 1.0);
+        simulation.run_until(3.0).unwrap();
+        assert!(simulation.diagnostics().is_clean());
+
+        let mut simulation = Simulation::new(SimpleQueue::with_seed(0), 0.0);
+        for name in ["A", "B"] {
+            simulation.add_actor(Box::new(Player {
+                name: name.to_owned(),
+                peer: "nobody".to_owned(),
+                delay: 1.0,
+                received: Rc::default(),
+            }));
+        }
+        simulation.schedule(Ping { timestamp: 0.0, destination: "A".to_owned() }).unwrap();
+        simulation.run_until(10.0).unwrap();
+
+        let diagnostics = simulation.diagnostics();
+        assert_eq!(Diagnostics {
+            events_processed: 2,
+            dead_letters: BTreeMap::from([("nobody".to_owned(), 1)]),
+            idle_actors: vec!["B".to_owned()],
+            queue_empty_at: Some(1.0),
+        }, diagnostics);
+        assert_eq!("2 events processed\n1 events sent to unknown actor nobody\nactor B received no events\n\
+                    the event queue emptied at 1.0, before the end of the run", diagnostics.to_string());
+
+        // A later run that keeps the queue busy clears the report.
+        simulation.schedule(Ping { timestamp: 12.0, destination: "A".to_owned() }).unwrap();
+        simulation.run_until(12.5).unwrap();
+        assert_eq!(None, simulation.diagnostics().queue_empty_at);
+    }
+
+    #[test]
+    fn test_integer_and_duration_time() {
+        let (mut simulation, _) = ping_pong(10u64, 3u64);
+        simulation.run_n(4).unwrap();
+        assert_eq!(19, simulation.now());
+
+        let (mut simulation, _) = ping_pong(TimeDelta::zero(), TimeDelta::milliseconds(1500));
+        simulation.run_n(3).unwrap();
+        assert_eq!(TimeDelta::seconds(3), simulation.now());
+        assert_eq!(3.0, simulation.now().to_f64());
+    }
+
+    #[test]
+    fn test_event_in_past_is_rejected() {
+        let (mut simulation, _) = ping_pong(0.0, -1.0);
+        simulation.step().unwrap();
+        let error = simulation.step().unwrap_err();
+        assert_eq!(SimError::EventInPast { now: 0.0, timestamp: -1.0, event_id: "-1.0".to_owned() }, error);
+
+        let error = simulation.schedule(Ping { timestamp: -5.0, destination: "A".to_owned() }).unwrap_err();
+        assert!(matches!(error, SimError::EventInPast { .. }));
+
+        let error = simulation.schedule(Ping { timestamp: f64::NAN, destination: "A".to_owned() }).unwrap_err();
+        assert!(matches!(error, SimError::InvalidTimestamp { .. }));
+    }
+
+    #[test]
+    fn test_unordered_timestamp_from_actor_is_reported() {
+        let (mut simulation, _) = ping_pong(0.0, f64::NAN);
+        simulation.step().unwrap();
+        let error = simulation.step().unwrap_err();
+        assert!(matches!(error, SimError::InvalidTimestamp { .. }));
+
+        let (mut simulation, received) = ping_pong(0.0, f64::NAN);
+        let error = simulation.run_until(10.0).unwrap_err();
+        assert!(matches!(error, SimError::InvalidTimestamp { .. }));
+        assert_eq!(1, received.borrow().len());
+        assert_eq!(0.0, simulation.now());
+    }
+
+    /// Forwards every ping to a random player after a random whole number of time units,
+    /// so that many events share a timestamp.
+    struct RandomPlayer {
+        name: String,
+        players: Vec<String>,
+        received: Log<f64>,
+    }
+
+    impl Actor for RandomPlayer {
+        type TyEvent = Ping<f64>;
+        type TyQueue = SimpleQueue<Ping<f64>>;
+
+        fn handle_event(&mut self, event: Ping<f64>, event_queue: &mut SimpleQueue<Ping<f64>>) {
+            self.received.borrow_mut().push((self.name.clone(), event.timestamp));
+            let delay = event_queue.rng().gen_range(0..3) as f64;
+            let destination = self.players[event_queue.rng().gen_range(0..self.players.len())].clone();
+            event_queue.add(Ping { timestamp: event.timestamp + delay, destination });
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+    }
+
+    fn random_trace(seed: u64) -> Vec<(String, f64)> {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let players: Vec<String> = ["A", "B", "C"].iter().map(|name| name.to_string()).collect();
+        let mut simulation = Simulation::new(SimpleQueue::with_seed(seed), 0.0);
+        for name in players.iter() {
+            simulation.add_actor(Box::new(RandomPlayer {
+                name: name.clone(),
+                players: players.clone(),
+                received: received.clone(),
+            }));
+            simulation.schedule(Ping { timestamp: 0.0, destination: name.clone() }).unwrap();
+        }
+        simulation.run_until(50.0).unwrap();
+        received.take()
+    }
+
+    #[test]
+    fn test_seed_reproduces_trace() {
+        let trace = random_trace(42);
+        assert!(trace.len() > 50);
+        assert_eq!(trace, random_trace(42));
+        assert_ne!(trace, random_trace(43));
+    }
+
+    /// Restarts an alarm every time it receives a ping, cancelling the previous one.
+    struct Watchdog {
+        alarm: Option<EventHandle>,
+    }
+
+    impl Actor for Watchdog {
+        type TyEvent = Ping<f64>;
+        type TyQueue = SimpleQueue<Ping<f64>>;
+
+        fn handle_event(&mut self, event: Ping<f64>, event_queue: &mut SimpleQueue<Ping<f64>>) {
+            if let Some(handle) = self.alarm.take() {
+                assert!(event_queue.cancel(handle).is_some());
+            }
+            let alarm = Ping { timestamp: event.timestamp + 10.0, destination: "Alarm".to_owned() };
+            self.alarm = Some(event_queue.add(alarm));
+        }
+
+        fn name(&self) -> String {
+            "Watchdog".to_owned()
+        }
+    }
+
+    #[test]
+    fn test_cancelled_timeouts_are_not_delivered() {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let mut simulation = Simulation::new(SimpleQueue::with_seed(0), 0.0);
+        simulation.add_actor(Box::new(Watchdog { alarm: None }));
+        simulation.add_actor(Box::new(Player {
+            name: "Alarm".to_owned(),
+            peer: "nobody".to_owned(),
+            delay: 0.0,
+            received: received.clone(),
+        }));
+        for timestamp in [0.0, 1.0, 2.0, 3.0] {
+            simulation.schedule(Ping { timestamp, destination: "Watchdog".to_owned() }).unwrap();
+        }
+        let cancelled = simulation.schedule(Ping { timestamp: 4.0, destination: "Watchdog".to_owned() }).unwrap();
+        assert!(simulation.cancel(cancelled).is_some());
+
+        simulation.run().unwrap();
+        assert_eq!(vec![("Alarm".to_owned(), 13.0)], *received.borrow());
+    }
+
+    #[test]
+    fn test_reschedule_before_clock_is_rejected() {
+        let (mut simulation, _) = ping_pong(0.0, 1.0);
+        let handle = simulation.schedule(Ping { timestamp: 5.0, destination: "B".to_owned() }).unwrap();
+        simulation.run_n(3).unwrap();
+        assert!(simulation.reschedule(handle, 1.0).is_err());
+        let error = simulation.reschedule(handle, f64::NAN).unwrap_err();
+        assert!(matches!(error, SimError::InvalidTimestamp { .. }));
+        assert!(simulation.reschedule(handle, 2.5).unwrap());
+        assert_eq!(Some(2.5), simulation.queue_mut().peek_timestamp());
+    }
+}
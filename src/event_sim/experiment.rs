@@ -0,0 +1,295 @@
+//! Independent replications and parameter sweeps.
+//!
+//! An `Experiment` runs a model once per replication for every configuration of a
+//! `ParameterGrid`, spreading the runs over threads. Each run returns a `StatsReport`; the
+//! results aggregate the value of every statistic across replications into a mean and a
+//! confidence interval per configuration.
+//!
+//! Replication `i` of every configuration uses seed `base_seed + i`, wrapping around after
+//! `u64::MAX`, so configurations are compared with common random numbers, and results do
+//! not depend on the thread count.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::event_sim::stats::{student_t_quantile, StatRow, StatsReport, Tally};
+
+/// Values of the model parameters for one run, in the order of the grid axes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Configuration {
+    values: Vec<(String, f64)>,
+}
+
+impl Configuration {
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.values.iter().find(|(key, _)| key == name).map(|(_, value)| *value)
+    }
+
+    /// Like `get`, but panics when the grid has no such parameter.
+    pub fn value(&self, name: &str) -> f64 {
+        self.get(name).unwrap_or_else(|| panic!("No parameter named {}.", name))
+    }
+
+    pub fn values(&self) -> &[(String, f64)] {
+        &self.values
+    }
+}
+
+/// Cartesian product of parameter values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParameterGrid {
+    axes: Vec<(String, Vec<f64>)>,
+}
+
+impl ParameterGrid {
+    pub fn new() -> ParameterGrid {
+        ParameterGrid::default()
+    }
+
+    pub fn axis(mut self, name: &str, values: Vec<f64>) -> ParameterGrid {
+        self.axes.push((name.to_owned(), values));
+        self
+    }
+
+    /// Every combination of values, with the last axis varying fastest. A grid without
+    /// axes has a single, empty configuration.
+    pub fn configurations(&self) -> Vec<Configuration> {
+        let mut configurations = vec![Configuration { values: Vec::new() }];
+        for (name, values) in self.axes.iter() {
+            configurations = configurations.into_iter()
+                .flat_map(|configuration| values.iter().map(move |value| {
+                    let mut configuration = configuration.clone();
+                    configuration.values.push((name.clone(), *value));
+                    configuration
+                }))
+                .collect();
+        }
+        configurations
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.axes.iter().map(|(name, _)| name.clone()).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Experiment {
+    pub replications: usize,
+    pub base_seed: u64,
+    pub threads: usize,
+    /// Confidence level of the intervals across replications.
+    pub level: f64,
+}
+
+impl Experiment {
+    /// Uses every available core.
+    pub fn new(replications: usize, base_seed: u64) -> Experiment {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Experiment { replications, base_seed, threads, level: 0.95 }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Experiment {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Runs `model(configuration, seed)` for every configuration and replication.
+    pub fn run<F>(&self, grid: &ParameterGrid, model: F) -> ExperimentResults
+    where
+        F: Fn(&Configuration, u64) -> StatsReport + Sync,
+    {
+        let configurations = grid.configurations();
+        let runs = configurations.len() * self.replications;
+        let next_run = AtomicUsize::new(0);
+        let reports: Mutex<Vec<Option<StatsReport>>> = Mutex::new(vec![None; runs]);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(runs) {
+                scope.spawn(|| loop {
+                    let run = next_run.fetch_add(1, Ordering::Relaxed);
+                    if run >= runs {
+                        break;
+                    }
+                    let configuration = &configurations[run / self.replications];
+                    let seed = self.base_seed.wrapping_add((run % self.replications) as u64);
+                    let report = model(configuration, seed);
+                    reports.lock().expect("Experiment lock poisoned.")[run] = Some(report);
+                });
+            }
+        });
+
+        let mut reports = reports.into_inner().expect("Experiment lock poisoned.").into_iter();
+        let results = configurations.into_iter()
+            .map(|configuration| {
+                let replications: Vec<StatsReport> = reports.by_ref()
+                    .take(self.replications)
+                    .map(|report| report.expect("every run produces a report"))
+                    .collect();
+                ConfigurationResult::aggregate(configuration, &replications, self.level)
+            })
+            .collect();
+        ExperimentResults { parameters: grid.names(), configurations: results }
+    }
+}
+
+/// The value of a statistic in a single run: the mean for tallies and levels, the count
+/// for counters.
+fn run_value(row: &StatRow) -> f64 {
+    row.mean.unwrap_or(row.count as f64)
+}
+
+/// A statistic aggregated across the replications of a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub replications: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub ci_half_width: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigurationResult {
+    pub configuration: Configuration,
+    pub statistics: BTreeMap<String, Summary>,
+}
+
+impl ConfigurationResult {
+    fn aggregate(configuration: Configuration, reports: &[StatsReport], level: f64) -> ConfigurationResult {
+        let mut tallies: BTreeMap<String, Tally> = BTreeMap::new();
+        for report in reports {
+            for row in report.rows.iter() {
+                tallies.entry(row.name.clone()).or_default().record(run_value(row));
+            }
+        }
+        let statistics = tallies.into_iter()
+            .map(|(name, tally)| {
+                let n = tally.count();
+                let ci_half_width = if n < 2 {
+                    f64::NAN
+                } else {
+                    student_t_quantile(0.5 + level / 2.0, (n - 1) as f64) * tally.std_dev() / (n as f64).sqrt()
+                };
+                let summary = Summary {
+                    replications: n,
+                    mean: tally.mean(),
+                    std_dev: tally.std_dev(),
+                    ci_half_width,
+                    min: tally.min(),
+                    max: tally.max(),
+                };
+                (name, summary)
+            })
+            .collect();
+        ConfigurationResult { configuration, statistics }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentResults {
+    pub parameters: Vec<String>,
+    pub configurations: Vec<ConfigurationResult>,
+}
+
+impl ExperimentResults {
+    /// One line per configuration and statistic: the parameter values, then the summary
+    /// of the statistic across replications.
+    pub fn write_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        let mut header = self.parameters.clone();
+        header.extend(["statistic", "replications", "mean", "std_dev", "ci_half_width", "min", "max"].map(String::from));
+        writer.write_record(&header)?;
+
+        for result in self.configurations.iter() {
+            for (name, summary) in result.statistics.iter() {
+                let mut record: Vec<String> = result.configuration.values().iter().map(|(_, value)| value.to_string()).collect();
+                record.push(name.clone());
+                record.push(summary.replications.to_string());
+                for value in [summary.mean, summary.std_dev, summary.ci_half_width, summary.min, summary.max] {
+                    record.push(value.to_string());
+                }
+                writer.write_record(&record)?;
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn write_csv_file(&self, path: &Path) -> io::Result<()> {
+        self.write_csv(BufWriter::new(File::create(path)?))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event_sim::queueing::{Distribution, Network, Sink, Source, Station};
+    use crate::event_sim::stats::Statistics;
+    use crate::event_sim::SimpleQueue;
+
+    fn mmc(configuration: &Configuration, seed: u64) -> StatsReport {
+        let statistics = Statistics::new();
+        let service = Distribution::Exponential { rate: 1.0 / configuration.value("service_time") };
+        let mut simulation = Network::new()
+            .source(Source::new("arrivals", Distribution::Exponential { rate: configuration.value("arrival_rate") }, "server"))
+            .station(Station::new("server", configuration.value("servers") as usize, service).to("exit").stats(&statistics))
+            .sink(Sink::new("exit").stats(&statistics))
+            .build(SimpleQueue::with_seed(seed));
+        simulation.run_until(500.0).unwrap();
+        statistics.report(simulation.now())
+    }
+
+    fn grid() -> ParameterGrid {
+        ParameterGrid::new()
+            .axis("arrival_rate", vec![0.5, 0.8])
+            .axis("service_time", vec![1.0])
+            .axis("servers", vec![1.0, 2.0])
+    }
+
+    #[test]
+    fn test_grid() {
+        let configurations = grid().configurations();
+        assert_eq!(4, configurations.len());
+        assert_eq!(vec![("arrival_rate".to_owned(), 0.5), ("service_time".to_owned(), 1.0), ("servers".to_owned(), 2.0)],
+                   configurations[1].values());
+        assert_eq!(1, ParameterGrid::new().configurations().len());
+    }
+
+    #[test]
+    fn test_results_do_not_depend_on_threads() {
+        let single = Experiment::new(4, 100).threads(1).run(&grid(), mmc);
+        let parallel = Experiment::new(4, 100).threads(3).run(&grid(), mmc);
+        assert_eq!(single, parallel);
+
+        let utilisation = &single.configurations[0].statistics["server.utilisation"];
+        assert_eq!(4, utilisation.replications);
+        // Distinct seeds give distinct replications.
+        assert!(utilisation.std_dev > 0.0);
+        assert!((utilisation.mean - 0.5).abs() < 3.0 * utilisation.ci_half_width.max(0.01));
+
+        let mut csv = Vec::new();
+        single.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(Some("arrival_rate,service_time,servers,statistic,replications,mean,std_dev,ci_half_width,min,max"),
+                   lines.next());
+        assert!(lines.next().unwrap().starts_with("0.5,1,1,exit.departures,4,"));
+    }
+
+    #[test]
+    fn test_seeds_wrap_around() {
+        let results = Experiment::new(3, u64::MAX - 1).threads(1).run(&ParameterGrid::new(), |_, seed| {
+            let statistics = Statistics::new();
+            statistics.count("seed", seed.wrapping_add(2));
+            statistics.report(0.0)
+        });
+        let seeds = &results.configurations[0].statistics["seed"];
+        assert_eq!((3, 0.0, 1.0, 2.0), (seeds.replications, seeds.min, seeds.mean, seeds.max));
+    }
+}
@@ -0,0 +1,323 @@
+use rand::SeedableRng;
+
+use crate::event_sim::checkpoint::{QueueState, RestorableQueue};
+use crate::event_sim::queue::{Key, PendingEvents};
+use crate::event_sim::{Event, EventHandle, EventQueue, MessageIds, SimRng, SimTime, TimeOf};
+
+/// Buckets with more keys than this are split into a finer rung rather than sorted.
+const THRESHOLD: usize = 50;
+
+const MAX_RUNGS: usize = 8;
+
+/// A level of the ladder: equal-width, unsorted buckets covering a time interval.
+struct Rung<T> {
+    start: f64,
+    width: f64,
+    buckets: Vec<Vec<Key<T>>>,
+    /// Buckets before `current` have been emptied into a finer rung or the bottom.
+    current: usize,
+}
+
+impl<T: SimTime> Rung<T> {
+    /// A rung spanning `[start, start + width * bucket_count)`.
+    fn new(start: f64, width: f64, bucket_count: usize) -> Rung<T> {
+        Rung { start, width, buckets: (0..bucket_count).map(|_| Vec::new()).collect(), current: 0 }
+    }
+
+    fn current_start(&self) -> f64 {
+        self.start + self.width * self.current as f64
+    }
+
+    fn insert(&mut self, key: Key<T>) {
+        let index = ((key.timestamp.to_f64() - self.start) / self.width).floor();
+        let index = (index.max(0.0) as usize).clamp(self.current, self.buckets.len() - 1);
+        self.buckets[index].push(key);
+    }
+}
+
+/// Ladder queue (W. T. Tang, R. S. M. Goh and I. L.-J. Thng, 2005).
+///
+/// New keys far in the future go to an unsorted "top" list. When the earlier part of the
+/// queue runs dry, the top is spread over a rung of buckets; buckets holding more than a
+/// few keys are spread again over a finer rung, and small buckets are sorted into the
+/// "bottom" list that events are delivered from. Unlike the calendar queue, bucket widths
+/// adapt to the local density of events without resizing the whole structure, which
+/// keeps the amortised cost O(1) for skewed timestamp distributions.
+///
+/// Delivery order is identical to `SimpleQueue`.
+pub struct LadderQueue<E: Event> {
+    top: Vec<Key<E::TyTime>>,
+    /// Keys at or after this time go to the top.
+    top_start: f64,
+    /// From coarsest to finest; each rung covers the current bucket of the previous one.
+    rungs: Vec<Rung<E::TyTime>>,
+    /// Sorted so that the next key is last.
+    bottom: Vec<Key<E::TyTime>>,
+    /// Keys stored in the ladder, including stale ones.
+    key_count: usize,
+    pending: PendingEvents<E>,
+    rng: SimRng,
+    message_ids: MessageIds,
+}
+
+impl<E: Event> LadderQueue<E> {
+    pub fn new() -> LadderQueue<E> {
+        LadderQueue::with_rng(SimRng::from_entropy())
+    }
+
+    pub fn with_seed(seed: u64) -> LadderQueue<E> {
+        LadderQueue::with_rng(SimRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng(rng: SimRng) -> LadderQueue<E> {
+        LadderQueue {
+            top: Vec::new(),
+            top_start: f64::NEG_INFINITY,
+            rungs: Vec::new(),
+            bottom: Vec::new(),
+            key_count: 0,
+            pending: PendingEvents::new(),
+            message_ids: MessageIds::from_rng(&rng),
+            rng,
+        }
+    }
+
+    fn insert_key(&mut self, key: Key<E::TyTime>) {
+        self.key_count += 1;
+        let time = key.timestamp.to_f64();
+        // With nothing below the top, any key may start a new epoch there.
+        if time >= self.top_start || (self.rungs.is_empty() && self.bottom.is_empty()) {
+            self.top.push(key);
+            return;
+        }
+        for rung in self.rungs.iter_mut() {
+            if time >= rung.current_start() {
+                rung.insert(key);
+                return;
+            }
+        }
+        let position = self.bottom.partition_point(|other| *other < key);
+        self.bottom.insert(position, key);
+    }
+
+    fn discard_stale(&mut self) {
+        while let Some(key) = self.bottom.last() {
+            if self.pending.is_live(key) {
+                break;
+            }
+            self.bottom.pop();
+            self.key_count -= 1;
+        }
+    }
+
+    /// Moves keys down the ladder until the bottom holds the next live key.
+    fn fill_bottom(&mut self) -> bool {
+        self.discard_stale();
+        while self.bottom.is_empty() {
+            if self.pending.is_empty() {
+                return false;
+            }
+            if self.rungs.is_empty() {
+                self.spread_top();
+            } else {
+                self.advance_rungs();
+            }
+            self.discard_stale();
+        }
+        true
+    }
+
+    /// Starts a new epoch: spreads the top over the first rung.
+    fn spread_top(&mut self) {
+        let keys = std::mem::take(&mut self.top);
+        let (min, max) = time_range(&keys);
+        self.top_start = max.next_up();
+        if keys.len() <= THRESHOLD || max <= min {
+            self.sort_into_bottom(keys);
+            return;
+        }
+        let mut rung = Rung::new(min, (max - min) / keys.len() as f64, keys.len());
+        for key in keys {
+            rung.insert(key);
+        }
+        self.rungs.push(rung);
+    }
+
+    /// Empties the current bucket of the finest rung into a new rung or the bottom.
+    fn advance_rungs(&mut self) {
+        let depth = self.rungs.len();
+        let rung = self.rungs.last_mut().expect("there is a rung");
+        while rung.current < rung.buckets.len() && rung.buckets[rung.current].is_empty() {
+            rung.current += 1;
+        }
+        if rung.current == rung.buckets.len() {
+            self.rungs.pop();
+            return;
+        }
+
+        let bucket_start = rung.current_start();
+        let keys = std::mem::take(&mut rung.buckets[rung.current]);
+        let width = rung.width / keys.len() as f64;
+        rung.current += 1;
+
+        let (min, max) = time_range(&keys);
+        if keys.len() > THRESHOLD && depth < MAX_RUNGS && max > min && width > 0.0 {
+            let mut child = Rung::new(bucket_start, width, keys.len());
+            for key in keys {
+                child.insert(key);
+            }
+            self.rungs.push(child);
+        } else {
+            self.sort_into_bottom(keys);
+        }
+    }
+
+    /// Only called when the bottom is empty.
+    fn sort_into_bottom(&mut self, mut keys: Vec<Key<E::TyTime>>) {
+        let count = keys.len();
+        keys.retain(|key| self.pending.is_live(key));
+        self.key_count -= count - keys.len();
+        keys.sort_unstable();
+        self.bottom = keys;
+    }
+
+    /// Rebuilds the ladder from the live keys once stale keys outnumber them.
+    fn compact_if_needed(&mut self) {
+        if self.key_count <= 2 * self.pending.len() + 64 {
+            return;
+        }
+        let mut keys = std::mem::take(&mut self.top);
+        keys.append(&mut self.bottom);
+        for rung in self.rungs.drain(..) {
+            keys.extend(rung.buckets.into_iter().flatten());
+        }
+        keys.retain(|key| self.pending.is_live(key));
+        self.key_count = keys.len();
+        self.top = keys;
+        self.top_start = f64::NEG_INFINITY;
+    }
+}
+
+fn time_range<T: SimTime>(keys: &[Key<T>]) -> (f64, f64) {
+    keys.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), key| {
+        let time = key.timestamp.to_f64();
+        (min.min(time), max.max(time))
+    })
+}
+
+impl<E: Event> Default for LadderQueue<E> {
+    fn default() -> Self {
+        LadderQueue::new()
+    }
+}
+
+impl<E: Event> EventQueue for LadderQueue<E> {
+    type TyEvent = E;
+
+    fn add(&mut self, event: E) -> EventHandle {
+        let (handle, key) = self.pending.add(event);
+        if let Some(key) = key {
+            self.insert_key(key);
+        }
+        handle
+    }
+
+    fn pop(&mut self) -> Option<E> {
+        if let Some(event) = self.pending.take_unordered() {
+            return Some(event);
+        }
+        if !self.fill_bottom() {
+            return None;
+        }
+        let key = self.bottom.pop().expect("bottom is not empty");
+        self.key_count -= 1;
+        self.pending.take(&key)
+    }
+
+    fn peek_timestamp(&mut self) -> Option<TimeOf<Self>> {
+        if let Some(timestamp) = self.pending.unordered_timestamp() {
+            return Some(timestamp);
+        }
+        if !self.fill_bottom() {
+            return None;
+        }
+        self.bottom.last().map(|key| key.timestamp)
+    }
+
+    fn cancel(&mut self, handle: EventHandle) -> Option<E> {
+        let event = self.pending.cancel(handle)?;
+        self.compact_if_needed();
+        Some(event)
+    }
+
+    fn reschedule(&mut self, handle: EventHandle, timestamp: TimeOf<Self>) -> bool {
+        match self.pending.reschedule(handle, timestamp) {
+            Some(key) => {
+                if let Some(key) = key {
+                    self.insert_key(key);
+                }
+                self.compact_if_needed();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn rng(&mut self) -> &mut SimRng {
+        &mut self.rng
+    }
+
+    fn message_ids(&mut self) -> &mut MessageIds {
+        &mut self.message_ids
+    }
+}
+
+
+impl<E: Event + Clone> RestorableQueue for LadderQueue<E> {
+    fn save_state(&self) -> QueueState<E> {
+        self.pending.save(&self.rng, &self.message_ids)
+    }
+
+    fn restore_state(&mut self, state: QueueState<E>) {
+        *self = LadderQueue::with_rng(state.rng.clone());
+        self.message_ids = state.message_ids.clone();
+        for key in self.pending.restore(state) {
+            self.insert_key(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event_sim::queue::test::{drain, random_workload, Job};
+    use crate::event_sim::SimpleQueue;
+
+    #[test]
+    fn test_same_order_as_simple_queue() {
+        for seed in 0..3 {
+            let expected = random_workload(&mut SimpleQueue::with_seed(0), seed);
+            assert_eq!(expected, random_workload(&mut LadderQueue::with_seed(0), seed));
+        }
+    }
+
+    #[test]
+    fn test_dense_buckets_spawn_rungs() {
+        let mut queue = LadderQueue::with_seed(0);
+        // Most events are crowded into the first bucket of the first rung.
+        for i in 0..1000 {
+            queue.add(Job::new(i as f64 * 1e-3, &i.to_string()));
+        }
+        queue.add(Job::new(1e6, "last"));
+        assert_eq!(Some(0.0), queue.peek_timestamp());
+        assert!(queue.rungs.len() > 1);
+
+        let mut expected: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+        expected.push("last".to_owned());
+        assert_eq!(expected, drain(&mut queue));
+    }
+}
@@ -0,0 +1,179 @@
+//! Events carrying a typed payload between named actors.
+//!
+//! Every message has a unique id. Messages sent in response to another one keep its
+//! correlation id, which identifies the whole exchange, and record it as their cause, so
+//! that an actor can reply to whoever started an exchange and measure its end-to-end
+//! latency from `origin_time`.
+//!
+//! Ids come from `MessageIds`, usually the one of the event queue, rather than from the
+//! model's random number generator, so that sending messages does not change the random
+//! numbers drawn by the model.
+
+use std::fmt;
+
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::event_sim::{Event, SimRng, SimTime};
+
+/// Stream of the random number generator reserved for message ids.
+const MESSAGE_ID_STREAM: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MessageId(pub u64);
+
+/// Generator of message ids, reproducible from a seed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageIds {
+    rng: SimRng,
+}
+
+impl MessageIds {
+    pub fn with_seed(seed: u64) -> MessageIds {
+        MessageIds::from_rng(&SimRng::seed_from_u64(seed))
+    }
+
+    /// Ids drawn from the seed of `rng` on a stream of their own; `rng` is left untouched.
+    pub fn from_rng(rng: &SimRng) -> MessageIds {
+        let mut rng = rng.clone();
+        rng.set_stream(MESSAGE_ID_STREAM);
+        MessageIds { rng }
+    }
+
+    pub fn next_id(&mut self) -> MessageId {
+        MessageId(self.rng.gen())
+    }
+}
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message<P, T = f64> {
+    pub timestamp: T,
+    pub priority: i32,
+    pub id: MessageId,
+    /// Id of the message that started the exchange.
+    pub correlation_id: MessageId,
+    /// Id of the message this one responds to, if any.
+    pub causation_id: Option<MessageId>,
+    /// Time the exchange started.
+    pub origin_time: T,
+    /// Actor that sent the message.
+    pub source: String,
+    pub destination: String,
+    pub payload: P,
+}
+
+impl<P, T: SimTime> Message<P, T> {
+    /// Starts a new exchange.
+    pub fn new(timestamp: T, source: &str, destination: &str, payload: P, ids: &mut MessageIds) -> Message<P, T> {
+        let id = ids.next_id();
+        Message {
+            timestamp,
+            priority: 0,
+            id,
+            correlation_id: id,
+            causation_id: None,
+            origin_time: timestamp,
+            source: source.to_owned(),
+            destination: destination.to_owned(),
+            payload,
+        }
+    }
+
+    /// A message in the same exchange, caused by this one and sent by its destination.
+    pub fn follow_up(&self, timestamp: T, destination: &str, payload: P, ids: &mut MessageIds) -> Message<P, T> {
+        Message {
+            timestamp,
+            priority: 0,
+            id: ids.next_id(),
+            correlation_id: self.correlation_id,
+            causation_id: Some(self.id),
+            origin_time: self.origin_time,
+            source: self.destination.clone(),
+            destination: destination.to_owned(),
+            payload,
+        }
+    }
+
+    /// A follow-up sent back to the source of this message.
+    pub fn reply(&self, timestamp: T, payload: P, ids: &mut MessageIds) -> Message<P, T> {
+        self.follow_up(timestamp, &self.source, payload, ids)
+    }
+
+    /// Time from the start of the exchange to `now`.
+    pub fn latency(&self, now: T) -> T {
+        now - self.origin_time
+    }
+}
+
+impl<P: Clone, T: SimTime> Event for Message<P, T> {
+    type TyEventType = P;
+    type TyTime = T;
+
+    fn timestamp(&self) -> T {
+        self.timestamp
+    }
+
+    fn set_timestamp(&mut self, timestamp: T) {
+        self.timestamp = timestamp;
+    }
+
+    fn event_type(&self) -> P {
+        self.payload.clone()
+    }
+
+    fn event_id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn destination(&self) -> String {
+        self.destination.clone()
+    }
+
+    fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_correlation() {
+        let mut ids = MessageIds::with_seed(0);
+        let request = Message::new(1.0, "client", "proxy", "get", &mut ids);
+        let forwarded = request.follow_up(1.5, "server", "get", &mut ids);
+        let response = forwarded.reply(4.0, "ok", &mut ids);
+
+        assert_eq!(("proxy", "server"), (forwarded.source.as_str(), forwarded.destination.as_str()));
+        assert_eq!(("server", "proxy"), (response.source.as_str(), response.destination.as_str()));
+        for message in [&forwarded, &response] {
+            assert_eq!(request.id, message.correlation_id);
+            assert_ne!(request.id, message.id);
+        }
+        assert_eq!(Some(forwarded.id), response.causation_id);
+        assert_eq!(None, request.causation_id);
+        assert_eq!(3.0, response.latency(response.timestamp));
+    }
+
+    #[test]
+    fn test_ids_leave_the_model_rng_alone() {
+        let mut rng = SimRng::seed_from_u64(7);
+        let mut ids = MessageIds::from_rng(&rng);
+        let first = ids.next_id();
+        assert_eq!(SimRng::seed_from_u64(7).gen::<u64>(), rng.gen::<u64>());
+        assert_ne!(first, ids.next_id());
+        assert_eq!(first, MessageIds::with_seed(7).next_id());
+    }
+}
@@ -0,0 +1,113 @@
+mod calendar_queue;
+pub mod checkpoint;
+pub mod config;
+mod engine;
+pub mod experiment;
+mod ladder_queue;
+pub mod parallel;
+pub mod process;
+pub mod message;
+mod queue;
+pub mod queueing;
+pub mod resources;
+pub mod stats;
+mod time;
+pub mod trace;
+
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event_sim::checkpoint::DynSnapshot;
+
+pub use engine::{BoxedActor, DeadLetterHandler, Diagnostics, Simulation};
+pub use calendar_queue::CalendarQueue;
+pub use ladder_queue::LadderQueue;
+pub use message::{Message, MessageId, MessageIds};
+pub use queue::{AnyQueue, QueueKind, SimpleQueue};
+pub use time::{SimError, SimTime};
+
+/// Random number generator of the event queues. It produces the same numbers as
+/// `rand::rngs::StdRng` for a given seed, and can be saved in a checkpoint.
+pub type SimRng = rand_chacha::ChaCha12Rng;
+
+
+pub trait Event {
+    type TyEventType;
+    type TyTime: SimTime;
+    fn timestamp(&self) -> Self::TyTime;
+    /// Used by `EventQueue::reschedule`.
+    fn set_timestamp(&mut self, timestamp: Self::TyTime);
+    fn event_type(&self) -> Self::TyEventType;
+    fn event_id(&self) -> String;
+    fn destination(&self) -> String;
+
+    /// Actor that sent the event, if known. Only used for tracing.
+    fn source(&self) -> String {
+        String::new()
+    }
+
+    /// Among events with the same timestamp, higher priorities are delivered first.
+    fn priority(&self) -> i32 {
+        0
+    }
+}
+
+/// Identifies an event added to an `EventQueue`, so that it can be cancelled or
+/// rescheduled. A handle stays valid until its event is delivered or cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EventHandle(pub u64);
+
+/// Time type of the events held by queue `Q`.
+pub type TimeOf<Q> = <<Q as EventQueue>::TyEvent as Event>::TyTime;
+
+pub trait EventQueue {
+    type TyEvent: Event;
+
+    fn add(&mut self, event: Self::TyEvent) -> EventHandle;
+    fn pop(&mut self) -> Option<Self::TyEvent>;
+
+    /// Removes a pending event so that it is never delivered, and returns it. Returns
+    /// `None` if the event was already delivered or cancelled.
+    fn cancel(&mut self, handle: EventHandle) -> Option<Self::TyEvent>;
+
+    /// Moves a pending event to `timestamp`. Returns false if the event was already
+    /// delivered or cancelled.
+    fn reschedule(&mut self, handle: EventHandle, timestamp: TimeOf<Self>) -> bool;
+
+    /// Timestamp of the event `pop` would return next.
+    fn peek_timestamp(&mut self) -> Option<TimeOf<Self>>;
+
+    fn is_empty(&self) -> bool;
+
+    /// Random number generator shared by the actors of the model. Drawing every random
+    /// number from it makes a run reproducible from the queue's seed.
+    fn rng(&mut self) -> &mut SimRng;
+
+    /// Generator of message ids, seeded like `rng` but independent of it.
+    fn message_ids(&mut self) -> &mut MessageIds;
+}
+
+pub trait Actor {
+    type TyEvent;
+    type TyQueue;
+
+    fn handle_event(&mut self, event: Self::TyEvent, event_queue: &mut Self::TyQueue);
+    fn name(&self) -> String;
+
+    /// Lets `Simulation::actor_as` downcast the actor to inspect its state after a run.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Lets checkpoints save and restore the actor's state. See `checkpoint::Snapshot`.
+    fn as_snapshot(&mut self) -> Option<&mut dyn DynSnapshot> {
+        None
+    }
+
+    /// Lets checkpoints leave out an actor without a snapshot. Only return true if the
+    /// actor's handling of an event does not depend on the events before it.
+    fn is_stateless(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,645 @@
+//! Conservative parallel simulation across logical processes.
+//!
+//! Actors are partitioned into logical processes (LPs), each with its own event queue.
+//! The model declares a lookahead: every event an actor sends to an actor of another LP is
+//! timestamped at least `lookahead` after the sender's clock. The run then proceeds in
+//! windows: if `t` is the earliest pending event of any LP, no event before
+//! `t + lookahead` can still be created by another LP, so every LP processes its events in
+//! `[t, t + lookahead)` on its own thread. Events for other LPs are exchanged at the
+//! barrier that ends the window.
+//!
+//! Each actor receives its events in the same order as in a sequential `Simulation`,
+//! except that simultaneous events of equal priority coming from different LPs are
+//! ordered by sending time, then by LP. Results are identical whenever such ties do not
+//! occur and actors draw random numbers from their own generators, since every LP queue
+//! has its own generator.
+//!
+//! Events addressed to unknown actors are dead letters, handled as by `Simulation`: they
+//! are counted and passed to the handler set with `on_dead_letter`, if any, or reported
+//! as `SimError::UndeliverableEvent` after `fail_on_dead_letter`. A panic of an actor
+//! stops the run and is resumed on the thread calling `run_until`.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+use rand::SeedableRng;
+
+use crate::event_sim::{Actor, Event, EventHandle, EventQueue, MessageIds, SimError, SimRng, SimTime, SimpleQueue, TimeOf};
+
+pub type SendActor<E> = Box<dyn Actor<TyEvent = E, TyQueue = LpQueue<E>> + Send>;
+
+/// Receives the events addressed to unknown actors, with the queue of the logical process
+/// where they were found. Calls from different logical processes are serialised, in no
+/// particular order.
+pub type SendDeadLetterHandler<E> = Box<dyn FnMut(E, &mut LpQueue<E>) + Send>;
+
+/// An event for another logical process, with the sender's clock when it was sent.
+struct Outgoing<E: Event> {
+    sent: E::TyTime,
+    destination: usize,
+    event: E,
+}
+
+/// Event queue of a logical process. Events for actors of other LPs are held until the
+/// end of the window; they cannot be cancelled or rescheduled.
+pub struct LpQueue<E: Event> {
+    local: SimpleQueue<E>,
+    lp: usize,
+    placement: Arc<HashMap<String, usize>>,
+    now: E::TyTime,
+    lookahead: E::TyTime,
+    outbox: Vec<Outgoing<E>>,
+    /// Handles returned for outgoing events count down from `u64::MAX`.
+    next_remote_handle: u64,
+    violation: Option<SimError<E::TyTime>>,
+}
+
+impl<E: Event> LpQueue<E> {
+    /// Index of the logical process owning the queue.
+    pub fn lp(&self) -> usize {
+        self.lp
+    }
+
+    /// Clock of the logical process.
+    pub fn now(&self) -> E::TyTime {
+        self.now
+    }
+}
+
+impl<E: Event> EventQueue for LpQueue<E> {
+    type TyEvent = E;
+
+    fn add(&mut self, event: E) -> EventHandle {
+        let destination = match self.placement.get(&event.destination()) {
+            Some(&lp) if lp != self.lp => lp,
+            _ => return self.local.add(event),
+        };
+        let earliest = self.now + self.lookahead;
+        if event.timestamp().partial_cmp(&earliest).is_none_or(|order| order == Ordering::Less) {
+            self.violation.get_or_insert(SimError::LookaheadViolation {
+                now: self.now,
+                lookahead: self.lookahead,
+                timestamp: event.timestamp(),
+                event_id: event.event_id(),
+            });
+        } else {
+            self.outbox.push(Outgoing { sent: self.now, destination, event });
+        }
+        self.next_remote_handle -= 1;
+        EventHandle(self.next_remote_handle)
+    }
+
+    fn pop(&mut self) -> Option<E> {
+        self.local.pop()
+    }
+
+    fn cancel(&mut self, handle: EventHandle) -> Option<E> {
+        self.local.cancel(handle)
+    }
+
+    fn reschedule(&mut self, handle: EventHandle, timestamp: TimeOf<Self>) -> bool {
+        self.local.reschedule(handle, timestamp)
+    }
+
+    fn peek_timestamp(&mut self) -> Option<TimeOf<Self>> {
+        self.local.peek_timestamp()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.local.is_empty()
+    }
+
+    fn rng(&mut self) -> &mut SimRng {
+        self.local.rng()
+    }
+
+    fn message_ids(&mut self) -> &mut MessageIds {
+        self.local.message_ids()
+    }
+}
+
+fn lock<T>(lp: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    lp.lock().expect("A logical process panicked.")
+}
+
+struct LogicalProcess<E: Event> {
+    queue: LpQueue<E>,
+    actors: HashMap<String, SendActor<E>>,
+    events_processed: usize,
+    /// Number of undeliverable events by destination.
+    dead_letters: BTreeMap<String, usize>,
+    error: Option<SimError<E::TyTime>>,
+    /// Payload of a panic of one of the actors.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+/// Treatment of the events addressed to unknown actors; see `ParallelSimulation`.
+struct DeadLetterPolicy<'a, E: Event> {
+    handler: Option<&'a Mutex<SendDeadLetterHandler<E>>>,
+    fail: bool,
+}
+
+impl<E: Event> LogicalProcess<E> {
+    /// Delivers the events before `window_end`, up to and including `end_time`. A panic
+    /// of an actor is kept in `panic`.
+    fn run_window(&mut self, window_end: E::TyTime, end_time: E::TyTime, dead_letters: &DeadLetterPolicy<E>) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.process_window(window_end, end_time, dead_letters)));
+        if let Err(payload) = result {
+            self.panic = Some(payload);
+        }
+    }
+
+    fn process_window(&mut self, window_end: E::TyTime, end_time: E::TyTime, dead_letters: &DeadLetterPolicy<E>) {
+        while self.error.is_none() {
+            match self.queue.peek_timestamp() {
+                Some(timestamp) if timestamp.partial_cmp(&timestamp).is_none() => {
+                    let event = self.queue.pop().expect("the queue has an event");
+                    self.error = Some(SimError::InvalidTimestamp { timestamp, event_id: event.event_id() });
+                    break;
+                }
+                Some(timestamp) if timestamp < window_end && timestamp <= end_time => {}
+                _ => break,
+            }
+            let event = self.queue.pop().expect("the queue has an event");
+            if event.timestamp() < self.queue.now {
+                self.error = Some(SimError::EventInPast {
+                    now: self.queue.now,
+                    timestamp: event.timestamp(),
+                    event_id: event.event_id(),
+                });
+                break;
+            }
+            self.queue.now = event.timestamp();
+            let destination = event.destination();
+            match self.actors.get_mut(&destination) {
+                Some(actor) => actor.handle_event(event, &mut self.queue),
+                None => {
+                    *self.dead_letters.entry(destination.clone()).or_default() += 1;
+                    if dead_letters.fail {
+                        let event_id = event.event_id();
+                        self.error = Some(SimError::UndeliverableEvent { now: self.queue.now, destination, event_id });
+                        break;
+                    }
+                    if let Some(handler) = dead_letters.handler {
+                        let mut handler = handler.lock().expect("A dead-letter handler panicked.");
+                        handler(event, &mut self.queue);
+                    }
+                }
+            }
+            self.events_processed += 1;
+            self.error = self.queue.violation.take();
+        }
+    }
+}
+
+/// Orders timestamps, unordered ones first so that a window starts at them and their LP
+/// reports them.
+fn unordered_first<T: PartialOrd>(a: &T, b: &T) -> Ordering {
+    match (a.partial_cmp(a), b.partial_cmp(b)) {
+        (None, None) => Ordering::Equal,
+        (None, _) => Ordering::Less,
+        (_, None) => Ordering::Greater,
+        _ => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// A simulation whose actors are partitioned into logical processes run on separate
+/// threads. See the module documentation.
+pub struct ParallelSimulation<E: Event> {
+    lps: Vec<LogicalProcess<E>>,
+    placement: HashMap<String, usize>,
+    lookahead: E::TyTime,
+    threads: usize,
+    clock: E::TyTime,
+    dead_letter_handler: Option<Mutex<SendDeadLetterHandler<E>>>,
+    fail_on_dead_letter: bool,
+}
+
+impl<E> ParallelSimulation<E>
+where
+    E: Event + Send,
+    E::TyTime: Send + Sync,
+{
+    /// `lp_count` logical processes whose queues are seeded with `seed`, `seed + 1`, ...,
+    /// wrapping around after `u64::MAX`. Panics unless `lookahead` is positive.
+    pub fn new(lp_count: usize, lookahead: E::TyTime, start_time: E::TyTime, seed: u64) -> ParallelSimulation<E> {
+        assert!(lookahead.to_f64() > 0.0, "The lookahead must be positive.");
+        let lps = (0..lp_count)
+            .map(|lp| LogicalProcess {
+                queue: LpQueue {
+                    local: SimpleQueue::with_rng(SimRng::seed_from_u64(seed.wrapping_add(lp as u64))),
+                    lp,
+                    placement: Arc::new(HashMap::new()),
+                    now: start_time,
+                    lookahead,
+                    outbox: Vec::new(),
+                    next_remote_handle: u64::MAX,
+                    violation: None,
+                },
+                actors: HashMap::new(),
+                events_processed: 0,
+                dead_letters: BTreeMap::new(),
+                error: None,
+                panic: None,
+            })
+            .collect();
+        ParallelSimulation {
+            lps,
+            placement: HashMap::new(),
+            lookahead,
+            threads: lp_count,
+            clock: start_time,
+            dead_letter_handler: None,
+            fail_on_dead_letter: false,
+        }
+    }
+
+    /// Number of worker threads; by default one per logical process.
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Registers an actor in logical process `lp`, returning the actor it replaces, if any.
+    pub fn add_actor(&mut self, lp: usize, actor: SendActor<E>) -> Option<SendActor<E>> {
+        let name = actor.name();
+        let previous = match self.placement.insert(name.clone(), lp) {
+            Some(previous_lp) => self.lps[previous_lp].actors.remove(&name),
+            None => None,
+        };
+        self.lps[lp].actors.insert(name, actor);
+        previous
+    }
+
+    pub fn actor_as<T: 'static>(&self, name: &str) -> Option<&T> {
+        let lp = *self.placement.get(name)?;
+        let actor: &dyn Any = self.lps[lp].actors.get(name)?.as_any()?;
+        actor.downcast_ref()
+    }
+
+    /// Passes every event addressed to an unknown actor to `handler`.
+    pub fn on_dead_letter<F>(&mut self, handler: F)
+    where
+        F: FnMut(E, &mut LpQueue<E>) + Send + 'static,
+    {
+        self.dead_letter_handler = Some(Mutex::new(Box::new(handler)));
+    }
+
+    /// Makes `run_until` fail with `SimError::UndeliverableEvent` on the first event
+    /// addressed to an unknown actor, instead of passing it to the dead-letter handler.
+    pub fn fail_on_dead_letter(&mut self, fail: bool) {
+        self.fail_on_dead_letter = fail;
+    }
+
+    /// Number of events addressed to unknown actors so far.
+    pub fn dead_letters(&self) -> usize {
+        self.lps.iter().flat_map(|lp| lp.dead_letters.values()).sum()
+    }
+
+    /// Adds an event to the queue of the LP owning its destination, or of the first LP if
+    /// the destination is unknown, where it becomes a dead letter.
+    pub fn schedule(&mut self, event: E) -> Result<EventHandle, SimError<E::TyTime>> {
+        match event.timestamp().partial_cmp(&self.clock) {
+            None => return Err(SimError::InvalidTimestamp { timestamp: event.timestamp(), event_id: event.event_id() }),
+            Some(Ordering::Less) => return Err(SimError::EventInPast {
+                now: self.clock,
+                timestamp: event.timestamp(),
+                event_id: event.event_id(),
+            }),
+            Some(_) => {}
+        }
+        let lp = self.placement.get(&event.destination()).copied().unwrap_or(0);
+        Ok(self.lps[lp].queue.local.add(event))
+    }
+
+    pub fn now(&self) -> E::TyTime {
+        self.clock
+    }
+
+    pub fn events_processed(&self) -> usize {
+        self.lps.iter().map(|lp| lp.events_processed).sum()
+    }
+
+    /// Delivers every event up to and including `end_time`, then advances the clock to
+    /// `end_time`. Returns the number of events delivered.
+    ///
+    /// If an actor panics, the run stops at the end of the current window and the panic
+    /// is resumed here.
+    pub fn run_until(&mut self, end_time: E::TyTime) -> Result<usize, SimError<E::TyTime>> {
+        let placement = Arc::new(self.placement.clone());
+        for lp in self.lps.iter_mut() {
+            lp.queue.placement = placement.clone();
+        }
+        let processed_before = self.events_processed();
+
+        let threads = self.threads.min(self.lps.len()).max(1);
+        let lps: Vec<Mutex<LogicalProcess<E>>> = self.lps.drain(..).map(Mutex::new).collect();
+        let window: Mutex<Option<E::TyTime>> = Mutex::new(None);
+        let barrier = Barrier::new(threads + 1);
+        let dead_letters = DeadLetterPolicy { handler: self.dead_letter_handler.as_ref(), fail: self.fail_on_dead_letter };
+
+        let (result, panicked) = thread::scope(|scope| {
+            for worker in 0..threads {
+                let (lps, window, barrier, dead_letters) = (&lps, &window, &barrier, &dead_letters);
+                scope.spawn(move || loop {
+                    barrier.wait();
+                    let window_end = match *window.lock().expect("Window lock poisoned.") {
+                        Some(window_end) => window_end,
+                        None => break,
+                    };
+                    for lp in lps.iter().skip(worker).step_by(threads) {
+                        lock(lp).run_window(window_end, end_time, dead_letters);
+                    }
+                    barrier.wait();
+                });
+            }
+
+            let mut result = Ok(());
+            let mut panicked = None;
+            loop {
+                let next = lps.iter()
+                    .filter_map(|lp| lock(lp).queue.peek_timestamp())
+                    .min_by(unordered_first)
+                    .filter(|next| next.partial_cmp(&end_time) != Some(Ordering::Greater));
+                let proceed = result.is_ok() && panicked.is_none() && next.is_some();
+                *window.lock().expect("Window lock poisoned.") = next.filter(|_| proceed).map(|next| next + self.lookahead);
+                barrier.wait();
+                if !proceed {
+                    break;
+                }
+                barrier.wait();
+
+                let mut outgoing = Vec::new();
+                for lp in lps.iter() {
+                    let mut lp = lock(lp);
+                    if let Some(error) = lp.error.take() {
+                        result = Err(error);
+                    }
+                    if let Some(payload) = lp.panic.take() {
+                        panicked.get_or_insert(payload);
+                    }
+                    outgoing.append(&mut lp.queue.outbox);
+                }
+                // Stable sort: events sent at the same time stay ordered by LP, then by
+                // sending order.
+                outgoing.sort_by(|a, b| a.sent.partial_cmp(&b.sent).unwrap_or(Ordering::Equal));
+                for Outgoing { destination, event, .. } in outgoing {
+                    lock(&lps[destination]).queue.local.add(event);
+                }
+            }
+            (result, panicked)
+        });
+
+        self.lps = lps.into_iter().map(|lp| lp.into_inner().expect("A logical process panicked.")).collect();
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+        result?;
+        if self.clock < end_time {
+            self.clock = end_time;
+        }
+        for lp in self.lps.iter_mut() {
+            lp.queue.now = self.clock;
+        }
+        Ok(self.events_processed() - processed_before)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::marker::PhantomData;
+
+    use rand::rngs::StdRng;
+    use rand::Rng;
+
+    use super::*;
+    use crate::event_sim::{Message, Simulation};
+
+    type Ping = Message<u32>;
+
+    /// Forwards each ping to a random peer after a random delay of at least 1, using its
+    /// own random number generator.
+    struct Relay<Q> {
+        name: String,
+        peers: Vec<String>,
+        rng: StdRng,
+        log: Vec<(f64, u32)>,
+        _queue: PhantomData<fn() -> Q>,
+    }
+
+    impl<Q> Relay<Q> {
+        fn new(index: usize, count: usize) -> Relay<Q> {
+            Relay {
+                name: format!("relay {}", index),
+                peers: (0..count).map(|peer| format!("relay {}", peer)).collect(),
+                rng: StdRng::seed_from_u64(index as u64),
+                log: Vec::new(),
+                _queue: PhantomData,
+            }
+        }
+    }
+
+    impl<Q: EventQueue<TyEvent = Ping> + 'static> Actor for Relay<Q> {
+        type TyEvent = Ping;
+        type TyQueue = Q;
+
+        fn handle_event(&mut self, event: Ping, queue: &mut Q) {
+            self.log.push((event.timestamp, event.payload));
+            let peer = &self.peers[self.rng.gen_range(0..self.peers.len())];
+            let delay = 1.0 + self.rng.gen::<f64>();
+            let hops = event.payload + 1;
+            let forward = event.follow_up(event.timestamp + delay, peer, hops, queue.message_ids());
+            queue.add(forward);
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn as_any(&self) -> Option<&dyn Any> {
+            Some(self)
+        }
+    }
+
+    const RELAYS: usize = 8;
+
+    fn seed_pings(rng: &mut StdRng) -> Vec<Ping> {
+        let mut ids = MessageIds::with_seed(99);
+        (0..20).map(|i| Ping::new(rng.gen::<f64>(), "test", &format!("relay {}", i % RELAYS), 0, &mut ids)).collect()
+    }
+
+    fn sequential_logs() -> Vec<Vec<(f64, u32)>> {
+        let mut simulation = Simulation::new(SimpleQueue::with_seed(0), 0.0);
+        for index in 0..RELAYS {
+            simulation.add_actor(Box::new(Relay::<SimpleQueue<Ping>>::new(index, RELAYS)));
+        }
+        for ping in seed_pings(&mut StdRng::seed_from_u64(99)) {
+            simulation.schedule(ping).unwrap();
+        }
+        simulation.run_until(200.0).unwrap();
+        (0..RELAYS)
+            .map(|index| simulation.actor_as::<Relay<SimpleQueue<Ping>>>(&format!("relay {}", index)).unwrap().log.clone())
+            .collect()
+    }
+
+    fn parallel_logs(lp_count: usize, threads: usize) -> Vec<Vec<(f64, u32)>> {
+        let mut simulation = ParallelSimulation::new(lp_count, 1.0, 0.0, 0);
+        simulation.threads(threads);
+        for index in 0..RELAYS {
+            simulation.add_actor(index % lp_count, Box::new(Relay::<LpQueue<Ping>>::new(index, RELAYS)));
+        }
+        for ping in seed_pings(&mut StdRng::seed_from_u64(99)) {
+            simulation.schedule(ping).unwrap();
+        }
+        // Two calls, to check that a run can be resumed.
+        simulation.run_until(100.0).unwrap();
+        simulation.run_until(200.0).unwrap();
+        assert_eq!(200.0, simulation.now());
+        (0..RELAYS)
+            .map(|index| simulation.actor_as::<Relay<LpQueue<Ping>>>(&format!("relay {}", index)).unwrap().log.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_identical_to_sequential_run() {
+        let expected = sequential_logs();
+        assert!(expected.iter().map(|log| log.len()).sum::<usize>() > 1000);
+        for (lp_count, threads) in [(1, 1), (2, 2), (4, 4), (4, 2), (8, 3)] {
+            assert_eq!(expected, parallel_logs(lp_count, threads), "{} LPs on {} threads", lp_count, threads);
+        }
+    }
+
+    #[test]
+    fn test_lookahead_violation() {
+        struct Eager;
+
+        impl Actor for Eager {
+            type TyEvent = Ping;
+            type TyQueue = LpQueue<Ping>;
+
+            fn handle_event(&mut self, event: Ping, queue: &mut LpQueue<Ping>) {
+                let early = event.follow_up(event.timestamp + 0.5, "sink", 0, queue.message_ids());
+                queue.add(early);
+            }
+
+            fn name(&self) -> String {
+                "eager".to_owned()
+            }
+        }
+
+        let mut simulation = ParallelSimulation::new(2, 1.0, 0.0, 0);
+        simulation.add_actor(0, Box::new(Eager));
+        simulation.schedule(Ping::new(2.0, "test", "eager", 0, &mut MessageIds::with_seed(0))).unwrap();
+        let mut sink = Relay::<LpQueue<Ping>>::new(0, 1);
+        sink.name = "sink".to_owned();
+        simulation.add_actor(1, Box::new(sink));
+
+        match simulation.run_until(10.0) {
+            Err(SimError::LookaheadViolation { now, timestamp, .. }) => assert_eq!((2.0, 2.5), (now, timestamp)),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_unordered_timestamp_from_actor_is_reported() {
+        /// Sends itself a ping at an unordered timestamp.
+        struct Unordered;
+
+        impl Actor for Unordered {
+            type TyEvent = Ping;
+            type TyQueue = LpQueue<Ping>;
+
+            fn handle_event(&mut self, event: Ping, queue: &mut LpQueue<Ping>) {
+                let ping = event.follow_up(f64::NAN, "unordered", 0, queue.message_ids());
+                queue.add(ping);
+            }
+
+            fn name(&self) -> String {
+                "unordered".to_owned()
+            }
+        }
+
+        let mut simulation = ParallelSimulation::new(2, 1.0, 0.0, 0);
+        simulation.add_actor(0, Box::new(Unordered));
+        simulation.add_actor(1, Box::new(Relay::<LpQueue<Ping>>::new(0, 1)));
+        let mut ids = MessageIds::with_seed(0);
+        simulation.schedule(Ping::new(0.0, "test", "unordered", 0, &mut ids)).unwrap();
+        simulation.schedule(Ping::new(0.5, "test", "relay 0", 0, &mut ids)).unwrap();
+
+        match simulation.run_until(10.0) {
+            Err(SimError::InvalidTimestamp { timestamp, .. }) => assert!(timestamp.is_nan()),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(0.0, simulation.now());
+        assert_eq!(1, simulation.actor_as::<Relay<LpQueue<Ping>>>("relay 0").unwrap().log.len());
+    }
+
+    #[test]
+    fn test_dead_letters() {
+        let mut simulation = ParallelSimulation::new(2, 1.0, 0.0, 0);
+        let mut relay = Relay::<LpQueue<Ping>>::new(0, 1);
+        relay.peers = vec!["nobody".to_owned()];
+        simulation.add_actor(0, Box::new(relay));
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let log = handled.clone();
+        simulation.on_dead_letter(move |event: Ping, queue: &mut LpQueue<Ping>| {
+            log.lock().unwrap().push(event.destination.clone());
+            if event.destination == "ghost" {
+                let redirected = event.follow_up(event.timestamp + 1.0, "relay 0", 0, queue.message_ids());
+                queue.add(redirected);
+            }
+        });
+        let mut ids = MessageIds::with_seed(0);
+        simulation.schedule(Ping::new(0.0, "test", "relay 0", 0, &mut ids)).unwrap();
+        simulation.schedule(Ping::new(0.5, "test", "ghost", 0, &mut ids)).unwrap();
+        simulation.run_until(10.0).unwrap();
+
+        let mut handled = handled.lock().unwrap().clone();
+        handled.sort();
+        assert_eq!(vec!["ghost", "nobody", "nobody"], handled);
+        assert_eq!(3, simulation.dead_letters());
+        assert_eq!(2, simulation.actor_as::<Relay<LpQueue<Ping>>>("relay 0").unwrap().log.len());
+
+        simulation.fail_on_dead_letter(true);
+        simulation.schedule(Ping::new(11.0, "test", "ghost", 0, &mut ids)).unwrap();
+        match simulation.run_until(20.0) {
+            Err(SimError::UndeliverableEvent { now, destination, .. }) => assert_eq!((11.0, "ghost"), (now, destination.as_str())),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(4, simulation.dead_letters());
+    }
+
+    #[test]
+    fn test_actor_panic_is_resumed() {
+        struct Faulty;
+
+        impl Actor for Faulty {
+            type TyEvent = Ping;
+            type TyQueue = LpQueue<Ping>;
+
+            fn handle_event(&mut self, _event: Ping, _queue: &mut LpQueue<Ping>) {
+                panic!("injected failure");
+            }
+
+            fn name(&self) -> String {
+                "faulty".to_owned()
+            }
+        }
+
+        let mut simulation = ParallelSimulation::new(2, 1.0, 0.0, 0);
+        simulation.add_actor(0, Box::new(Faulty));
+        simulation.add_actor(1, Box::new(Relay::<LpQueue<Ping>>::new(1, 2)));
+        let mut ids = MessageIds::with_seed(0);
+        simulation.schedule(Ping::new(0.0, "test", "relay 1", 0, &mut ids)).unwrap();
+        simulation.schedule(Ping::new(3.0, "test", "faulty", 0, &mut ids)).unwrap();
+
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| simulation.run_until(10.0))).unwrap_err();
+        assert_eq!(Some(&"injected failure"), payload.downcast_ref::<&str>());
+        assert!(simulation.actor_as::<Relay<LpQueue<Ping>>>("relay 1").is_some());
+    }
+}
@@ -0,0 +1,494 @@
+//! Process-oriented modelling on top of actors.
+//!
+//! A process is an `async` block written as sequential code: it `hold`s for a duration,
+//! `request`s a resource, waiting until a unit is free, and `release`s it. Stores and
+//! containers are in `resources`. The processes of a model run inside a single actor,
+//! `ProcessModel`, which resumes a process whenever the simulation delivers one of its
+//! `ProcessEvent`s. Nothing is polled outside of event delivery, so the futures never
+//! need a real waker.
+//!
+//! ```ignore
+//! let mut model = ProcessModel::new();
+//! let server = model.resource("server", 1);
+//! model.process("customer", 0.0, move |process| async move {
+//!     process.request(&server).await;
+//!     process.hold(10.0).await;
+//!     process.release(&server);
+//! });
+//! let mut simulation = model.build(SimpleQueue::with_seed(1));
+//! simulation.run()?;
+//! ```
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::event_sim::resources::{Container, Request, Resource, Store};
+use crate::event_sim::{Actor, Event, EventHandle, EventQueue, SimRng, Simulation};
+
+/// Name of the actor running the processes.
+pub const PROCESS_ACTOR: &str = "processes";
+
+/// Resumes a process.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessEvent {
+    pub timestamp: f64,
+    pub process: usize,
+    /// Name of the process, for tracing.
+    pub name: String,
+}
+
+impl Event for ProcessEvent {
+    type TyEventType = usize;
+    type TyTime = f64;
+
+    fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    fn set_timestamp(&mut self, timestamp: f64) {
+        self.timestamp = timestamp;
+    }
+
+    fn event_type(&self) -> usize {
+        self.process
+    }
+
+    fn event_id(&self) -> String {
+        format!("{}#{}", self.name, self.process)
+    }
+
+    fn destination(&self) -> String {
+        PROCESS_ACTOR.to_owned()
+    }
+}
+
+type ProcessFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// State shared by the processes of a model and the actor running them.
+pub(super) struct Kernel {
+    pub(super) now: f64,
+    rng: SimRng,
+    names: Vec<String>,
+    /// Processes to resume, with the time at which to resume them.
+    wakeups: Vec<(f64, usize)>,
+    /// Processes whose pending wakeup must be cancelled because they have been
+    /// resumed early.
+    interrupts: Vec<usize>,
+    /// Processes in a preemptible hold, and those whose hold was cut short.
+    preemptible: HashSet<usize>,
+    interrupted: HashSet<usize>,
+    /// Processes started since the last event, not yet stored in the actor.
+    spawned: Vec<(usize, ProcessFuture)>,
+}
+
+impl Kernel {
+    /// Resumes `process` at the current time.
+    pub(super) fn wake(&mut self, process: usize) {
+        let now = self.now;
+        self.wakeups.push((now, process));
+    }
+
+    /// Called when `process` loses a resource to a higher priority request: a
+    /// preemptible hold of the process ends now.
+    pub(super) fn preempt(&mut self, process: usize) {
+        if self.preemptible.remove(&process) {
+            self.wakeups.retain(|(_, waiting)| *waiting != process);
+            self.interrupted.insert(process);
+            self.interrupts.push(process);
+            self.wake(process);
+        }
+    }
+
+    fn spawn<F, Fut>(kernel: &Rc<RefCell<Kernel>>, name: &str, start: f64, body: F)
+    where
+        F: FnOnce(Process) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let id = {
+            let mut state = kernel.borrow_mut();
+            let id = state.names.len();
+            state.names.push(name.to_owned());
+            state.wakeups.push((start, id));
+            id
+        };
+        let future = body(Process { kernel: kernel.clone(), id });
+        kernel.borrow_mut().spawned.push((id, Box::pin(future)));
+    }
+}
+
+/// Handle given to the body of a process.
+#[derive(Clone)]
+pub struct Process {
+    pub(super) kernel: Rc<RefCell<Kernel>>,
+    pub(super) id: usize,
+}
+
+impl Process {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn name(&self) -> String {
+        self.kernel.borrow().names[self.id].clone()
+    }
+
+    pub fn now(&self) -> f64 {
+        self.kernel.borrow().now
+    }
+
+    /// Suspends the process for `duration`. A zero duration lets the other processes
+    /// scheduled at the current time run first.
+    ///
+    /// A negative or NaN duration makes the run fail with `SimError::InvalidTimestamp`
+    /// when the process would have been resumed.
+    pub fn hold(&self, duration: f64) -> Hold {
+        Hold { process: self.clone(), duration, scheduled: false }
+    }
+
+    /// Like `hold`, but ends early if the process loses a resource to a preempting
+    /// request. Returns the remaining duration in that case.
+    pub fn hold_preemptible(&self, duration: f64) -> HoldPreemptible {
+        HoldPreemptible { process: self.clone(), end: hold_end(self.now(), duration), scheduled: false }
+    }
+
+    /// Waits until a unit of `resource` is granted to the process.
+    pub fn request(&self, resource: &Resource) -> Request {
+        Request::new(self, resource, 0, false)
+    }
+
+    /// Waits for a unit of `resource` ahead of every request with a lower priority.
+    pub fn request_with_priority(&self, resource: &Resource, priority: i32) -> Request {
+        Request::new(self, resource, priority, false)
+    }
+
+    /// Like `request_with_priority`, but if every unit is in use, takes one from the
+    /// holder with the lowest priority below `priority`, if any.
+    pub fn preempt(&self, resource: &Resource, priority: i32) -> Request {
+        Request::new(self, resource, priority, true)
+    }
+
+    /// Returns the unit of `resource` held by the process, granting it to the first
+    /// waiting request, if any. Does nothing if the unit was preempted.
+    pub fn release(&self, resource: &Resource) {
+        resource.release(self);
+    }
+
+    /// Starts another process at the current time.
+    pub fn spawn<F, Fut>(&self, name: &str, body: F)
+    where
+        F: FnOnce(Process) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let now = self.now();
+        Kernel::spawn(&self.kernel, name, now, body);
+    }
+
+    /// Random number generator of the model, seeded from the queue's generator.
+    pub fn with_rng<T>(&self, f: impl FnOnce(&mut SimRng) -> T) -> T {
+        f(&mut self.kernel.borrow_mut().rng)
+    }
+}
+
+/// End of a hold of `duration` from `now`. An invalid duration gives a NaN end, which
+/// `Simulation::step` reports as an invalid timestamp.
+fn hold_end(now: f64, duration: f64) -> f64 {
+    if duration >= 0.0 { now + duration } else { f64::NAN }
+}
+
+/// Future returned by `Process::hold`.
+pub struct Hold {
+    process: Process,
+    duration: f64,
+    scheduled: bool,
+}
+
+impl Future for Hold {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.scheduled {
+            return Poll::Ready(());
+        }
+        self.scheduled = true;
+        let mut kernel = self.process.kernel.borrow_mut();
+        let wakeup = (hold_end(kernel.now, self.duration), self.process.id);
+        kernel.wakeups.push(wakeup);
+        Poll::Pending
+    }
+}
+
+/// Future returned by `Process::hold_preemptible`.
+pub struct HoldPreemptible {
+    process: Process,
+    end: f64,
+    scheduled: bool,
+}
+
+impl Future for HoldPreemptible {
+    type Output = Option<f64>;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<f64>> {
+        let id = self.process.id;
+        let mut kernel = self.process.kernel.borrow_mut();
+        if !self.scheduled {
+            let wakeup = (self.end, id);
+            kernel.wakeups.push(wakeup);
+            kernel.preemptible.insert(id);
+            drop(kernel);
+            self.scheduled = true;
+            return Poll::Pending;
+        }
+        kernel.preemptible.remove(&id);
+        match kernel.interrupted.remove(&id) {
+            true => Poll::Ready(Some(self.end - kernel.now)),
+            false => Poll::Ready(None),
+        }
+    }
+}
+
+/// Actor running the processes of a model. See the module documentation.
+pub struct ProcessModel<Q> {
+    kernel: Rc<RefCell<Kernel>>,
+    processes: Vec<Option<ProcessFuture>>,
+    /// Handle of the pending wakeup of each suspended process. A process is resumed once
+    /// per suspension: a later wakeup replaces a pending one.
+    pending: HashMap<usize, EventHandle>,
+    _queue: PhantomData<fn() -> Q>,
+}
+
+impl<Q: EventQueue<TyEvent = ProcessEvent> + 'static> ProcessModel<Q> {
+    pub fn new() -> ProcessModel<Q> {
+        let kernel = Kernel {
+            now: 0.0,
+            rng: SimRng::seed_from_u64(0),
+            names: Vec::new(),
+            wakeups: Vec::new(),
+            interrupts: Vec::new(),
+            preemptible: HashSet::new(),
+            interrupted: HashSet::new(),
+            spawned: Vec::new(),
+        };
+        ProcessModel {
+            kernel: Rc::new(RefCell::new(kernel)),
+            processes: Vec::new(),
+            pending: HashMap::new(),
+            _queue: PhantomData,
+        }
+    }
+
+    pub fn resource(&self, name: &str, capacity: usize) -> Resource {
+        Resource::new(&self.kernel, name, capacity)
+    }
+
+    /// A store holding at most `capacity` items. Panics if `capacity` is 0.
+    pub fn store<T: 'static>(&self, name: &str, capacity: usize) -> Store<T> {
+        Store::new(&self.kernel, name, capacity)
+    }
+
+    /// A container holding a continuous quantity between 0 and `capacity`.
+    pub fn container(&self, name: &str, capacity: f64, initial: f64) -> Container {
+        Container::new(&self.kernel, name, capacity, initial)
+    }
+
+    /// Starts a process at time `start`.
+    pub fn process<F, Fut>(&mut self, name: &str, start: f64, body: F) -> &mut Self
+    where
+        F: FnOnce(Process) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        assert!(start >= 0.0, "Processes start at non-negative times.");
+        Kernel::spawn(&self.kernel, name, start, body);
+        self
+    }
+
+    /// Number of processes started and not yet finished.
+    pub fn active(&self) -> usize {
+        self.processes.iter().flatten().count() + self.kernel.borrow().spawned.len()
+    }
+
+    /// Moves newly started processes into the actor and schedules every pending wakeup.
+    fn schedule_wakeups(&mut self, queue: &mut Q) {
+        let mut kernel = self.kernel.borrow_mut();
+        for (id, future) in kernel.spawned.drain(..) {
+            if self.processes.len() <= id {
+                self.processes.resize_with(id + 1, || None);
+            }
+            self.processes[id] = Some(future);
+        }
+        for process in kernel.interrupts.drain(..) {
+            if let Some(handle) = self.pending.remove(&process) {
+                queue.cancel(handle);
+            }
+        }
+        let wakeups: Vec<(f64, usize)> = kernel.wakeups.drain(..).collect();
+        for (timestamp, process) in wakeups {
+            let handle = queue.add(ProcessEvent { timestamp, process, name: kernel.names[process].clone() });
+            if let Some(replaced) = self.pending.insert(process, handle) {
+                queue.cancel(replaced);
+            }
+        }
+    }
+
+    /// Seeds the model's generator from the queue's, then schedules the processes.
+    pub fn build(mut self, mut queue: Q) -> Simulation<Q> {
+        self.kernel.borrow_mut().rng = SimRng::seed_from_u64(queue.rng().gen());
+        self.schedule_wakeups(&mut queue);
+        let mut simulation = Simulation::new(queue, 0.0);
+        simulation.add_actor(Box::new(self));
+        simulation
+    }
+}
+
+impl<Q: EventQueue<TyEvent = ProcessEvent> + 'static> Default for ProcessModel<Q> {
+    fn default() -> Self {
+        ProcessModel::new()
+    }
+}
+
+impl<Q: EventQueue<TyEvent = ProcessEvent> + 'static> Actor for ProcessModel<Q> {
+    type TyEvent = ProcessEvent;
+    type TyQueue = Q;
+
+    fn handle_event(&mut self, event: ProcessEvent, queue: &mut Q) {
+        self.kernel.borrow_mut().now = event.timestamp;
+        self.pending.remove(&event.process);
+        if let Some(process) = self.processes.get_mut(event.process).and_then(Option::as_mut) {
+            let mut context = Context::from_waker(Waker::noop());
+            if process.as_mut().poll(&mut context).is_ready() {
+                self.processes[event.process] = None;
+            }
+        }
+        self.schedule_wakeups(queue);
+    }
+
+    fn name(&self) -> String {
+        PROCESS_ACTOR.to_owned()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event_sim::{SimError, SimpleQueue};
+
+    type Queue = SimpleQueue<ProcessEvent>;
+    type Log = Rc<RefCell<Vec<(f64, String)>>>;
+
+    fn log(process: &Process, log: &Log, what: &str) {
+        log.borrow_mut().push((process.now(), format!("{} {}", process.name(), what)));
+    }
+
+    #[test]
+    fn test_hold_interleaves_processes() {
+        let trace: Log = Rc::default();
+        let mut model = ProcessModel::<Queue>::new();
+        for (name, period) in [("fast", 1.0), ("slow", 1.5)] {
+            let trace = trace.clone();
+            model.process(name, 0.5, move |process| async move {
+                for _ in 0..3 {
+                    process.hold(period).await;
+                    log(&process, &trace, "ticks");
+                }
+            });
+        }
+        let mut simulation = model.build(Queue::with_seed(0));
+        simulation.run().unwrap();
+
+        // Both wake at 3.5; the slow process was scheduled first.
+        let trace = trace.borrow();
+        let times: Vec<(f64, &str)> = trace.iter().map(|(t, what)| (*t, &what[..4])).collect();
+        assert_eq!(vec![(1.5, "fast"), (2.0, "slow"), (2.5, "fast"), (3.5, "slow"), (3.5, "fast"), (5.0, "slow")], times);
+        assert_eq!(0, simulation.actor_as::<ProcessModel<Queue>>(PROCESS_ACTOR).unwrap().active());
+    }
+
+    #[test]
+    fn test_resource_serves_requests_in_order() {
+        let trace: Log = Rc::default();
+        let mut model = ProcessModel::<Queue>::new();
+        let server = model.resource("server", 2);
+        let arrivals = {
+            let (trace, server) = (trace.clone(), server.clone());
+            move |process: Process| async move {
+                for i in 0..5 {
+                    let (trace, server) = (trace.clone(), server.clone());
+                    process.spawn(&format!("customer {}", i), move |customer| async move {
+                        customer.request(&server).await;
+                        log(&customer, &trace, "starts");
+                        customer.hold(3.0).await;
+                        customer.release(&server);
+                    });
+                    process.hold(1.0).await;
+                }
+            }
+        };
+        model.process("arrivals", 0.0, arrivals);
+        let mut simulation = model.build(Queue::with_seed(0));
+
+        simulation.run_until(2.5).unwrap();
+        assert_eq!((2, 1), (server.in_use(), server.queue_length()));
+        simulation.run().unwrap();
+        assert_eq!((0, 0), (server.in_use(), server.queue_length()));
+
+        let starts: Vec<(f64, String)> = trace.borrow().clone();
+        let expected: Vec<(f64, String)> = [0.0, 1.0, 3.0, 4.0, 6.0].iter().enumerate()
+            .map(|(i, t)| (*t, format!("customer {} starts", i)))
+            .collect();
+        assert_eq!(expected, starts);
+        assert_eq!(9.0, simulation.now());
+    }
+
+    #[test]
+    fn test_process_is_resumed_once_per_suspension() {
+        let trace: Log = Rc::default();
+        let mut model = ProcessModel::<Queue>::new();
+        let sleeper = {
+            let trace = trace.clone();
+            move |process: Process| async move {
+                process.hold(10.0).await;
+                log(&process, &trace, "woken");
+                process.hold(1.0).await;
+                log(&process, &trace, "done");
+            }
+        };
+        model.process("sleeper", 0.0, sleeper);
+        model.process("waker", 2.0, |process: Process| async move {
+            let mut kernel = process.kernel.borrow_mut();
+            kernel.wake(0);
+            kernel.wake(0);
+        });
+        let mut simulation = model.build(Queue::with_seed(0));
+        simulation.run().unwrap();
+
+        assert_eq!(vec![(2.0, "sleeper woken".to_owned()), (3.0, "sleeper done".to_owned())], *trace.borrow());
+        assert_eq!(3.0, simulation.now());
+    }
+
+    #[test]
+    fn test_invalid_hold_is_reported() {
+        for duration in [-1.0, f64::NAN] {
+            let mut model = ProcessModel::<Queue>::new();
+            model.process("impatient", 1.0, move |process: Process| async move {
+                process.hold(duration).await;
+            });
+            let mut simulation = model.build(Queue::with_seed(0));
+            match simulation.run() {
+                Err(SimError::InvalidTimestamp { event_id, .. }) => assert_eq!("impatient#0", event_id),
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use rand::SeedableRng;
+
+use crate::event_sim::checkpoint::{QueueState, RestorableQueue, SavedEvent};
+use crate::event_sim::{CalendarQueue, Event, EventHandle, EventQueue, LadderQueue, MessageIds, SimRng, TimeOf};
+
+/// Ordering key of a pending event. The sequence number is assigned when the event is
+/// added or rescheduled, so events with the same timestamp and priority are delivered in
+/// the order they were scheduled.
+pub(crate) struct Key<T> {
+    pub(crate) timestamp: T,
+    pub(crate) priority: i32,
+    pub(crate) sequence: u64,
+    pub(crate) handle: EventHandle,
+}
+
+impl<T: PartialOrd> PartialEq for Key<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.sequence == other.sequence
+    }
+}
+
+impl<T: PartialOrd> Eq for Key<T> {}
+
+impl<T: PartialOrd> PartialOrd for Key<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The key to deliver first compares as the greatest, which suits `BinaryHeap` and lets
+/// sorted buckets pop the next key from their end.
+impl<T: PartialOrd> Ord for Key<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.timestamp.partial_cmp(&self.timestamp).expect("Timestamp cannot be NaN.")
+            .then(self.priority.cmp(&other.priority))
+            .then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Pending events by handle, shared by the queue implementations.
+///
+/// Queues only store keys. Cancelling or rescheduling an event leaves its old key behind;
+/// such stale keys are recognised by their sequence number and skipped (lazy deletion).
+///
+/// The keys of events whose timestamp cannot be ordered, e.g. `f64::NAN`, are kept here
+/// instead, and delivered before any other so that `Simulation::step` reports them.
+pub(crate) struct PendingEvents<E: Event> {
+    events: HashMap<EventHandle, (u64, E)>,
+    next_sequence: u64,
+    unordered: VecDeque<Key<E::TyTime>>,
+}
+
+impl<E: Event> PendingEvents<E> {
+    pub(crate) fn new() -> PendingEvents<E> {
+        PendingEvents { events: HashMap::new(), next_sequence: 0, unordered: VecDeque::new() }
+    }
+
+    /// Stores a new event and returns its handle, and its key unless it is unordered.
+    pub(crate) fn add(&mut self, event: E) -> (EventHandle, Option<Key<E::TyTime>>) {
+        let handle = EventHandle(self.next_sequence);
+        (handle, self.insert(handle, event))
+    }
+
+    /// Stores an event under an existing handle, with a new sequence number, and returns
+    /// its key unless it is unordered.
+    pub(crate) fn insert(&mut self, handle: EventHandle, event: E) -> Option<Key<E::TyTime>> {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        let key = Key { timestamp: event.timestamp(), priority: event.priority(), sequence, handle };
+        self.events.insert(handle, (sequence, event));
+        self.admit(key)
+    }
+
+    /// Keeps `key` if its timestamp cannot be ordered, otherwise returns it.
+    fn admit(&mut self, key: Key<E::TyTime>) -> Option<Key<E::TyTime>> {
+        if key.timestamp.partial_cmp(&key.timestamp).is_none() {
+            self.unordered.push_back(key);
+            None
+        } else {
+            Some(key)
+        }
+    }
+
+    /// Drops the stale unordered keys in front.
+    fn discard_stale_unordered(&mut self) {
+        while self.unordered.front().is_some_and(|key| !self.is_live(key)) {
+            self.unordered.pop_front();
+        }
+    }
+
+    /// Removes the next unordered event, which is delivered before any other.
+    pub(crate) fn take_unordered(&mut self) -> Option<E> {
+        self.discard_stale_unordered();
+        let key = self.unordered.pop_front()?;
+        self.take(&key)
+    }
+
+    pub(crate) fn unordered_timestamp(&mut self) -> Option<E::TyTime> {
+        self.discard_stale_unordered();
+        self.unordered.front().map(|key| key.timestamp)
+    }
+
+    pub(crate) fn is_live(&self, key: &Key<E::TyTime>) -> bool {
+        self.events.get(&key.handle).is_some_and(|(sequence, _)| *sequence == key.sequence)
+    }
+
+    /// Removes the event of a live key.
+    pub(crate) fn take(&mut self, key: &Key<E::TyTime>) -> Option<E> {
+        if self.is_live(key) {
+            self.events.remove(&key.handle).map(|(_, event)| event)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn cancel(&mut self, handle: EventHandle) -> Option<E> {
+        self.events.remove(&handle).map(|(_, event)| event)
+    }
+
+    /// Moves an event to `timestamp`. Returns None if the event is not pending, otherwise
+    /// its new key unless it is unordered.
+    pub(crate) fn reschedule(&mut self, handle: EventHandle, timestamp: E::TyTime) -> Option<Option<Key<E::TyTime>>> {
+        let (_, mut event) = self.events.remove(&handle)?;
+        event.set_timestamp(timestamp);
+        Some(self.insert(handle, event))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// The pending events, ordered by sequence number, with what is needed to restore
+    /// them in the same order and under the same handles.
+    pub(crate) fn save(&self, rng: &SimRng, message_ids: &MessageIds) -> QueueState<E>
+    where
+        E: Clone,
+    {
+        let mut events: Vec<SavedEvent<E>> = self.events.iter()
+            .map(|(handle, (sequence, event))| SavedEvent { handle: *handle, sequence: *sequence, event: event.clone() })
+            .collect();
+        events.sort_by_key(|saved| saved.sequence);
+        QueueState { events, next_sequence: self.next_sequence, rng: rng.clone(), message_ids: message_ids.clone() }
+    }
+
+    /// Replaces the pending events with saved ones and returns their ordered keys.
+    pub(crate) fn restore(&mut self, state: QueueState<E>) -> Vec<Key<E::TyTime>> {
+        self.events.clear();
+        self.unordered.clear();
+        self.next_sequence = state.next_sequence;
+        state.events.into_iter()
+            .filter_map(|SavedEvent { handle, sequence, event }| {
+                let key = Key { timestamp: event.timestamp(), priority: event.priority(), sequence, handle };
+                self.events.insert(handle, (sequence, event));
+                self.admit(key)
+            })
+            .collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Event queue backed by a `BinaryHeap`.
+///
+/// Events are delivered by increasing timestamp, then decreasing `Event::priority`, then
+/// in the order they were added. The queue also owns the random number generator of the
+/// model, so that a given seed reproduces an identical run.
+///
+/// Cancellation is lazy: the heap is rebuilt when stale keys outnumber live ones.
+pub struct SimpleQueue<E: Event> {
+    queue: BinaryHeap<Key<E::TyTime>>,
+    pending: PendingEvents<E>,
+    rng: SimRng,
+    message_ids: MessageIds,
+}
+
+impl<E: Event> SimpleQueue<E> {
+    /// Creates a queue whose random number generator is seeded from the OS.
+    pub fn new() -> SimpleQueue<E> {
+        SimpleQueue::with_rng(SimRng::from_entropy())
+    }
+
+    pub fn with_seed(seed: u64) -> SimpleQueue<E> {
+        SimpleQueue::with_rng(SimRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng(rng: SimRng) -> SimpleQueue<E> {
+        SimpleQueue {
+            queue: BinaryHeap::new(),
+            pending: PendingEvents::new(),
+            message_ids: MessageIds::from_rng(&rng),
+            rng,
+        }
+    }
+
+    /// Drops stale keys from the top of the heap.
+    fn discard_stale(&mut self) {
+        while let Some(key) = self.queue.peek() {
+            if self.pending.is_live(key) {
+                break;
+            }
+            self.queue.pop();
+        }
+    }
+
+    fn compact_if_needed(&mut self) {
+        if self.queue.len() > 2 * self.pending.len() + 64 {
+            let keys = std::mem::take(&mut self.queue).into_vec();
+            let live: Vec<Key<E::TyTime>> = keys.into_iter().filter(|key| self.pending.is_live(key)).collect();
+            self.queue = BinaryHeap::from(live);
+        }
+    }
+}
+
+impl<E: Event> Default for SimpleQueue<E> {
+    fn default() -> Self {
+        SimpleQueue::new()
+    }
+}
+
+impl<E: Event> EventQueue for SimpleQueue<E> {
+    type TyEvent = E;
+    fn add(&mut self, event: E) -> EventHandle {
+        let (handle, key) = self.pending.add(event);
+        self.queue.extend(key);
+        handle
+    }
+
+    fn pop(&mut self) -> Option<E> {
+        if let Some(event) = self.pending.take_unordered() {
+            return Some(event);
+        }
+        self.discard_stale();
+        let key = self.queue.pop()?;
+        self.pending.take(&key)
+    }
+
+    fn peek_timestamp(&mut self) -> Option<TimeOf<Self>> {
+        if let Some(timestamp) = self.pending.unordered_timestamp() {
+            return Some(timestamp);
+        }
+        self.discard_stale();
+        self.queue.peek().map(|key| key.timestamp)
+    }
+
+    fn cancel(&mut self, handle: EventHandle) -> Option<E> {
+        let event = self.pending.cancel(handle)?;
+        self.compact_if_needed();
+        Some(event)
+    }
+
+    fn reschedule(&mut self, handle: EventHandle, timestamp: TimeOf<Self>) -> bool {
+        match self.pending.reschedule(handle, timestamp) {
+            Some(key) => {
+                self.queue.extend(key);
+                self.compact_if_needed();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn rng(&mut self) -> &mut SimRng {
+        &mut self.rng
+    }
+
+    fn message_ids(&mut self) -> &mut MessageIds {
+        &mut self.message_ids
+    }
+}
+
+impl<E: Event + Clone> RestorableQueue for SimpleQueue<E> {
+    fn save_state(&self) -> QueueState<E> {
+        self.pending.save(&self.rng, &self.message_ids)
+    }
+
+    fn restore_state(&mut self, state: QueueState<E>) {
+        self.rng = state.rng.clone();
+        self.message_ids = state.message_ids.clone();
+        self.queue = BinaryHeap::from(self.pending.restore(state));
+    }
+}
+
+/// Event queue implementations that can be chosen at run time, e.g. from a command line
+/// option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueKind {
+    BinaryHeap,
+    Calendar,
+    Ladder,
+}
+
+impl QueueKind {
+    pub const ALL: [QueueKind; 3] = [QueueKind::BinaryHeap, QueueKind::Calendar, QueueKind::Ladder];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueueKind::BinaryHeap => "binary-heap",
+            QueueKind::Calendar => "calendar",
+            QueueKind::Ladder => "ladder",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<QueueKind> {
+        QueueKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// One of the event queue implementations, selected by `QueueKind`.
+pub enum AnyQueue<E: Event> {
+    BinaryHeap(SimpleQueue<E>),
+    Calendar(CalendarQueue<E>),
+    Ladder(LadderQueue<E>),
+}
+
+impl<E: Event> AnyQueue<E> {
+    pub fn with_seed(kind: QueueKind, seed: u64) -> AnyQueue<E> {
+        AnyQueue::with_rng(kind, SimRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng(kind: QueueKind, rng: SimRng) -> AnyQueue<E> {
+        match kind {
+            QueueKind::BinaryHeap => AnyQueue::BinaryHeap(SimpleQueue::with_rng(rng)),
+            QueueKind::Calendar => AnyQueue::Calendar(CalendarQueue::with_rng(rng)),
+            QueueKind::Ladder => AnyQueue::Ladder(LadderQueue::with_rng(rng)),
+        }
+    }
+
+    pub fn kind(&self) -> QueueKind {
+        match self {
+            AnyQueue::BinaryHeap(_) => QueueKind::BinaryHeap,
+            AnyQueue::Calendar(_) => QueueKind::Calendar,
+            AnyQueue::Ladder(_) => QueueKind::Ladder,
+        }
+    }
+}
+
+macro_rules! delegate {
+    ($self:ident, $queue:ident => $call:expr) => {
+        match $self {
+            AnyQueue::BinaryHeap($queue) => $call,
+            AnyQueue::Calendar($queue) => $call,
+            AnyQueue::Ladder($queue) => $call,
+        }
+    };
+}
+
+impl<E: Event> EventQueue for AnyQueue<E> {
+    type TyEvent = E;
+
+    fn add(&mut self, event: E) -> EventHandle {
+        delegate!(self, queue => queue.add(event))
+    }
+
+    fn pop(&mut self) -> Option<E> {
+        delegate!(self, queue => queue.pop())
+    }
+
+    fn cancel(&mut self, handle: EventHandle) -> Option<E> {
+        delegate!(self, queue => queue.cancel(handle))
+    }
+
+    fn reschedule(&mut self, handle: EventHandle, timestamp: TimeOf<Self>) -> bool {
+        delegate!(self, queue => queue.reschedule(handle, timestamp))
+    }
+
+    fn peek_timestamp(&mut self) -> Option<TimeOf<Self>> {
+        delegate!(self, queue => queue.peek_timestamp())
+    }
+
+    fn is_empty(&self) -> bool {
+        delegate!(self, queue => queue.is_empty())
+    }
+
+    fn rng(&mut self) -> &mut SimRng {
+        delegate!(self, queue => queue.rng())
+    }
+
+    fn message_ids(&mut self) -> &mut MessageIds {
+        delegate!(self, queue => queue.message_ids())
+    }
+}
+
+impl<E: Event + Clone> RestorableQueue for AnyQueue<E> {
+    fn save_state(&self) -> QueueState<E> {
+        delegate!(self, queue => queue.save_state())
+    }
+
+    fn restore_state(&mut self, state: QueueState<E>) {
+        delegate!(self, queue => queue.restore_state(state))
+    }
+}
+
+
+#[cfg(test)]
+pub(crate) mod test {
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    pub(crate) struct Job {
+        pub(crate) timestamp: f64,
+        pub(crate) priority: i32,
+        pub(crate) name: String,
+    }
+
+    impl Job {
+        pub(crate) fn new(timestamp: f64, name: &str) -> Job {
+            Job { timestamp, priority: 0, name: name.to_owned() }
+        }
+    }
+
+    impl Event for Job {
+        type TyEventType = ();
+        type TyTime = f64;
+
+        fn timestamp(&self) -> f64 {
+            self.timestamp
+        }
+
+        fn set_timestamp(&mut self, timestamp: f64) {
+            self.timestamp = timestamp;
+        }
+
+        fn event_type(&self) {}
+
+        fn event_id(&self) -> String {
+            self.name.clone()
+        }
+
+        fn destination(&self) -> String {
+            String::new()
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+    }
+
+    pub(crate) fn drain<Q: EventQueue<TyEvent = Job>>(queue: &mut Q) -> Vec<String> {
+        let mut order = Vec::new();
+        while let Some(job) = queue.pop() {
+            order.push(job.name);
+        }
+        order
+    }
+
+    /// Runs a random mix of adds, pops, cancellations and reschedules, with many ties, a
+    /// growing and then shrinking queue and occasional far-future events. Returns the
+    /// names of the delivered events in order.
+    pub(crate) fn random_workload<Q: EventQueue<TyEvent = Job>>(queue: &mut Q, seed: u64) -> Vec<String> {
+        use rand::Rng;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut now = 0.0;
+        let mut handles = Vec::new();
+        let mut delivered = Vec::new();
+        for step in 0..20_000 {
+            let growing = step % 5000 < 3000;
+            match rng.gen_range(0..10) {
+                0..=4 if growing || rng.gen_bool(0.3) => {
+                    let delay: f64 = if rng.gen_bool(0.02) { rng.gen_range(0.0..1e4) } else { rng.gen_range(0.0..20.0) };
+                    let timestamp = now + (delay * 4.0).round() / 4.0;
+                    let priority = rng.gen_range(-1..=1);
+                    handles.push(queue.add(Job { timestamp, priority, name: format!("job {}", step) }));
+                }
+                7 if !handles.is_empty() => {
+                    let handle = handles.swap_remove(rng.gen_range(0..handles.len()));
+                    queue.cancel(handle);
+                }
+                8 if !handles.is_empty() => {
+                    let handle = handles[rng.gen_range(0..handles.len())];
+                    queue.reschedule(handle, now + rng.gen_range(0..40) as f64 / 4.0);
+                }
+                _ => {
+                    if let Some(job) = queue.pop() {
+                        assert!(job.timestamp >= now);
+                        now = job.timestamp;
+                        delivered.push(job.name);
+                    }
+                }
+            }
+        }
+        delivered.extend(drain(queue));
+        delivered
+    }
+
+    #[test]
+    fn test_delivery_order() {
+        let mut queue = SimpleQueue::with_seed(0);
+        for (timestamp, priority, name) in [
+            (2.0, 0, "late"),
+            (1.0, 0, "first"),
+            (1.0, 0, "second"),
+            (1.0, 5, "urgent"),
+            (1.0, -1, "background"),
+            (1.0, 0, "third"),
+        ] {
+            queue.add(Job { timestamp, priority, name: name.to_owned() });
+        }
+        assert_eq!(vec!["urgent", "first", "second", "third", "background", "late"], drain(&mut queue));
+    }
+
+    #[test]
+    fn test_cancelled_events_are_never_delivered() {
+        let mut queue = SimpleQueue::with_seed(0);
+        let first = queue.add(Job::new(1.0, "first"));
+        queue.add(Job::new(2.0, "second"));
+        let timeout = queue.add(Job::new(3.0, "timeout"));
+
+        assert_eq!("first", queue.cancel(first).unwrap().name);
+        assert!(queue.cancel(first).is_none());
+        assert_eq!(Some(2.0), queue.peek_timestamp());
+
+        assert!(queue.cancel(timeout).is_some());
+        assert_eq!(vec!["second"], drain(&mut queue));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_reschedule() {
+        let mut queue = SimpleQueue::with_seed(0);
+        let a = queue.add(Job::new(1.0, "a"));
+        queue.add(Job::new(2.0, "b"));
+        queue.add(Job::new(3.0, "c"));
+
+        assert!(queue.reschedule(a, 2.0));
+        // Rescheduled events go after events already scheduled for the same time.
+        assert_eq!(vec!["b", "a", "c"], drain(&mut queue));
+        assert!(!queue.reschedule(a, 5.0));
+    }
+
+    #[test]
+    fn test_handle_survives_reschedule() {
+        let mut queue = SimpleQueue::with_seed(0);
+        let handle = queue.add(Job::new(1.0, "a"));
+        queue.reschedule(handle, 4.0);
+        queue.reschedule(handle, 3.0);
+        assert_eq!(Some(3.0), queue.peek_timestamp());
+        assert!(queue.cancel(handle).is_some());
+        assert!(queue.is_empty());
+        assert_eq!(None, queue.peek_timestamp());
+    }
+
+    #[test]
+    fn test_unordered_timestamps_come_first() {
+        for kind in QueueKind::ALL {
+            let mut queue = AnyQueue::with_seed(kind, 0);
+            queue.add(Job::new(1.0, "a"));
+            queue.add(Job::new(f64::NAN, "nan"));
+            let moved = queue.add(Job::new(2.0, "moved"));
+            let cancelled = queue.add(Job::new(f64::NAN, "cancelled"));
+            queue.add(Job::new(0.5, "b"));
+            assert!(queue.reschedule(moved, f64::NAN));
+            queue.cancel(cancelled);
+
+            assert!(queue.peek_timestamp().unwrap().is_nan(), "{}", kind.name());
+            assert_eq!(vec!["nan", "moved", "b", "a"], drain(&mut queue), "{}", kind.name());
+        }
+    }
+
+    #[test]
+    fn test_many_cancellations_compact_the_heap() {
+        let mut queue = SimpleQueue::with_seed(0);
+        let handles: Vec<EventHandle> = (0..1000).map(|i| queue.add(Job::new(i as f64, "job"))).collect();
+        for handle in handles.iter().skip(1) {
+            queue.cancel(*handle);
+        }
+        assert!(queue.queue.len() < 100);
+        assert_eq!(vec!["job"], drain(&mut queue));
+    }
+}
@@ -0,0 +1,1016 @@
+//! Queueing-network building blocks: sources, multi-server stations, routers and sinks.
+//!
+//! The blocks exchange `NetworkEvent`s carrying `Customer`s and work with any event queue
+//! holding that event type. A `Network` assembles them into a `Simulation`:
+//!
+//! ```ignore
+//! let mut simulation = Network::new()
+//!     .source(Source::new("arrivals", Distribution::Exponential { rate: 0.9 }, "server"))
+//!     .station(Station::new("server", 2, Distribution::Exponential { rate: 0.5 }).to("exit"))
+//!     .sink(Sink::new("exit"))
+//!     .build(SimpleQueue::with_seed(1));
+//! simulation.run_until(1000.0)?;
+//! ```
+//!
+//! The blocks implement `Snapshot`, so a network can be checkpointed; the collectors of
+//! `Statistics` are not part of their state.
+//!
+//! # Blocking
+//!
+//! A station with a full buffer and `Overflow::Block` keeps the arriving customer aside and
+//! replies `Blocked` to the sender, whose server then stays occupied until the station
+//! admits the customer and replies `Unblocked` (blocking after service). A blocked source
+//! stops generating customers. To learn whether its customer was accepted, a station
+//! releases a server through a `Transferred` event sent to itself at the lowest priority,
+//! so that any `Blocked` reply at the same time is delivered first.
+//!
+//! # Statistics
+//!
+//! Stations and sinks given a `Statistics` handle record, under their name:
+//! - station: counters `arrivals` and `dropped`, tallies `waiting_time` and `sojourn`
+//!   (time at the station), levels `queue_length`, `in_system` and `utilisation`;
+//! - sink: counter `departures` and tally `sojourn` (time since the customer was created).
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::event_sim::checkpoint::{DynSnapshot, Snapshot};
+use crate::event_sim::stats::{ComponentStats, Statistics};
+use crate::event_sim::{Actor, BoxedActor, Event, EventHandle, EventQueue, Simulation};
+
+/// Probability distribution of inter-arrival or service times.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Distribution {
+    Exponential { rate: f64 },
+    Deterministic(f64),
+    /// Draws uniformly from observed values.
+    Empirical(Vec<f64>),
+}
+
+impl Distribution {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self {
+            Distribution::Exponential { rate } => -(1.0 - rng.gen::<f64>()).ln() / rate,
+            Distribution::Deterministic(value) => *value,
+            Distribution::Empirical(values) => {
+                assert!(!values.is_empty(), "Empirical distribution has no values.");
+                values[rng.gen_range(0..values.len())]
+            }
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        match self {
+            Distribution::Exponential { rate } => 1.0 / rate,
+            Distribution::Deterministic(value) => *value,
+            Distribution::Empirical(values) => values.iter().sum::<f64>() / values.len() as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Customer {
+    /// Unique within the source that created the customer.
+    pub id: u64,
+    pub source: String,
+    /// Served first by `Discipline::Priority` stations when higher.
+    pub class: i32,
+    pub created: f64,
+    /// Arrival time at the current station.
+    pub arrived: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NetworkEventKind {
+    /// A source creates its next customer.
+    Generate,
+    /// A customer reaches a station, router or sink. `server` is the index of the
+    /// sender's server, echoed in `Blocked` and `Unblocked` replies.
+    Arrival { customer: Customer, server: usize },
+    /// A station finishes serving the customer of `server`.
+    Departure { server: usize },
+    /// The customer of `server` was accepted downstream unless a `Blocked` reply came first.
+    Transferred { server: usize },
+    Blocked { server: usize },
+    Unblocked { server: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkEvent {
+    pub timestamp: f64,
+    pub priority: i32,
+    /// Actor that sent the event.
+    pub source: String,
+    pub destination: String,
+    pub kind: NetworkEventKind,
+}
+
+impl NetworkEvent {
+    pub fn new(timestamp: f64, source: &str, destination: &str, kind: NetworkEventKind) -> NetworkEvent {
+        NetworkEvent {
+            timestamp,
+            priority: 0,
+            source: source.to_owned(),
+            destination: destination.to_owned(),
+            kind,
+        }
+    }
+}
+
+impl Event for NetworkEvent {
+    type TyEventType = NetworkEventKind;
+    type TyTime = f64;
+
+    fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    fn set_timestamp(&mut self, timestamp: f64) {
+        self.timestamp = timestamp;
+    }
+
+    fn event_type(&self) -> NetworkEventKind {
+        self.kind.clone()
+    }
+
+    fn event_id(&self) -> String {
+        match &self.kind {
+            NetworkEventKind::Arrival { customer, .. } => format!("{}#{}", customer.source, customer.id),
+            kind => format!("{}:{:?}", self.destination, kind),
+        }
+    }
+
+    fn destination(&self) -> String {
+        self.destination.clone()
+    }
+
+    fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
+/// Generates customers with a given inter-arrival distribution. The first customer is
+/// created when the source is started.
+pub struct Source<Q> {
+    name: String,
+    interarrival: Distribution,
+    destination: String,
+    class: i32,
+    limit: Option<u64>,
+    generated: u64,
+    next: Option<EventHandle>,
+    blocked: bool,
+    _queue: PhantomData<fn() -> Q>,
+}
+
+impl<Q> Source<Q> {
+    pub fn new(name: &str, interarrival: Distribution, destination: &str) -> Source<Q> {
+        Source {
+            name: name.to_owned(),
+            interarrival,
+            destination: destination.to_owned(),
+            class: 0,
+            limit: None,
+            generated: 0,
+            next: None,
+            blocked: false,
+            _queue: PhantomData,
+        }
+    }
+
+    pub fn class(mut self, class: i32) -> Source<Q> {
+        self.class = class;
+        self
+    }
+
+    /// Stops after generating `limit` customers.
+    pub fn limit(mut self, limit: u64) -> Source<Q> {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// The event that makes the source generate its first customer at `timestamp`.
+    pub fn start_event(&self, timestamp: f64) -> NetworkEvent {
+        NetworkEvent::new(timestamp, &self.name, &self.name, NetworkEventKind::Generate)
+    }
+
+    pub fn generated(&self) -> u64 {
+        self.generated
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.blocked
+    }
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent>> Source<Q> {
+    fn schedule_next(&mut self, now: f64, queue: &mut Q) {
+        if self.limit.is_some_and(|limit| self.generated >= limit) {
+            self.next = None;
+            return;
+        }
+        let timestamp = now + self.interarrival.sample(queue.rng());
+        self.next = Some(queue.add(self.start_event(timestamp)));
+    }
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent> + 'static> Actor for Source<Q> {
+    type TyEvent = NetworkEvent;
+    type TyQueue = Q;
+
+    fn handle_event(&mut self, event: NetworkEvent, queue: &mut Q) {
+        let now = event.timestamp;
+        match event.kind {
+            NetworkEventKind::Generate => {
+                if self.blocked || self.limit.is_some_and(|limit| self.generated >= limit) {
+                    return;
+                }
+                let customer = Customer {
+                    id: self.generated,
+                    source: self.name.clone(),
+                    class: self.class,
+                    created: now,
+                    arrived: now,
+                };
+                self.generated += 1;
+                queue.add(NetworkEvent::new(now, &self.name, &self.destination,
+                                            NetworkEventKind::Arrival { customer, server: 0 }));
+                self.schedule_next(now, queue);
+            }
+            NetworkEventKind::Blocked { .. } => {
+                self.blocked = true;
+                if let Some(next) = self.next.take() {
+                    queue.cancel(next);
+                }
+            }
+            NetworkEventKind::Unblocked { .. } => {
+                self.blocked = false;
+                self.schedule_next(now, queue);
+            }
+            _ => {}
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_snapshot(&mut self) -> Option<&mut dyn DynSnapshot> {
+        Some(self)
+    }
+}
+
+/// Saved state of a `Source`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceState {
+    generated: u64,
+    next: Option<EventHandle>,
+    blocked: bool,
+}
+
+impl<Q> Snapshot for Source<Q> {
+    type State = SourceState;
+
+    fn snapshot(&self) -> SourceState {
+        SourceState { generated: self.generated, next: self.next, blocked: self.blocked }
+    }
+
+    fn restore(&mut self, state: SourceState) {
+        self.generated = state.generated;
+        self.next = state.next;
+        self.blocked = state.blocked;
+    }
+}
+
+/// Order in which waiting customers are served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Discipline {
+    Fifo,
+    Lifo,
+    /// Highest `Customer::class` first, FIFO within a class.
+    Priority,
+}
+
+/// What a station does with customers arriving at a full buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    Drop,
+    /// Block the sender until there is room; see the module documentation.
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ServerState {
+    Idle,
+    Busy(Customer),
+    /// Service finished; waiting for the `Transferred` event.
+    Transferring,
+    Blocked,
+}
+
+/// Customers held back by a full `Overflow::Block` station, with the server that sent them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BlockedArrival {
+    customer: Customer,
+    sender: String,
+    server: usize,
+}
+
+/// A station with `servers` identical servers, a waiting line and an optional finite
+/// buffer. Served customers are sent to the destination, or leave the network if there is
+/// none.
+pub struct Station<Q> {
+    name: String,
+    service: Distribution,
+    discipline: Discipline,
+    /// Maximum number of waiting customers, not counting those in service.
+    buffer: Option<usize>,
+    overflow: Overflow,
+    destination: Option<String>,
+    servers: Vec<ServerState>,
+    waiting: VecDeque<Customer>,
+    blocked_arrivals: VecDeque<BlockedArrival>,
+    arrivals: u64,
+    served: u64,
+    dropped: u64,
+    stats: Option<ComponentStats>,
+    _queue: PhantomData<fn() -> Q>,
+}
+
+impl<Q> Station<Q> {
+    pub fn new(name: &str, servers: usize, service: Distribution) -> Station<Q> {
+        assert!(servers > 0, "A station needs at least one server.");
+        Station {
+            name: name.to_owned(),
+            service,
+            discipline: Discipline::Fifo,
+            buffer: None,
+            overflow: Overflow::Drop,
+            destination: None,
+            servers: vec![ServerState::Idle; servers],
+            waiting: VecDeque::new(),
+            blocked_arrivals: VecDeque::new(),
+            arrivals: 0,
+            served: 0,
+            dropped: 0,
+            stats: None,
+            _queue: PhantomData,
+        }
+    }
+
+    pub fn discipline(mut self, discipline: Discipline) -> Station<Q> {
+        self.discipline = discipline;
+        self
+    }
+
+    pub fn buffer(mut self, capacity: usize, overflow: Overflow) -> Station<Q> {
+        self.buffer = Some(capacity);
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn to(mut self, destination: &str) -> Station<Q> {
+        self.destination = Some(destination.to_owned());
+        self
+    }
+
+    /// Records the station's statistics; see the module documentation.
+    pub fn stats(mut self, statistics: &Statistics) -> Station<Q> {
+        let collectors = ["arrivals", "dropped", "waiting_time", "sojourn", "queue_length", "in_system", "utilisation"];
+        self.stats = Some(ComponentStats::new(statistics, &self.name, &collectors));
+        self
+    }
+
+    pub fn arrivals(&self) -> u64 {
+        self.arrivals
+    }
+
+    pub fn served(&self) -> u64 {
+        self.served
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Customers in service or waiting, not counting blocked arrivals.
+    pub fn in_system(&self) -> usize {
+        self.busy_servers() + self.waiting.len()
+    }
+
+    pub fn busy_servers(&self) -> usize {
+        self.servers.iter().filter(|state| matches!(state, ServerState::Busy(_))).count()
+    }
+
+    /// Servers holding a customer that could not be passed downstream.
+    pub fn blocked_servers(&self) -> usize {
+        self.servers.iter().filter(|state| **state == ServerState::Blocked).count()
+    }
+
+    fn push_waiting(&mut self, customer: Customer) {
+        match self.discipline {
+            Discipline::Fifo | Discipline::Lifo => self.waiting.push_back(customer),
+            Discipline::Priority => {
+                let position = self.waiting.partition_point(|other| other.class >= customer.class);
+                self.waiting.insert(position, customer);
+            }
+        }
+    }
+
+    fn pop_waiting(&mut self) -> Option<Customer> {
+        match self.discipline {
+            Discipline::Fifo | Discipline::Priority => self.waiting.pop_front(),
+            Discipline::Lifo => self.waiting.pop_back(),
+        }
+    }
+
+    fn idle_server(&self) -> Option<usize> {
+        self.servers.iter().position(|state| *state == ServerState::Idle)
+    }
+
+    fn has_room(&self) -> bool {
+        self.buffer.is_none_or(|capacity| self.waiting.len() < capacity)
+    }
+
+    fn count(&self, counter: &str) {
+        if let Some(stats) = &self.stats {
+            stats.count(counter, 1);
+        }
+    }
+
+    fn tally(&self, tally: &str, value: f64) {
+        if let Some(stats) = &self.stats {
+            stats.tally(tally, value);
+        }
+    }
+
+    fn record_levels(&self, now: f64) {
+        if let Some(stats) = &self.stats {
+            stats.level("queue_length", now, self.waiting.len() as f64);
+            stats.level("in_system", now, self.in_system() as f64);
+            stats.level("utilisation", now,
+                        self.busy_servers() as f64 / self.servers.len() as f64);
+        }
+    }
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent>> Station<Q> {
+    fn start_service(&mut self, server: usize, customer: Customer, now: f64, queue: &mut Q) {
+        let service_time = self.service.sample(queue.rng());
+        self.tally("waiting_time", now - customer.arrived);
+        self.servers[server] = ServerState::Busy(customer);
+        queue.add(NetworkEvent::new(now + service_time, &self.name, &self.name,
+                                    NetworkEventKind::Departure { server }));
+    }
+
+    fn arrive(&mut self, mut customer: Customer, sender: String, sender_server: usize, now: f64, queue: &mut Q) {
+        self.arrivals += 1;
+        self.count("arrivals");
+        customer.arrived = now;
+        if let Some(server) = self.idle_server() {
+            self.start_service(server, customer, now, queue);
+        } else if self.has_room() {
+            self.push_waiting(customer);
+        } else if self.overflow == Overflow::Drop {
+            self.dropped += 1;
+            self.count("dropped");
+        } else {
+            queue.add(NetworkEvent::new(now, &self.name, &sender,
+                                        NetworkEventKind::Blocked { server: sender_server }));
+            self.blocked_arrivals.push_back(BlockedArrival { customer, sender, server: sender_server });
+        }
+    }
+
+    /// Starts waiting customers on idle servers and admits blocked arrivals while there
+    /// is room.
+    fn fill_servers(&mut self, now: f64, queue: &mut Q) {
+        loop {
+            if let Some(server) = self.idle_server() {
+                if let Some(customer) = self.pop_waiting() {
+                    self.start_service(server, customer, now, queue);
+                    continue;
+                }
+            }
+            let can_admit = self.has_room() || (self.waiting.is_empty() && self.idle_server().is_some());
+            match self.blocked_arrivals.front() {
+                Some(_) if can_admit => {
+                    let BlockedArrival { mut customer, sender, server } =
+                        self.blocked_arrivals.pop_front().expect("there is a blocked arrival");
+                    customer.arrived = now;
+                    self.push_waiting(customer);
+                    queue.add(NetworkEvent::new(now, &self.name, &sender, NetworkEventKind::Unblocked { server }));
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn release(&mut self, server: usize, now: f64, queue: &mut Q) {
+        self.servers[server] = ServerState::Idle;
+        self.fill_servers(now, queue);
+    }
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent> + 'static> Actor for Station<Q> {
+    type TyEvent = NetworkEvent;
+    type TyQueue = Q;
+
+    fn handle_event(&mut self, event: NetworkEvent, queue: &mut Q) {
+        let now = event.timestamp;
+        match event.kind {
+            NetworkEventKind::Arrival { customer, server } => self.arrive(customer, event.source, server, now, queue),
+            NetworkEventKind::Departure { server } => {
+                let customer = match std::mem::replace(&mut self.servers[server], ServerState::Transferring) {
+                    ServerState::Busy(customer) => customer,
+                    state => panic!("Departure from server {} of {} in state {:?}.", server, self.name, state),
+                };
+                self.served += 1;
+                self.tally("sojourn", now - customer.arrived);
+                match &self.destination {
+                    Some(destination) => {
+                        queue.add(NetworkEvent::new(now, &self.name, destination,
+                                                    NetworkEventKind::Arrival { customer, server }));
+                        let mut transferred = NetworkEvent::new(now, &self.name, &self.name,
+                                                                NetworkEventKind::Transferred { server });
+                        transferred.priority = i32::MIN;
+                        queue.add(transferred);
+                    }
+                    None => self.release(server, now, queue),
+                }
+            }
+            NetworkEventKind::Transferred { server } => {
+                if self.servers[server] == ServerState::Transferring {
+                    self.release(server, now, queue);
+                }
+            }
+            NetworkEventKind::Blocked { server } => {
+                if self.servers[server] == ServerState::Transferring {
+                    self.servers[server] = ServerState::Blocked;
+                }
+            }
+            NetworkEventKind::Unblocked { server } => self.release(server, now, queue),
+            NetworkEventKind::Generate => {}
+        }
+        self.record_levels(now);
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_snapshot(&mut self) -> Option<&mut dyn DynSnapshot> {
+        Some(self)
+    }
+}
+
+/// Saved state of a `Station`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationState {
+    servers: Vec<ServerState>,
+    waiting: VecDeque<Customer>,
+    blocked_arrivals: VecDeque<BlockedArrival>,
+    arrivals: u64,
+    served: u64,
+    dropped: u64,
+}
+
+impl<Q> Snapshot for Station<Q> {
+    type State = StationState;
+
+    fn snapshot(&self) -> StationState {
+        StationState {
+            servers: self.servers.clone(),
+            waiting: self.waiting.clone(),
+            blocked_arrivals: self.blocked_arrivals.clone(),
+            arrivals: self.arrivals,
+            served: self.served,
+            dropped: self.dropped,
+        }
+    }
+
+    fn restore(&mut self, state: StationState) {
+        self.servers = state.servers;
+        self.waiting = state.waiting;
+        self.blocked_arrivals = state.blocked_arrivals;
+        self.arrivals = state.arrivals;
+        self.served = state.served;
+        self.dropped = state.dropped;
+    }
+}
+
+/// Forwards each arriving customer to one of its routes, chosen with probability
+/// proportional to the route's weight. Replies from the destination go straight back to
+/// the original sender.
+pub struct Router<Q> {
+    name: String,
+    routes: Vec<(String, f64)>,
+    routed: Vec<u64>,
+    _queue: PhantomData<fn() -> Q>,
+}
+
+impl<Q> Router<Q> {
+    pub fn new(name: &str) -> Router<Q> {
+        Router { name: name.to_owned(), routes: Vec::new(), routed: Vec::new(), _queue: PhantomData }
+    }
+
+    pub fn route(mut self, destination: &str, weight: f64) -> Router<Q> {
+        assert!(weight >= 0.0 && weight.is_finite(), "Route weights must be non-negative.");
+        self.routes.push((destination.to_owned(), weight));
+        self.routed.push(0);
+        self
+    }
+
+    /// Whether some route has a positive weight, as a router needs to forward customers.
+    pub fn has_routes(&self) -> bool {
+        self.routes.iter().any(|(_, weight)| *weight > 0.0)
+    }
+
+    /// Number of customers sent to `destination`.
+    pub fn routed(&self, destination: &str) -> u64 {
+        self.routes.iter().zip(self.routed.iter())
+            .filter(|((name, _), _)| name == destination)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let total: f64 = self.routes.iter().map(|(_, weight)| weight).sum();
+        let mut target = rng.gen::<f64>() * total;
+        for (index, (_, weight)) in self.routes.iter().enumerate() {
+            if target < *weight {
+                return index;
+            }
+            target -= weight;
+        }
+        self.routes.iter().rposition(|(_, weight)| *weight > 0.0).expect("Router has no routes.")
+    }
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent> + 'static> Actor for Router<Q> {
+    type TyEvent = NetworkEvent;
+    type TyQueue = Q;
+
+    fn handle_event(&mut self, mut event: NetworkEvent, queue: &mut Q) {
+        if let NetworkEventKind::Arrival { .. } = event.kind {
+            let index = self.choose(queue.rng());
+            self.routed[index] += 1;
+            event.destination = self.routes[index].0.clone();
+            queue.add(event);
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_snapshot(&mut self) -> Option<&mut dyn DynSnapshot> {
+        Some(self)
+    }
+}
+
+impl<Q> Snapshot for Router<Q> {
+    /// Number of customers sent along each route.
+    type State = Vec<u64>;
+
+    fn snapshot(&self) -> Vec<u64> {
+        self.routed.clone()
+    }
+
+    fn restore(&mut self, routed: Vec<u64>) {
+        self.routed = routed;
+    }
+}
+
+/// Absorbs customers leaving the network.
+pub struct Sink<Q> {
+    name: String,
+    departures: u64,
+    total_sojourn: f64,
+    stats: Option<ComponentStats>,
+    _queue: PhantomData<fn() -> Q>,
+}
+
+impl<Q> Sink<Q> {
+    pub fn new(name: &str) -> Sink<Q> {
+        Sink { name: name.to_owned(), departures: 0, total_sojourn: 0.0, stats: None, _queue: PhantomData }
+    }
+
+    /// Records the sink's statistics; see the module documentation.
+    pub fn stats(mut self, statistics: &Statistics) -> Sink<Q> {
+        self.stats = Some(ComponentStats::new(statistics, &self.name, &["departures", "sojourn"]));
+        self
+    }
+
+    pub fn departures(&self) -> u64 {
+        self.departures
+    }
+
+    /// Mean time from creation to arrival at the sink.
+    pub fn mean_sojourn(&self) -> f64 {
+        self.total_sojourn / self.departures as f64
+    }
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent> + 'static> Actor for Sink<Q> {
+    type TyEvent = NetworkEvent;
+    type TyQueue = Q;
+
+    fn handle_event(&mut self, event: NetworkEvent, _queue: &mut Q) {
+        if let NetworkEventKind::Arrival { customer, .. } = event.kind {
+            let sojourn = event.timestamp - customer.created;
+            self.departures += 1;
+            self.total_sojourn += sojourn;
+            if let Some(stats) = &self.stats {
+                stats.count("departures", 1);
+                stats.tally("sojourn", sojourn);
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_snapshot(&mut self) -> Option<&mut dyn DynSnapshot> {
+        Some(self)
+    }
+}
+
+/// Saved state of a `Sink`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SinkState {
+    departures: u64,
+    total_sojourn: f64,
+}
+
+impl<Q> Snapshot for Sink<Q> {
+    type State = SinkState;
+
+    fn snapshot(&self) -> SinkState {
+        SinkState { departures: self.departures, total_sojourn: self.total_sojourn }
+    }
+
+    fn restore(&mut self, state: SinkState) {
+        self.departures = state.departures;
+        self.total_sojourn = state.total_sojourn;
+    }
+}
+
+/// Collects building blocks and assembles them into a simulation.
+pub struct Network<Q: EventQueue<TyEvent = NetworkEvent>> {
+    actors: Vec<BoxedActor<Q>>,
+    start_events: Vec<NetworkEvent>,
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent> + 'static> Network<Q> {
+    pub fn new() -> Network<Q> {
+        Network { actors: Vec::new(), start_events: Vec::new() }
+    }
+
+    /// Adds a source that starts at time 0.
+    pub fn source(mut self, source: Source<Q>) -> Network<Q> {
+        self.start_events.push(source.start_event(0.0));
+        self.actors.push(Box::new(source));
+        self
+    }
+
+    pub fn station(mut self, station: Station<Q>) -> Network<Q> {
+        self.actors.push(Box::new(station));
+        self
+    }
+
+    /// Panics if the router has no route with a positive weight.
+    pub fn router(mut self, router: Router<Q>) -> Network<Q> {
+        assert!(router.has_routes(), "Router {} has no route with a positive weight.", router.name);
+        self.actors.push(Box::new(router));
+        self
+    }
+
+    pub fn sink(mut self, sink: Sink<Q>) -> Network<Q> {
+        self.actors.push(Box::new(sink));
+        self
+    }
+
+    /// Any other actor handling `NetworkEvent`s.
+    pub fn actor(mut self, actor: BoxedActor<Q>) -> Network<Q> {
+        self.actors.push(actor);
+        self
+    }
+
+    pub fn build(self, queue: Q) -> Simulation<Q> {
+        let mut simulation = Simulation::new(queue, 0.0);
+        for actor in self.actors {
+            simulation.add_actor(actor);
+        }
+        for event in self.start_events {
+            simulation.schedule(event).expect("Sources start at time 0.");
+        }
+        simulation
+    }
+}
+
+impl<Q: EventQueue<TyEvent = NetworkEvent> + 'static> Default for Network<Q> {
+    fn default() -> Self {
+        Network::new()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event_sim::checkpoint::Checkpoint;
+    use crate::event_sim::SimpleQueue;
+
+    type Queue = SimpleQueue<NetworkEvent>;
+
+    #[test]
+    fn test_mmc_conserves_customers() {
+        let mut simulation = Network::new()
+            .source(Source::new("arrivals", Distribution::Exponential { rate: 1.8 }, "server"))
+            .station(Station::new("server", 2, Distribution::Exponential { rate: 1.0 }).to("exit"))
+            .sink(Sink::new("exit"))
+            .build(Queue::with_seed(7));
+        simulation.run_until(10_000.0).unwrap();
+
+        let source: &Source<Queue> = simulation.actor_as("arrivals").unwrap();
+        let station: &Station<Queue> = simulation.actor_as("server").unwrap();
+        let sink: &Sink<Queue> = simulation.actor_as("exit").unwrap();
+        assert_eq!(source.generated(), station.arrivals());
+        assert_eq!(station.arrivals(), station.served() + station.in_system() as u64);
+        assert_eq!(station.served(), sink.departures());
+        // M/M/2 with utilisation 0.9: the mean sojourn time is 1 / (mu (1 - rho^2)) = 5.26.
+        assert!((sink.mean_sojourn() - 5.26).abs() < 0.6, "{}", sink.mean_sojourn());
+    }
+
+    /// Five customers arrive while the first is in service; returns the classes of the
+    /// customers in the order they finish.
+    fn service_order(discipline: Discipline) -> Vec<i32> {
+        struct Recorder {
+            order: Vec<i32>,
+        }
+
+        impl Actor for Recorder {
+            type TyEvent = NetworkEvent;
+            type TyQueue = Queue;
+
+            fn handle_event(&mut self, event: NetworkEvent, _queue: &mut Queue) {
+                if let NetworkEventKind::Arrival { customer, .. } = event.kind {
+                    self.order.push(customer.class);
+                }
+            }
+
+            fn name(&self) -> String {
+                "recorder".to_owned()
+            }
+
+            fn as_any(&self) -> Option<&dyn Any> {
+                Some(self)
+            }
+        }
+
+        let mut network = Network::new()
+            .station(Station::new("server", 1, Distribution::Deterministic(10.0)).discipline(discipline).to("recorder"))
+            .actor(Box::new(Recorder { order: Vec::new() }));
+        for (index, class) in [0, 1, 2, 1, 3, 0].into_iter().enumerate() {
+            let source = Source::new(&format!("source {}", index), Distribution::Deterministic(index as f64), "server")
+                .class(class)
+                .limit(1);
+            network = network.source(source);
+        }
+        let mut simulation = network.build(Queue::with_seed(0));
+        simulation.run().unwrap();
+        simulation.actor_as::<Recorder>("recorder").unwrap().order.clone()
+    }
+
+    #[test]
+    fn test_disciplines() {
+        // All sources start at time 0, in order; the first customer is served immediately.
+        assert_eq!(vec![0, 1, 2, 1, 3, 0], service_order(Discipline::Fifo));
+        assert_eq!(vec![0, 0, 3, 1, 2, 1], service_order(Discipline::Lifo));
+        assert_eq!(vec![0, 3, 2, 1, 1, 0], service_order(Discipline::Priority));
+    }
+
+    #[test]
+    fn test_finite_buffer_drops() {
+        let mut simulation = Network::new()
+            .source(Source::new("arrivals", Distribution::Deterministic(1.0), "server").limit(100))
+            .station(Station::new("server", 1, Distribution::Deterministic(10.0)).buffer(2, Overflow::Drop))
+            .build(Queue::with_seed(0));
+        simulation.run().unwrap();
+
+        let station: &Station<Queue> = simulation.actor_as("server").unwrap();
+        assert_eq!(100, station.arrivals());
+        // One customer in service and two waiting at any time: one in ten is served.
+        assert_eq!(12, station.served());
+        assert_eq!(88, station.dropped());
+    }
+
+    #[test]
+    fn test_blocking_holds_upstream_servers() {
+        let mut simulation = Network::new()
+            .source(Source::new("arrivals", Distribution::Deterministic(1.0), "fast").limit(50))
+            .station(Station::new("fast", 1, Distribution::Deterministic(1.0)).buffer(1, Overflow::Block).to("slow"))
+            .station(Station::new("slow", 1, Distribution::Deterministic(5.0)).buffer(1, Overflow::Block).to("exit"))
+            .sink(Sink::new("exit"))
+            .build(Queue::with_seed(0));
+
+        simulation.run_until(40.0).unwrap();
+        let fast: &Station<Queue> = simulation.actor_as("fast").unwrap();
+        let source: &Source<Queue> = simulation.actor_as("arrivals").unwrap();
+        assert_eq!(1, fast.blocked_servers());
+        assert!(source.is_blocked());
+
+        simulation.run().unwrap();
+        let slow: &Station<Queue> = simulation.actor_as("slow").unwrap();
+        let sink: &Sink<Queue> = simulation.actor_as("exit").unwrap();
+        assert_eq!(0, slow.dropped());
+        assert_eq!(50, sink.departures());
+        // The slow station is the bottleneck and never idles once started.
+        assert_eq!(1.0 + 50.0 * 5.0, simulation.now());
+    }
+
+    #[test]
+    fn test_restored_network_continues_identically() {
+        let network = || Network::new()
+            .source(Source::new("arrivals", Distribution::Exponential { rate: 1.0 }, "split").limit(500))
+            .router(Router::new("split").route("fast", 1.0).route("slow", 1.0))
+            .station(Station::new("fast", 1, Distribution::Exponential { rate: 1.0 }).buffer(1, Overflow::Block).to("slow"))
+            .station(Station::new("slow", 2, Distribution::Exponential { rate: 0.6 }).buffer(2, Overflow::Block).to("exit"))
+            .sink(Sink::new("exit"))
+            .build(Queue::with_seed(11));
+        let mut uninterrupted = network();
+        uninterrupted.run().unwrap();
+
+        let mut first_half = network();
+        first_half.run_until(200.0).unwrap();
+        let mut file = Vec::new();
+        first_half.checkpoint().unwrap().write_json(&mut file).unwrap();
+        let mut resumed = network();
+        resumed.restore(Checkpoint::read_json(&file[..]).unwrap()).unwrap();
+        resumed.run().unwrap();
+
+        assert_eq!(uninterrupted.now(), resumed.now());
+        for name in ["fast", "slow"] {
+            let expected: &Station<Queue> = uninterrupted.actor_as(name).unwrap();
+            let actual: &Station<Queue> = resumed.actor_as(name).unwrap();
+            assert_eq!((expected.arrivals(), expected.served()), (actual.arrivals(), actual.served()));
+        }
+        let expected: &Sink<Queue> = uninterrupted.actor_as("exit").unwrap();
+        let actual: &Sink<Queue> = resumed.actor_as("exit").unwrap();
+        assert_eq!(500, actual.departures());
+        assert_eq!(expected.mean_sojourn(), actual.mean_sojourn());
+        let router: &Router<Queue> = resumed.actor_as("split").unwrap();
+        assert_eq!(500, router.routed("fast") + router.routed("slow"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Router split has no route with a positive weight.")]
+    fn test_router_needs_a_route() {
+        Network::<Queue>::new().router(Router::new("split").route("a", 0.0));
+    }
+
+    #[test]
+    fn test_router_splits_by_weight() {
+        let mut simulation = Network::new()
+            .source(Source::new("arrivals", Distribution::Empirical(vec![0.5, 1.5]), "split").limit(10_000))
+            .router(Router::new("split").route("a", 3.0).route("b", 1.0))
+            .sink(Sink::new("a"))
+            .sink(Sink::new("b"))
+            .build(Queue::with_seed(3));
+        simulation.run().unwrap();
+
+        let router: &Router<Queue> = simulation.actor_as("split").unwrap();
+        let a: &Sink<Queue> = simulation.actor_as("a").unwrap();
+        assert_eq!(router.routed("a"), a.departures());
+        assert_eq!(10_000, router.routed("a") + router.routed("b"));
+        assert!((7300..7700).contains(&a.departures()), "{}", a.departures());
+    }
+}
@@ -0,0 +1,669 @@
+//! Shared primitives for process models: counted resources, item stores and containers.
+//!
+//! They are created by a `ProcessModel` and used from its processes. A process blocked on
+//! one of them is resumed by an event scheduled at the time its request is satisfied, so
+//! blocking follows the simulation clock like `Process::hold`. Requests are served in
+//! order of arrival, and in order of priority for resources.
+//!
+//! Each primitive records statistics once given a `Statistics` handle:
+//! - resource: levels `in_use`, `utilisation` (fraction of units in use) and
+//!   `queue_length`, tally `waiting_time`, counter `preemptions`;
+//! - store: level `level` (number of items), tally `waiting_time` of gets;
+//! - container: level `level`, tally `waiting_time` of gets.
+//!
+//! The names are prefixed with the primitive's name, e.g. `server.utilisation`.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use crate::event_sim::process::{Kernel, Process};
+use crate::event_sim::stats::{ComponentStats, Statistics};
+
+struct Waiter {
+    process: usize,
+    priority: i32,
+    since: f64,
+}
+
+struct ResourceState {
+    name: String,
+    capacity: usize,
+    /// Processes holding a unit, with the priority of their request, in order of grant.
+    holders: Vec<(usize, i32)>,
+    /// By decreasing priority, then in order of request.
+    waiting: VecDeque<Waiter>,
+    /// Processes that lost their unit and have not released it yet.
+    preempted: HashSet<usize>,
+    stats: Option<ComponentStats>,
+}
+
+impl ResourceState {
+    fn grant(&mut self, process: usize, priority: i32, since: f64, now: f64) {
+        self.preempted.remove(&process);
+        self.holders.push((process, priority));
+        if let Some(stats) = self.stats.as_ref() {
+            stats.tally("waiting_time", now - since);
+        }
+    }
+
+    fn record(&self, now: f64) {
+        if let Some(stats) = self.stats.as_ref() {
+            let in_use = self.holders.len() as f64;
+            stats.level("in_use", now, in_use);
+            stats.level("utilisation", now, in_use / self.capacity as f64);
+            stats.level("queue_length", now, self.waiting.len() as f64);
+        }
+    }
+}
+
+/// A resource with a number of identical units.
+#[derive(Clone)]
+pub struct Resource {
+    kernel: Rc<RefCell<Kernel>>,
+    state: Rc<RefCell<ResourceState>>,
+}
+
+impl Resource {
+    pub(super) fn new(kernel: &Rc<RefCell<Kernel>>, name: &str, capacity: usize) -> Resource {
+        assert!(capacity > 0, "Resource {} has no units.", name);
+        let state = ResourceState {
+            name: name.to_owned(),
+            capacity,
+            holders: Vec::new(),
+            waiting: VecDeque::new(),
+            preempted: HashSet::new(),
+            stats: None,
+        };
+        Resource { kernel: kernel.clone(), state: Rc::new(RefCell::new(state)) }
+    }
+
+    pub fn stats(self, stats: &Statistics) -> Resource {
+        let now = self.kernel.borrow().now;
+        let mut state = self.state.borrow_mut();
+        let collectors = ["in_use", "utilisation", "queue_length", "waiting_time", "preemptions"];
+        state.stats = Some(ComponentStats::new(stats, &state.name, &collectors));
+        state.record(now);
+        drop(state);
+        self
+    }
+
+    pub fn name(&self) -> String {
+        self.state.borrow().name.clone()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.state.borrow().capacity
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.state.borrow().holders.len()
+    }
+
+    /// Number of requests waiting for a unit.
+    pub fn queue_length(&self) -> usize {
+        self.state.borrow().waiting.len()
+    }
+
+    pub(super) fn release(&self, process: &Process) {
+        let mut kernel = process.kernel.borrow_mut();
+        let now = kernel.now;
+        let mut state = self.state.borrow_mut();
+        if state.preempted.remove(&process.id) {
+            return;
+        }
+        let holder = state.holders.iter().position(|(holder, _)| *holder == process.id)
+            .unwrap_or_else(|| panic!("Process {} releases {} which it does not hold.", process.id, state.name));
+        state.holders.remove(holder);
+        if let Some(waiter) = state.waiting.pop_front() {
+            state.grant(waiter.process, waiter.priority, waiter.since, now);
+            kernel.wake(waiter.process);
+        }
+        state.record(now);
+    }
+}
+
+/// Future returned by `Process::request`, `Process::request_with_priority` and
+/// `Process::preempt`.
+pub struct Request {
+    process: Process,
+    resource: Resource,
+    priority: i32,
+    preempt: bool,
+    queued: bool,
+}
+
+impl Request {
+    pub(super) fn new(process: &Process, resource: &Resource, priority: i32, preempt: bool) -> Request {
+        Request { process: process.clone(), resource: resource.clone(), priority, preempt, queued: false }
+    }
+}
+
+impl Future for Request {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        // A queued process is only resumed once `release` has granted it a unit.
+        if self.queued {
+            return Poll::Ready(());
+        }
+        let (id, priority) = (self.process.id, self.priority);
+        let mut kernel = self.process.kernel.borrow_mut();
+        let now = kernel.now;
+        let mut state = self.resource.state.borrow_mut();
+
+        if state.holders.len() < state.capacity {
+            state.grant(id, priority, now, now);
+            state.record(now);
+            return Poll::Ready(());
+        }
+        if self.preempt {
+            // The holder with the lowest priority, the most recent one among equals.
+            let victim = state.holders.iter().enumerate().rev()
+                .filter(|(_, (_, held))| *held < priority)
+                .min_by_key(|(_, (_, held))| *held)
+                .map(|(index, _)| index);
+            if let Some(index) = victim {
+                let (victim, _) = state.holders.remove(index);
+                state.preempted.insert(victim);
+                kernel.preempt(victim);
+                if let Some(stats) = state.stats.as_ref() {
+                    stats.count("preemptions", 1);
+                }
+                state.grant(id, priority, now, now);
+                state.record(now);
+                return Poll::Ready(());
+            }
+        }
+
+        let position = state.waiting.iter().position(|waiter| waiter.priority < priority).unwrap_or(state.waiting.len());
+        state.waiting.insert(position, Waiter { process: id, priority, since: now });
+        state.record(now);
+        drop(state);
+        drop(kernel);
+        self.queued = true;
+        Poll::Pending
+    }
+}
+
+struct StoreState<T> {
+    name: String,
+    capacity: usize,
+    items: VecDeque<T>,
+    /// Blocked gets, with the time they started waiting.
+    getters: VecDeque<(usize, f64)>,
+    /// Blocked puts, with their item.
+    putters: VecDeque<(usize, T)>,
+    /// Items handed to blocked gets, until they resume.
+    delivered: HashMap<usize, T>,
+    stats: Option<ComponentStats>,
+}
+
+impl<T> StoreState<T> {
+    fn record(&self, now: f64) {
+        if let Some(stats) = self.stats.as_ref() {
+            stats.level("level", now, self.items.len() as f64);
+        }
+    }
+
+    fn record_wait(&self, since: f64, now: f64) {
+        if let Some(stats) = self.stats.as_ref() {
+            stats.tally("waiting_time", now - since);
+        }
+    }
+}
+
+/// A FIFO buffer of items. Gets wait while the store is empty, puts while it is full.
+pub struct Store<T> {
+    kernel: Rc<RefCell<Kernel>>,
+    state: Rc<RefCell<StoreState<T>>>,
+}
+
+impl<T> Clone for Store<T> {
+    fn clone(&self) -> Self {
+        Store { kernel: self.kernel.clone(), state: self.state.clone() }
+    }
+}
+
+impl<T: 'static> Store<T> {
+    pub(super) fn new(kernel: &Rc<RefCell<Kernel>>, name: &str, capacity: usize) -> Store<T> {
+        assert!(capacity > 0, "Store {} has no room.", name);
+        let state = StoreState {
+            name: name.to_owned(),
+            capacity,
+            items: VecDeque::new(),
+            getters: VecDeque::new(),
+            putters: VecDeque::new(),
+            delivered: HashMap::new(),
+            stats: None,
+        };
+        Store { kernel: kernel.clone(), state: Rc::new(RefCell::new(state)) }
+    }
+
+    pub fn stats(self, stats: &Statistics) -> Store<T> {
+        let now = self.kernel.borrow().now;
+        let mut state = self.state.borrow_mut();
+        state.stats = Some(ComponentStats::new(stats, &state.name, &["level", "waiting_time"]));
+        state.record(now);
+        drop(state);
+        self
+    }
+
+    pub fn name(&self) -> String {
+        self.state.borrow().name.clone()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.state.borrow().capacity
+    }
+
+    /// Number of items in the store.
+    pub fn len(&self) -> usize {
+        self.state.borrow().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.borrow().items.is_empty()
+    }
+
+    /// Adds `item`, waiting for room if the store is full.
+    pub fn put(&self, process: &Process, item: T) -> Put<T> {
+        Put { process: process.clone(), store: self.clone(), item: Some(item), queued: false }
+    }
+
+    /// Takes the oldest item, waiting for one if the store is empty.
+    pub fn get(&self, process: &Process) -> Get<T> {
+        Get { process: process.clone(), store: self.clone(), queued: false }
+    }
+}
+
+/// Future returned by `Store::put`.
+pub struct Put<T> {
+    process: Process,
+    store: Store<T>,
+    item: Option<T>,
+    queued: bool,
+}
+
+// The item is only moved out by value, never pinned.
+impl<T> Unpin for Put<T> {}
+
+impl<T> Future for Put<T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.queued {
+            return Poll::Ready(());
+        }
+        let item = self.item.take().expect("a put is polled again only once queued");
+        let id = self.process.id;
+        let mut kernel = self.process.kernel.borrow_mut();
+        let now = kernel.now;
+        let mut state = self.store.state.borrow_mut();
+
+        // Gets only wait while the store is empty.
+        if let Some((getter, since)) = state.getters.pop_front() {
+            state.delivered.insert(getter, item);
+            state.record_wait(since, now);
+            kernel.wake(getter);
+            return Poll::Ready(());
+        }
+        if state.items.len() < state.capacity && state.putters.is_empty() {
+            state.items.push_back(item);
+            state.record(now);
+            return Poll::Ready(());
+        }
+        state.putters.push_back((id, item));
+        drop(state);
+        drop(kernel);
+        self.queued = true;
+        Poll::Pending
+    }
+}
+
+/// Future returned by `Store::get`.
+pub struct Get<T> {
+    process: Process,
+    store: Store<T>,
+    queued: bool,
+}
+
+impl<T> Future for Get<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+        let id = self.process.id;
+        let mut kernel = self.process.kernel.borrow_mut();
+        let now = kernel.now;
+        let mut state = self.store.state.borrow_mut();
+        if self.queued {
+            return Poll::Ready(state.delivered.remove(&id).expect("a queued get is resumed with an item"));
+        }
+
+        if state.getters.is_empty() {
+            if let Some(item) = state.items.pop_front() {
+                if let Some((putter, queued_item)) = state.putters.pop_front() {
+                    state.items.push_back(queued_item);
+                    kernel.wake(putter);
+                }
+                state.record_wait(now, now);
+                state.record(now);
+                return Poll::Ready(item);
+            }
+        }
+        state.getters.push_back((id, now));
+        drop(state);
+        drop(kernel);
+        self.queued = true;
+        Poll::Pending
+    }
+}
+
+struct ContainerState {
+    name: String,
+    capacity: f64,
+    level: f64,
+    /// Blocked gets and puts with their amount; gets also with the time they started
+    /// waiting.
+    getters: VecDeque<(usize, f64, f64)>,
+    putters: VecDeque<(usize, f64)>,
+    stats: Option<ComponentStats>,
+}
+
+impl ContainerState {
+    /// Serves the blocked gets and puts that fit, in order, until neither can proceed.
+    fn settle(&mut self, kernel: &mut Kernel) {
+        loop {
+            let mut progress = false;
+            match self.getters.front() {
+                Some(&(getter, amount, since)) if amount <= self.level => {
+                    self.getters.pop_front();
+                    self.level -= amount;
+                    if let Some(stats) = self.stats.as_ref() {
+                        stats.tally("waiting_time", kernel.now - since);
+                    }
+                    kernel.wake(getter);
+                    progress = true;
+                }
+                _ => {}
+            }
+            match self.putters.front() {
+                Some(&(putter, amount)) if self.level + amount <= self.capacity => {
+                    self.putters.pop_front();
+                    self.level += amount;
+                    kernel.wake(putter);
+                    progress = true;
+                }
+                _ => {}
+            }
+            if !progress {
+                break;
+            }
+        }
+        if let Some(stats) = self.stats.as_ref() {
+            stats.level("level", kernel.now, self.level);
+        }
+    }
+}
+
+/// A continuous quantity, e.g. the content of a tank, between 0 and a capacity. Gets
+/// wait until enough is available, puts until there is enough room.
+#[derive(Clone)]
+pub struct Container {
+    kernel: Rc<RefCell<Kernel>>,
+    state: Rc<RefCell<ContainerState>>,
+}
+
+impl Container {
+    pub(super) fn new(kernel: &Rc<RefCell<Kernel>>, name: &str, capacity: f64, initial: f64) -> Container {
+        assert!(0.0 <= initial && initial <= capacity, "Container {} starts outside [0, {}].", name, capacity);
+        let state = ContainerState {
+            name: name.to_owned(),
+            capacity,
+            level: initial,
+            getters: VecDeque::new(),
+            putters: VecDeque::new(),
+            stats: None,
+        };
+        Container { kernel: kernel.clone(), state: Rc::new(RefCell::new(state)) }
+    }
+
+    pub fn stats(self, stats: &Statistics) -> Container {
+        let mut state = self.state.borrow_mut();
+        state.stats = Some(ComponentStats::new(stats, &state.name, &["level", "waiting_time"]));
+        state.settle(&mut self.kernel.borrow_mut());
+        drop(state);
+        self
+    }
+
+    pub fn name(&self) -> String {
+        self.state.borrow().name.clone()
+    }
+
+    pub fn capacity(&self) -> f64 {
+        self.state.borrow().capacity
+    }
+
+    pub fn level(&self) -> f64 {
+        self.state.borrow().level
+    }
+
+    /// Adds `amount`, waiting until it fits.
+    pub fn put(&self, process: &Process, amount: f64) -> ContainerPut {
+        assert!(0.0 <= amount && amount <= self.capacity(), "Cannot put {} into {}.", amount, self.name());
+        ContainerPut { process: process.clone(), container: self.clone(), amount, queued: false }
+    }
+
+    /// Removes `amount`, waiting until it is available.
+    pub fn get(&self, process: &Process, amount: f64) -> ContainerGet {
+        assert!(0.0 <= amount && amount <= self.capacity(), "Cannot get {} from {}.", amount, self.name());
+        ContainerGet { process: process.clone(), container: self.clone(), amount, queued: false }
+    }
+}
+
+/// Future returned by `Container::put`.
+pub struct ContainerPut {
+    process: Process,
+    container: Container,
+    amount: f64,
+    queued: bool,
+}
+
+impl Future for ContainerPut {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.queued {
+            return Poll::Ready(());
+        }
+        let mut kernel = self.process.kernel.borrow_mut();
+        let mut state = self.container.state.borrow_mut();
+        let ready = state.putters.is_empty() && state.level + self.amount <= state.capacity;
+        if ready {
+            state.level += self.amount;
+        } else {
+            state.putters.push_back((self.process.id, self.amount));
+        }
+        state.settle(&mut kernel);
+        drop(state);
+        drop(kernel);
+        self.queued = !ready;
+        if ready { Poll::Ready(()) } else { Poll::Pending }
+    }
+}
+
+/// Future returned by `Container::get`.
+pub struct ContainerGet {
+    process: Process,
+    container: Container,
+    amount: f64,
+    queued: bool,
+}
+
+impl Future for ContainerGet {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.queued {
+            return Poll::Ready(());
+        }
+        let mut kernel = self.process.kernel.borrow_mut();
+        let now = kernel.now;
+        let mut state = self.container.state.borrow_mut();
+        let ready = state.getters.is_empty() && self.amount <= state.level;
+        if ready {
+            state.level -= self.amount;
+            if let Some(stats) = state.stats.as_ref() {
+                stats.tally("waiting_time", 0.0);
+            }
+        } else {
+            state.getters.push_back((self.process.id, self.amount, now));
+        }
+        state.settle(&mut kernel);
+        drop(state);
+        drop(kernel);
+        self.queued = !ready;
+        if ready { Poll::Ready(()) } else { Poll::Pending }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event_sim::process::{ProcessEvent, ProcessModel};
+    use crate::event_sim::SimpleQueue;
+
+    type Queue = SimpleQueue<ProcessEvent>;
+    type Log = Rc<RefCell<Vec<(f64, String)>>>;
+
+    fn log(process: &Process, log: &Log, what: &str) {
+        log.borrow_mut().push((process.now(), format!("{} {}", process.name(), what)));
+    }
+
+    #[test]
+    fn test_priorities_and_preemption() {
+        let trace: Log = Rc::default();
+        let stats = Statistics::new();
+        let mut model = ProcessModel::<Queue>::new();
+        let machine = model.resource("machine", 1).stats(&stats);
+
+        // A low priority job, preempted at 2 and resumed when the urgent job is done.
+        let (job_trace, job_machine) = (trace.clone(), machine.clone());
+        model.process("job", 0.0, move |process| async move {
+            let mut remaining = 10.0;
+            loop {
+                process.request_with_priority(&job_machine, 0).await;
+                match process.hold_preemptible(remaining).await {
+                    Some(left) => remaining = left,
+                    None => break,
+                }
+                log(&process, &job_trace, "preempted");
+                process.release(&job_machine);
+            }
+            process.release(&job_machine);
+            log(&process, &job_trace, "done");
+        });
+        let (urgent_trace, urgent_machine) = (trace.clone(), machine.clone());
+        model.process("urgent", 2.0, move |process| async move {
+            process.preempt(&urgent_machine, 10).await;
+            process.hold(3.0).await;
+            process.release(&urgent_machine);
+            log(&process, &urgent_trace, "done");
+        });
+        // Requests queued behind the job are served by priority.
+        for (name, priority) in [("low", -1), ("high", 5), ("medium", 1)] {
+            let (trace, machine) = (trace.clone(), machine.clone());
+            model.process(name, 1.0, move |process| async move {
+                process.request_with_priority(&machine, priority).await;
+                log(&process, &trace, "served");
+                process.hold(1.0).await;
+                process.release(&machine);
+            });
+        }
+
+        let mut simulation = model.build(Queue::with_seed(0));
+        simulation.run().unwrap();
+        // The job waits again from 2, behind high and medium but ahead of low, and needs
+        // 8 more units of work.
+        let expected = [(2.0, "job preempted"), (5.0, "urgent done"), (5.0, "high served"), (6.0, "medium served"),
+                        (15.0, "job done"), (15.0, "low served")];
+        let expected: Vec<(f64, String)> = expected.iter().map(|(t, what)| (*t, what.to_string())).collect();
+        assert_eq!(expected, *trace.borrow());
+        assert_eq!(16.0, simulation.now());
+        assert_eq!(1, stats.counter("machine.preemptions"));
+        assert_eq!(1.0, stats.get_level("machine.utilisation").unwrap().mean(16.0));
+        assert_eq!(6, stats.get_tally("machine.waiting_time").unwrap().count());
+    }
+
+    #[test]
+    #[should_panic(expected = "Store buffer has no room.")]
+    fn test_store_needs_room() {
+        ProcessModel::<Queue>::new().store::<u32>("buffer", 0);
+    }
+
+    #[test]
+    fn test_store_blocks_producer_and_consumer() {
+        let trace: Log = Rc::default();
+        let stats = Statistics::new();
+        let mut model = ProcessModel::<Queue>::new();
+        let buffer = model.store::<u32>("buffer", 2).stats(&stats);
+
+        let (producer_trace, producer_buffer) = (trace.clone(), buffer.clone());
+        model.process("producer", 0.0, move |process| async move {
+            for item in 0..4 {
+                producer_buffer.put(&process, item).await;
+                log(&process, &producer_trace, &format!("put {}", item));
+            }
+        });
+        let (consumer_trace, consumer_buffer) = (trace.clone(), buffer.clone());
+        model.process("consumer", 1.0, move |process| async move {
+            for _ in 0..5 {
+                let item = consumer_buffer.get(&process).await;
+                log(&process, &consumer_trace, &format!("got {}", item));
+                process.hold(1.0).await;
+            }
+        });
+        let mut simulation = model.build(Queue::with_seed(0));
+        simulation.run().unwrap();
+
+        let trace: Vec<String> = trace.borrow().iter().map(|(t, what)| format!("{} {}", t, what)).collect();
+        assert_eq!(vec!["0 producer put 0", "0 producer put 1", "1 consumer got 0", "1 producer put 2",
+                        "2 consumer got 1", "2 producer put 3", "3 consumer got 2", "4 consumer got 3"], trace);
+        // The fifth get waits forever.
+        assert!(buffer.is_empty());
+        assert_eq!(1, simulation.actor_as::<ProcessModel<Queue>>("processes").unwrap().active());
+        assert_eq!(4, stats.get_tally("buffer.waiting_time").unwrap().count());
+        assert_eq!(2.0, stats.get_level("buffer.level").unwrap().max());
+    }
+
+    #[test]
+    fn test_container_levels() {
+        let trace: Log = Rc::default();
+        let mut model = ProcessModel::<Queue>::new();
+        let tank = model.container("tank", 10.0, 2.0);
+
+        let filler_tank = tank.clone();
+        model.process("filler", 0.0, move |process| async move {
+            for _ in 0..4 {
+                process.hold(1.0).await;
+                filler_tank.put(&process, 4.0).await;
+            }
+        });
+        let (drain_trace, drain_tank) = (trace.clone(), tank.clone());
+        model.process("drain", 0.0, move |process| async move {
+            drain_tank.get(&process, 9.0).await;
+            log(&process, &drain_trace, "drained");
+        });
+        let mut simulation = model.build(Queue::with_seed(0));
+        simulation.run().unwrap();
+
+        // 2 + 4 + 4 >= 9 at time 2, leaving 1; then 1 + 4 + 4.
+        assert_eq!(vec![(2.0, "drain drained".to_owned())], *trace.borrow());
+        assert_eq!(9.0, tank.level());
+        assert_eq!(4.0, simulation.now());
+    }
+}
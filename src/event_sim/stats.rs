@@ -0,0 +1,579 @@
+//! Statistics collected during simulation runs.
+//!
+//! `Statistics` is a cloneable handle to a set of named collectors, so the same set can
+//! be shared by every actor of a model:
+//! - counters count occurrences, e.g. dropped customers;
+//! - tallies summarise observations, e.g. waiting times, with mean and variance, and with
+//!   percentiles and a batch-means confidence interval for those that keep their values
+//!   (`Statistics::keep_values`);
+//! - levels are time-weighted averages of piecewise-constant quantities, e.g. a queue
+//!   length or the fraction of busy servers.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Serialize;
+
+/// Number of batches and confidence level of the intervals in a `StatsReport`.
+const REPORT_BATCHES: usize = 20;
+const REPORT_LEVEL: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    pub half_width: f64,
+    pub level: f64,
+}
+
+impl ConfidenceInterval {
+    pub fn lower(&self) -> f64 {
+        self.mean - self.half_width
+    }
+
+    pub fn upper(&self) -> f64 {
+        self.mean + self.half_width
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.lower() <= value && value <= self.upper()
+    }
+}
+
+/// Summary of a sequence of observations. Mean and variance use Welford's algorithm in
+/// constant memory; percentiles and batch means need the observations themselves, which
+/// are only kept by a tally created with `keeping_values`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tally {
+    count: usize,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+    values: Option<Vec<f64>>,
+}
+
+impl Tally {
+    pub fn new() -> Tally {
+        Tally::default()
+    }
+
+    /// A tally that also keeps every observation, for percentiles and batch means.
+    pub fn keeping_values() -> Tally {
+        Tally { values: Some(Vec::new()), ..Tally::default() }
+    }
+
+    pub fn record(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        if let Some(values) = self.values.as_mut() {
+            values.push(value);
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Forgets the observations, keeping whether their values are kept.
+    pub fn clear(&mut self) {
+        let values = self.values.take().map(|mut values| {
+            values.clear();
+            values
+        });
+        *self = Tally { values, ..Tally::default() };
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Sample variance; 0 with fewer than two observations.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// The observations, if they are kept.
+    pub fn values(&self) -> Option<&[f64]> {
+        self.values.as_deref()
+    }
+
+    /// Percentile with linear interpolation between closest ranks; `p` is in [0, 1].
+    /// Returns `None` without observations or if they are not kept.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        let values = self.values.as_ref().filter(|values| !values.is_empty())?;
+        let mut sorted = values.clone();
+        sorted.sort_by(f64::total_cmp);
+        let rank = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+        let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+        Some(sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64))
+    }
+
+    /// Confidence interval for the mean by the method of batch means: the observations are
+    /// split into `batches` consecutive batches whose means are treated as independent.
+    /// Returns `None` with fewer than two observations per batch or if they are not kept.
+    pub fn batch_means(&self, batches: usize, level: f64) -> Option<ConfidenceInterval> {
+        let values = self.values.as_ref()?;
+        if batches < 2 || values.len() < 2 * batches {
+            return None;
+        }
+        let batch_size = values.len() / batches;
+        let mut means = Tally::new();
+        for batch in values.chunks_exact(batch_size).take(batches) {
+            means.record(batch.iter().sum::<f64>() / batch_size as f64);
+        }
+        let t = student_t_quantile(0.5 + level / 2.0, (batches - 1) as f64);
+        Some(ConfidenceInterval {
+            mean: means.mean(),
+            half_width: t * means.std_dev() / (batches as f64).sqrt(),
+            level,
+        })
+    }
+}
+
+/// Time-weighted average of a piecewise-constant quantity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeWeighted {
+    start: f64,
+    last_time: f64,
+    value: f64,
+    area: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TimeWeighted {
+    pub fn new(time: f64, value: f64) -> TimeWeighted {
+        TimeWeighted { start: time, last_time: time, value, area: 0.0, min: value, max: value }
+    }
+
+    /// The quantity changes to `value` at `time`.
+    pub fn update(&mut self, time: f64, value: f64) {
+        self.area += self.value * (time - self.last_time);
+        self.last_time = time;
+        self.value = value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Average over the interval from the start (or last reset) to `now`.
+    pub fn mean(&self, now: f64) -> f64 {
+        let duration = now - self.start;
+        if duration <= 0.0 {
+            return self.value;
+        }
+        (self.area + self.value * (now - self.last_time)) / duration
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Restarts the average at `time`, keeping the current value.
+    pub fn reset(&mut self, time: f64) {
+        *self = TimeWeighted::new(time, self.value);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Collectors {
+    counters: BTreeMap<String, u64>,
+    tallies: BTreeMap<String, Tally>,
+    levels: BTreeMap<String, TimeWeighted>,
+    /// Names of the tallies that keep their values.
+    kept: BTreeSet<String>,
+}
+
+/// Shared, named statistics collectors. Cloning the handle shares the collectors.
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    collectors: Arc<Mutex<Collectors>>,
+}
+
+impl Statistics {
+    pub fn new() -> Statistics {
+        Statistics::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Collectors> {
+        self.collectors.lock().expect("Statistics lock poisoned.")
+    }
+
+    pub fn count(&self, name: &str, n: u64) {
+        let mut collectors = self.lock();
+        match collectors.counters.get_mut(name) {
+            Some(count) => *count += n,
+            None => {
+                collectors.counters.insert(name.to_owned(), n);
+            }
+        }
+    }
+
+    pub fn tally(&self, name: &str, value: f64) {
+        let mut collectors = self.lock();
+        match collectors.tallies.get_mut(name) {
+            Some(tally) => tally.record(value),
+            None => {
+                let mut tally = if collectors.kept.contains(name) { Tally::keeping_values() } else { Tally::new() };
+                tally.record(value);
+                collectors.tallies.insert(name.to_owned(), tally);
+            }
+        }
+    }
+
+    /// Keeps the observations of the tally `name`, so that it reports percentiles and a
+    /// confidence interval. Observations recorded before are not recovered.
+    pub fn keep_values(&self, name: &str) {
+        let mut collectors = self.lock();
+        collectors.kept.insert(name.to_owned());
+        if let Some(tally) = collectors.tallies.get_mut(name) {
+            if tally.values.is_none() {
+                tally.values = Some(Vec::new());
+            }
+        }
+    }
+
+    /// Sets the level `name` to `value` from `time` on.
+    pub fn level(&self, name: &str, time: f64, value: f64) {
+        let mut collectors = self.lock();
+        match collectors.levels.get_mut(name) {
+            Some(level) => level.update(time, value),
+            None => {
+                collectors.levels.insert(name.to_owned(), TimeWeighted::new(time, value));
+            }
+        }
+    }
+
+    pub fn counter(&self, name: &str) -> u64 {
+        self.lock().counters.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn get_tally(&self, name: &str) -> Option<Tally> {
+        self.lock().tallies.get(name).cloned()
+    }
+
+    pub fn get_level(&self, name: &str) -> Option<TimeWeighted> {
+        self.lock().levels.get(name).cloned()
+    }
+
+    /// Discards everything collected so far, e.g. at the end of a warm-up period. Levels
+    /// keep their current value.
+    pub fn reset(&self, time: f64) {
+        let mut collectors = self.lock();
+        collectors.counters.values_mut().for_each(|count| *count = 0);
+        collectors.tallies.values_mut().for_each(Tally::clear);
+        collectors.levels.values_mut().for_each(|level| level.reset(time));
+    }
+
+    /// Summary of every collector at `end_time`.
+    pub fn report(&self, end_time: f64) -> StatsReport {
+        let collectors = self.lock();
+        let mut rows = Vec::new();
+        for (name, count) in collectors.counters.iter() {
+            rows.push(StatRow { name: name.clone(), kind: "counter", count: *count, ..StatRow::default() });
+        }
+        for (name, tally) in collectors.tallies.iter() {
+            let interval = tally.batch_means(REPORT_BATCHES, REPORT_LEVEL);
+            rows.push(StatRow {
+                name: name.clone(),
+                kind: "tally",
+                count: tally.count() as u64,
+                mean: Some(tally.mean()),
+                std_dev: Some(tally.std_dev()),
+                min: Some(tally.min()),
+                max: Some(tally.max()),
+                p50: tally.percentile(0.5),
+                p95: tally.percentile(0.95),
+                p99: tally.percentile(0.99),
+                ci_half_width: interval.map(|interval| interval.half_width),
+            });
+        }
+        for (name, level) in collectors.levels.iter() {
+            rows.push(StatRow {
+                name: name.clone(),
+                kind: "time-weighted",
+                mean: Some(level.mean(end_time)),
+                min: Some(level.min()),
+                max: Some(level.max()),
+                ..StatRow::default()
+            });
+        }
+        StatsReport { end_time, rows }
+    }
+}
+
+/// The collectors of one model component, named after it, e.g. `server.waiting_time`.
+/// The full names are built once, when the component is given its statistics, rather
+/// than on every observation.
+#[derive(Debug, Clone)]
+pub(crate) struct ComponentStats {
+    statistics: Statistics,
+    names: Vec<(&'static str, String)>,
+}
+
+impl ComponentStats {
+    pub(crate) fn new(statistics: &Statistics, component: &str, collectors: &[&'static str]) -> ComponentStats {
+        let names = collectors.iter().map(|collector| (*collector, format!("{}.{}", component, collector))).collect();
+        ComponentStats { statistics: statistics.clone(), names }
+    }
+
+    fn name(&self, collector: &str) -> &str {
+        self.names.iter()
+            .find(|(short, _)| *short == collector)
+            .map(|(_, name)| name.as_str())
+            .unwrap_or_else(|| panic!("Undeclared collector {}.", collector))
+    }
+
+    pub(crate) fn count(&self, collector: &str, n: u64) {
+        self.statistics.count(self.name(collector), n);
+    }
+
+    pub(crate) fn tally(&self, collector: &str, value: f64) {
+        self.statistics.tally(self.name(collector), value);
+    }
+
+    pub(crate) fn level(&self, collector: &str, time: f64, value: f64) {
+        self.statistics.level(self.name(collector), time, value);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StatRow {
+    pub name: String,
+    pub kind: &'static str,
+    pub count: u64,
+    pub mean: Option<f64>,
+    pub std_dev: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+    /// Half width of the 95% batch-means confidence interval of the mean.
+    pub ci_half_width: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub end_time: f64,
+    pub rows: Vec<StatRow>,
+}
+
+impl StatsReport {
+    pub fn row(&self, name: &str) -> Option<&StatRow> {
+        self.rows.iter().find(|row| row.name == name)
+    }
+
+    /// One line per collector; missing values are left empty.
+    pub fn write_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for row in self.rows.iter() {
+            writer.serialize(row)?;
+        }
+        writer.flush()
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn write_csv_file(&self, path: &Path) -> io::Result<()> {
+        self.write_csv(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn write_json_file(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_json(&mut writer)?;
+        writer.flush()
+    }
+}
+
+/// Quantile of the standard normal distribution (P. J. Acklam's rational approximation,
+/// relative error below 1.2e-9).
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e+01, 2.209460984245205e+02, -2.759285104469687e+02,
+                         1.38357751867269e+02, -3.066479806614716e+01, 2.506628277459239e+00];
+    const B: [f64; 5] = [-5.447609879822406e+01, 1.615858368580409e+02, -1.556989798598866e+02,
+                         6.680131188771972e+01, -1.328068155288572e+01];
+    const C: [f64; 6] = [-7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00,
+                         -2.549732539343734e+00, 4.374664141464968e+00, 2.938163982698783e+00];
+    const D: [f64; 4] = [7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00,
+                         3.754408661907416e+00];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Quantile of Student's t distribution: exact for 1 and 2 degrees of freedom, otherwise
+/// the Cornish-Fisher expansion around the normal quantile (G. W. Hill, 1970).
+pub(crate) fn student_t_quantile(p: f64, dof: f64) -> f64 {
+    if dof == 1.0 {
+        return (std::f64::consts::PI * (p - 0.5)).tan();
+    }
+    if dof == 2.0 {
+        return (2.0 * p - 1.0) / (2.0 * p * (1.0 - p)).sqrt();
+    }
+    let z = normal_quantile(p);
+    let z2 = z * z;
+    let g1 = (z2 + 1.0) * z / 4.0;
+    let g2 = ((5.0 * z2 + 16.0) * z2 + 3.0) * z / 96.0;
+    let g3 = (((3.0 * z2 + 19.0) * z2 + 17.0) * z2 - 15.0) * z / 384.0;
+    let g4 = ((((79.0 * z2 + 776.0) * z2 + 1482.0) * z2 - 1920.0) * z2 - 945.0) * z / 92160.0;
+    z + g1 / dof + g2 / dof.powi(2) + g3 / dof.powi(3) + g4 / dof.powi(4)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compare_utils::assert_eq_f64;
+    use crate::event_sim::queueing::{Distribution, Network, Sink, Source, Station};
+    use crate::event_sim::SimpleQueue;
+
+    #[test]
+    fn test_tally() {
+        let mut tally = Tally::new();
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            tally.record(value);
+        }
+        assert_eq!(8, tally.count());
+        assert_eq_f64(tally.mean(), 5.0, 1e-12);
+        assert_eq_f64(tally.variance(), 32.0 / 7.0, 1e-12);
+        assert_eq!((2.0, 9.0), (tally.min(), tally.max()));
+        assert_eq!(None, tally.percentile(0.5));
+        assert!(tally.values().is_none());
+
+        let mut kept = Tally::keeping_values();
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            kept.record(value);
+        }
+        assert_eq!((tally.mean(), tally.variance()), (kept.mean(), kept.variance()));
+        assert_eq!(Some(4.5), kept.percentile(0.5));
+        assert_eq!(Some(9.0), kept.percentile(1.0));
+        kept.clear();
+        assert_eq!((0, Some(&[][..])), (kept.count(), kept.values()));
+    }
+
+    #[test]
+    fn test_time_weighted() {
+        // 0 on [0, 2), 3 on [2, 3), 1 on [3, 5).
+        let mut level = TimeWeighted::new(0.0, 0.0);
+        level.update(2.0, 3.0);
+        level.update(3.0, 1.0);
+        assert_eq_f64(level.mean(5.0), 1.0, 1e-12);
+        level.reset(5.0);
+        assert_eq_f64(level.mean(7.0), 1.0, 1e-12);
+    }
+
+    #[test]
+    fn test_student_t_quantile() {
+        assert_eq_f64(student_t_quantile(0.975, 1.0), 12.7062, 1e-3);
+        assert_eq_f64(student_t_quantile(0.975, 2.0), 4.3027, 1e-3);
+        assert_eq_f64(student_t_quantile(0.975, 19.0), 2.0930, 1e-3);
+        assert_eq_f64(student_t_quantile(0.95, 9.0), 1.8331, 1e-3);
+        assert_eq_f64(normal_quantile(0.975), 1.959964, 1e-6);
+    }
+
+    #[test]
+    fn test_report_formats() {
+        let statistics = Statistics::new();
+        statistics.count("dropped", 2);
+        statistics.tally("wait", 1.5);
+        statistics.level("queue", 0.0, 2.0);
+        let report = statistics.report(4.0);
+        assert_eq!(Some(2.0), report.row("queue").unwrap().mean);
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("name,kind,count,mean,std_dev,min,max,p50,p95,p99,ci_half_width\n"));
+        assert!(csv.contains("dropped,counter,2,,,,,,,,\n"));
+
+        let mut json = Vec::new();
+        report.write_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!("wait", json["rows"][1]["name"]);
+        assert_eq!(1.5, json["rows"][1]["mean"]);
+    }
+
+    #[test]
+    fn test_mm1_matches_analytic_results() {
+        // Arrival rate 0.5 and service rate 1: utilisation 0.5, mean waiting time 1, mean
+        // sojourn time 2, mean number waiting 0.5 and mean number in the system 1.
+        let statistics = Statistics::new();
+        statistics.keep_values("server.waiting_time");
+        let mut simulation = Network::new()
+            .source(Source::new("arrivals", Distribution::Exponential { rate: 0.5 }, "server"))
+            .station(Station::new("server", 1, Distribution::Exponential { rate: 1.0 }).to("exit").stats(&statistics))
+            .sink(Sink::new("exit").stats(&statistics))
+            .build(SimpleQueue::with_seed(11));
+
+        simulation.run_until(1_000.0).unwrap();
+        statistics.reset(1_000.0);
+        simulation.run_until(201_000.0).unwrap();
+        let report = statistics.report(simulation.now());
+
+        let mean = |name: &str| report.row(name).unwrap().mean.unwrap();
+        assert_eq_f64(mean("server.utilisation"), 0.5, 0.02);
+        assert_eq_f64(mean("server.queue_length"), 0.5, 0.05);
+        assert_eq_f64(mean("server.in_system"), 1.0, 0.05);
+        assert_eq_f64(mean("exit.sojourn"), 2.0, 0.1);
+
+        let waiting = statistics.get_tally("server.waiting_time").unwrap();
+        let interval = waiting.batch_means(20, 0.95).unwrap();
+        assert!(interval.contains(1.0), "{:?}", interval);
+        assert!(interval.half_width < 0.1);
+        assert!(report.row("server.waiting_time").unwrap().p95.is_some());
+        assert!(report.row("exit.sojourn").unwrap().p95.is_none());
+        assert_eq!(0, statistics.counter("server.dropped"));
+    }
+}
@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::ops::{Add, Sub};
+
+use chrono::TimeDelta;
+
+/// Simulation time. Implemented for `f64` seconds, `u64` ticks and `chrono::TimeDelta`
+/// offsets from the start of the run.
+pub trait SimTime: Copy + PartialOrd + Debug + Add<Output = Self> + Sub<Output = Self> {
+    /// Converts the time to a number of time units, e.g. for statistics.
+    fn to_f64(self) -> f64;
+
+    /// Inverse of `to_f64`. Integer times are rounded to the nearest tick.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl SimTime for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+impl SimTime for u64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.round() as u64
+    }
+}
+
+/// One time unit is one second.
+impl SimTime for TimeDelta {
+    fn to_f64(self) -> f64 {
+        match self.num_nanoseconds() {
+            Some(nanoseconds) => nanoseconds as f64 / 1e9,
+            None => self.num_milliseconds() as f64 / 1e3,
+        }
+    }
+
+    fn from_f64(value: f64) -> Self {
+        TimeDelta::nanoseconds((value * 1e9).round() as i64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimError<T> {
+    /// An event was scheduled before the current clock.
+    EventInPast { now: T, timestamp: T, event_id: String },
+    /// The timestamp cannot be compared with the clock, e.g. `f64::NAN`.
+    InvalidTimestamp { timestamp: T, event_id: String },
+    /// An event sent to another logical process is earlier than the sender's clock plus
+    /// the declared lookahead. See `ParallelSimulation`.
+    LookaheadViolation { now: T, lookahead: T, timestamp: T, event_id: String },
+    /// An event is addressed to an actor that does not exist. See
+    /// `Simulation::fail_on_dead_letter`.
+    UndeliverableEvent { now: T, destination: String, event_id: String },
+}
+
+impl<T: Debug> fmt::Display for SimError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::EventInPast { now, timestamp, event_id } => {
+                write!(f, "event {} is scheduled at {:?}, before the current time {:?}", event_id, timestamp, now)
+            }
+            SimError::InvalidTimestamp { timestamp, event_id } => {
+                write!(f, "event {} has an invalid timestamp {:?}", event_id, timestamp)
+            }
+            SimError::LookaheadViolation { now, lookahead, timestamp, event_id } => {
+                write!(f, "event {} is sent at {:?} for {:?}, within the lookahead {:?}", event_id, now, timestamp, lookahead)
+            }
+            SimError::UndeliverableEvent { now, destination, event_id } => {
+                write!(f, "event {} at {:?} is addressed to unknown actor {}", event_id, now, destination)
+            }
+        }
+    }
+}
+
+impl<T: Debug> Error for SimError<T> {}
@@ -0,0 +1,385 @@
+//! Recording delivered events to a trace file, and replaying a trace into one actor.
+//!
+//! Each record holds the delivery time and index, the source, destination and
+//! type of the event, and the whole event serialised as JSON. Traces are written either as
+//! JSON lines or as an Arrow IPC file with one column per field.
+
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{Array, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::event_sim::{Actor, Event, EventQueue, SimTime};
+
+/// Records buffered before an Arrow record batch is written.
+const ARROW_BATCH_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    JsonLines,
+    ArrowIpc,
+}
+
+impl TraceFormat {
+    /// Guesses the format from the file extension: `.arrow`, `.ipc` and `.feather` are
+    /// Arrow IPC, anything else JSON lines.
+    pub fn from_path(path: &Path) -> TraceFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("arrow") | Some("ipc") | Some("feather") => TraceFormat::ArrowIpc,
+            _ => TraceFormat::JsonLines,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Arrow(ArrowError),
+    /// A trace file that does not have the expected columns.
+    Format(String),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(error) => write!(f, "trace I/O error: {}", error),
+            TraceError::Json(error) => write!(f, "trace JSON error: {}", error),
+            TraceError::Arrow(error) => write!(f, "trace Arrow error: {}", error),
+            TraceError::Format(message) => write!(f, "invalid trace: {}", message),
+        }
+    }
+}
+
+impl Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(error: io::Error) -> Self {
+        TraceError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for TraceError {
+    fn from(error: serde_json::Error) -> Self {
+        TraceError::Json(error)
+    }
+}
+
+impl From<ArrowError> for TraceError {
+    fn from(error: ArrowError) -> Self {
+        TraceError::Arrow(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub time: f64,
+    /// Position of the event in the delivery order of the run, from 0. This is not the
+    /// sequence number the event had in the queue.
+    pub index: u64,
+    pub source: String,
+    pub destination: String,
+    /// `Debug` representation of `Event::event_type`.
+    pub event_type: String,
+    pub event: serde_json::Value,
+}
+
+impl TraceRecord {
+    pub fn new<E>(index: u64, event: &E) -> Result<TraceRecord, TraceError>
+    where
+        E: Event + Serialize,
+        E::TyEventType: Debug,
+    {
+        Ok(TraceRecord {
+            time: event.timestamp().to_f64(),
+            index,
+            source: event.source(),
+            destination: event.destination(),
+            event_type: format!("{:?}", event.event_type()),
+            event: serde_json::to_value(event)?,
+        })
+    }
+
+    pub fn event<E: DeserializeOwned>(&self) -> Result<E, TraceError> {
+        Ok(E::deserialize(&self.event)?)
+    }
+}
+
+/// Receives every event delivered by a `Simulation`; see `Simulation::record_trace`.
+pub trait TraceSink<E> {
+    /// Records the `index`-th event delivered by the run.
+    fn record(&mut self, index: u64, event: &E) -> Result<(), TraceError>;
+
+    /// Flushes the trace. Nothing is recorded afterwards.
+    fn finish(&mut self) -> Result<(), TraceError>;
+}
+
+enum Output {
+    JsonLines(Box<dyn Write>),
+    Arrow { writer: Box<FileWriter<Box<dyn Write>>>, buffer: Vec<TraceRecord> },
+    Finished,
+}
+
+/// Writes a trace as JSON lines or Arrow IPC.
+pub struct TraceWriter {
+    output: Output,
+}
+
+fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new("time", DataType::Float64, false),
+        Field::new("index", DataType::UInt64, false),
+        Field::new("source", DataType::Utf8, false),
+        Field::new("destination", DataType::Utf8, false),
+        Field::new("event_type", DataType::Utf8, false),
+        Field::new("event", DataType::Utf8, false),
+    ])
+}
+
+impl TraceWriter {
+    pub fn new<W: Write + 'static>(writer: W, format: TraceFormat) -> Result<TraceWriter, TraceError> {
+        let writer: Box<dyn Write> = Box::new(writer);
+        let output = match format {
+            TraceFormat::JsonLines => Output::JsonLines(writer),
+            TraceFormat::ArrowIpc => Output::Arrow { writer: Box::new(FileWriter::try_new(writer, &schema())?), buffer: Vec::new() },
+        };
+        Ok(TraceWriter { output })
+    }
+
+    /// Creates the file at `path`, in the format given by its extension.
+    pub fn create(path: &Path) -> Result<TraceWriter, TraceError> {
+        TraceWriter::new(BufWriter::new(File::create(path)?), TraceFormat::from_path(path))
+    }
+
+    pub fn write(&mut self, record: TraceRecord) -> Result<(), TraceError> {
+        match &mut self.output {
+            Output::JsonLines(writer) => {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writer.write_all(b"\n")?;
+            }
+            Output::Arrow { writer, buffer } => {
+                buffer.push(record);
+                if buffer.len() >= ARROW_BATCH_SIZE {
+                    write_batch(writer, buffer)?;
+                }
+            }
+            Output::Finished => {}
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), TraceError> {
+        match std::mem::replace(&mut self.output, Output::Finished) {
+            Output::JsonLines(mut writer) => writer.flush()?,
+            Output::Arrow { mut writer, mut buffer } => {
+                write_batch(&mut writer, &mut buffer)?;
+                writer.finish()?;
+                writer.into_inner()?.flush()?;
+            }
+            Output::Finished => {}
+        }
+        Ok(())
+    }
+}
+
+fn write_batch(writer: &mut FileWriter<Box<dyn Write>>, buffer: &mut Vec<TraceRecord>) -> Result<(), TraceError> {
+    if buffer.is_empty() {
+        return Ok(());
+    }
+    let records = std::mem::take(buffer);
+    let strings = |field: fn(&TraceRecord) -> String| {
+        Arc::new(StringArray::from(records.iter().map(field).collect::<Vec<String>>()))
+    };
+    let batch = RecordBatch::try_new(Arc::new(schema()), vec![
+        Arc::new(Float64Array::from(records.iter().map(|record| record.time).collect::<Vec<f64>>())),
+        Arc::new(UInt64Array::from(records.iter().map(|record| record.index).collect::<Vec<u64>>())),
+        strings(|record| record.source.clone()),
+        strings(|record| record.destination.clone()),
+        strings(|record| record.event_type.clone()),
+        strings(|record| record.event.to_string()),
+    ])?;
+    writer.write(&batch)?;
+    Ok(())
+}
+
+impl<E> TraceSink<E> for TraceWriter
+where
+    E: Event + Serialize,
+    E::TyEventType: Debug,
+{
+    fn record(&mut self, index: u64, event: &E) -> Result<(), TraceError> {
+        self.write(TraceRecord::new(index, event)?)
+    }
+
+    fn finish(&mut self) -> Result<(), TraceError> {
+        TraceWriter::finish(self)
+    }
+}
+
+pub fn read_json_lines<R: BufRead>(reader: R) -> Result<Vec<TraceRecord>, TraceError> {
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(records)
+}
+
+pub fn read_arrow<R: Read + Seek>(reader: R) -> Result<Vec<TraceRecord>, TraceError> {
+    fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T, TraceError> {
+        batch.column_by_name(name)
+            .and_then(|column| column.as_any().downcast_ref::<T>())
+            .ok_or_else(|| TraceError::Format(format!("missing or mistyped column {}", name)))
+    }
+
+    let mut records = Vec::new();
+    for batch in FileReader::try_new(reader, None)? {
+        let batch = batch?;
+        let time = column::<Float64Array>(&batch, "time")?;
+        let index = column::<UInt64Array>(&batch, "index")?;
+        let source = column::<StringArray>(&batch, "source")?;
+        let destination = column::<StringArray>(&batch, "destination")?;
+        let event_type = column::<StringArray>(&batch, "event_type")?;
+        let event = column::<StringArray>(&batch, "event")?;
+        for row in 0..batch.num_rows() {
+            records.push(TraceRecord {
+                time: time.value(row),
+                index: index.value(row),
+                source: source.value(row).to_owned(),
+                destination: destination.value(row).to_owned(),
+                event_type: event_type.value(row).to_owned(),
+                event: serde_json::from_str(event.value(row))?,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// Reads a trace written by `TraceWriter`, in the format given by the file extension.
+pub fn read_trace(path: &Path) -> Result<Vec<TraceRecord>, TraceError> {
+    let file = File::open(path)?;
+    match TraceFormat::from_path(path) {
+        TraceFormat::JsonLines => read_json_lines(BufReader::new(file)),
+        TraceFormat::ArrowIpc => read_arrow(BufReader::new(file)),
+    }
+}
+
+/// An event replayed into the actor under test, and the events it scheduled in response.
+#[derive(Debug)]
+pub struct ReplayStep<E> {
+    /// Index of the event in the recorded run.
+    pub index: u64,
+    pub event: E,
+    pub scheduled: Vec<E>,
+}
+
+/// Feeds the recorded events addressed to `actor` into it, in delivery order, as the
+/// simulation did. The events the actor schedules are taken from `queue` after each call,
+/// so they are never delivered; a handle the actor kept to one of them is no longer valid.
+pub fn replay<A, Q>(records: &[TraceRecord], actor: &mut A, queue: &mut Q) -> Result<Vec<ReplayStep<Q::TyEvent>>, TraceError>
+where
+    A: Actor<TyEvent = Q::TyEvent, TyQueue = Q>,
+    Q: EventQueue,
+    Q::TyEvent: DeserializeOwned,
+{
+    let name = actor.name();
+    let mut steps = Vec::new();
+    for record in records.iter().filter(|record| record.destination == name) {
+        actor.handle_event(record.event()?, queue);
+        let mut scheduled = Vec::new();
+        while let Some(event) = queue.pop() {
+            scheduled.push(event);
+        }
+        steps.push(ReplayStep { index: record.index, event: record.event()?, scheduled });
+    }
+    Ok(steps)
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::event_sim::queueing::{Distribution, Network, NetworkEvent, NetworkEventKind, Sink, Source, Station};
+    use crate::event_sim::SimpleQueue;
+
+    type Queue = SimpleQueue<NetworkEvent>;
+
+    /// Writes into a buffer the test can read after the writer is dropped.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn network() -> Network<Queue> {
+        Network::new()
+            .source(Source::new("arrivals", Distribution::Exponential { rate: 1.0 }, "server").limit(20))
+            .station(Station::new("server", 1, Distribution::Exponential { rate: 1.2 }).to("exit"))
+            .sink(Sink::new("exit"))
+    }
+
+    fn record(format: TraceFormat) -> (usize, Vec<u8>) {
+        let buffer = SharedBuffer::default();
+        let mut simulation = network().build(Queue::with_seed(5));
+        simulation.record_trace(Box::new(TraceWriter::new(buffer.clone(), format).unwrap()));
+        let delivered = simulation.run().unwrap();
+        simulation.finish_trace().unwrap();
+        let bytes = buffer.0.borrow().clone();
+        (delivered, bytes)
+    }
+
+    #[test]
+    fn test_formats_hold_the_same_records() {
+        let (delivered, json) = record(TraceFormat::JsonLines);
+        let (_, arrow) = record(TraceFormat::ArrowIpc);
+        let records = read_json_lines(Cursor::new(json)).unwrap();
+        assert_eq!(delivered, records.len());
+        assert_eq!(records, read_arrow(Cursor::new(arrow)).unwrap());
+
+        assert!(records.iter().enumerate().all(|(index, record)| record.index == index as u64));
+        assert!(records.windows(2).all(|pair| pair[0].time <= pair[1].time));
+        let first = &records[0];
+        assert_eq!(("arrivals", "arrivals", "Generate"), (first.source.as_str(), first.destination.as_str(), first.event_type.as_str()));
+    }
+
+    #[test]
+    fn test_replay_into_one_actor() {
+        let (_, json) = record(TraceFormat::JsonLines);
+        let records = read_json_lines(Cursor::new(json)).unwrap();
+
+        let mut station = Station::new("server", 1, Distribution::Exponential { rate: 1.2 }).to("exit");
+        let steps = replay(&records, &mut station, &mut Queue::with_seed(5)).unwrap();
+        assert_eq!(records.iter().filter(|record| record.destination == "server").count(), steps.len());
+        assert_eq!(20, station.arrivals());
+        assert_eq!(20, station.served());
+
+        // Every departure sends the customer on and schedules the release of the server.
+        for step in steps.iter().filter(|step| matches!(step.event.kind, NetworkEventKind::Departure { .. })) {
+            assert_eq!(2, step.scheduled.len());
+            assert_eq!("exit", step.scheduled[0].destination);
+        }
+    }
+}
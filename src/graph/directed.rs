@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+use crate::graph::{transpose, AdjList};
+
+/// Strongly connected components of a directed graph.
+///
+/// Components are listed in topological order of the condensation: no edge leads from a
+/// component to one listed before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Components {
+    pub components: Vec<Vec<usize>>,
+    pub component_of: Vec<usize>,
+}
+
+impl Components {
+    fn from_components(n: usize, components: Vec<Vec<usize>>) -> Components {
+        let mut component_of = vec![0; n];
+        for (index, component) in components.iter().enumerate() {
+            for &node in component {
+                component_of[node] = index;
+            }
+        }
+        Components { components, component_of }
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+}
+
+/// Error returned by `topological_sort` when the graph is not acyclic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// Nodes of one cycle, in edge order. The last node has an edge back to the first.
+    pub cycle: Vec<usize>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self.cycle.iter().map(|node| node.to_string()).collect();
+        write!(f, "graph contains a cycle: {} -> {}", path.join(" -> "), self.cycle[0])
+    }
+}
+
+impl Error for CycleError {}
+
+/// Tarjan's algorithm. The DFS is iterative so that deep graphs do not overflow the stack.
+pub fn tarjan_scc(graph: &AdjList) -> Components {
+    let n = graph.len();
+    let mut visit_time = vec![0usize; n];
+    let mut low_points = vec![0usize; n];
+    let mut on_stack = vec![false; n];
+    let mut node_stack = Vec::new();
+    let mut components = Vec::new();
+    let mut curr_t = 0;
+
+    for root in 0..n {
+        if visit_time[root] != 0 {
+            continue;
+        }
+
+        // Each frame holds a node and the position of the next neighbor to explore.
+        let mut call_stack = vec![(root, 0usize)];
+        curr_t += 1;
+        visit_time[root] = curr_t;
+        low_points[root] = curr_t;
+        node_stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&mut (curr, ref mut next_index)) = call_stack.last_mut() {
+            if let Some(&next_node) = graph[curr].get(*next_index) {
+                *next_index += 1;
+                if visit_time[next_node] == 0 {
+                    curr_t += 1;
+                    visit_time[next_node] = curr_t;
+                    low_points[next_node] = curr_t;
+                    node_stack.push(next_node);
+                    on_stack[next_node] = true;
+                    call_stack.push((next_node, 0));
+                } else if on_stack[next_node] {
+                    low_points[curr] = low_points[curr].min(visit_time[next_node]);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                low_points[parent] = low_points[parent].min(low_points[curr]);
+            }
+
+            if low_points[curr] == visit_time[curr] {
+                let mut component = Vec::new();
+                while let Some(node) = node_stack.pop() {
+                    on_stack[node] = false;
+                    component.push(node);
+                    if node == curr {
+                        break;
+                    }
+                }
+                component.sort_unstable();
+                components.push(component);
+            }
+        }
+    }
+
+    // Tarjan emits sink components first.
+    components.reverse();
+    Components::from_components(n, components)
+}
+
+/// Kosaraju's algorithm: order nodes by DFS finish time, then collect components on the
+/// transposed graph in decreasing finish order.
+pub fn kosaraju_scc(graph: &AdjList) -> Components {
+    let n = graph.len();
+    let mut visited = vec![false; n];
+    let mut finish_order = Vec::with_capacity(n);
+
+    for root in 0..n {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        let mut call_stack = vec![(root, 0usize)];
+        while let Some(&mut (curr, ref mut next_index)) = call_stack.last_mut() {
+            if let Some(&next_node) = graph[curr].get(*next_index) {
+                *next_index += 1;
+                if !visited[next_node] {
+                    visited[next_node] = true;
+                    call_stack.push((next_node, 0));
+                }
+            } else {
+                finish_order.push(curr);
+                call_stack.pop();
+            }
+        }
+    }
+
+    let reversed = transpose(graph);
+    let mut assigned = vec![false; n];
+    let mut components = Vec::new();
+    for &root in finish_order.iter().rev() {
+        if assigned[root] {
+            continue;
+        }
+        assigned[root] = true;
+        let mut component = Vec::new();
+        let mut stack = vec![root];
+        while let Some(curr) = stack.pop() {
+            component.push(curr);
+            for &next_node in reversed[curr].iter() {
+                if !assigned[next_node] {
+                    assigned[next_node] = true;
+                    stack.push(next_node);
+                }
+            }
+        }
+        component.sort_unstable();
+        components.push(component);
+    }
+
+    Components::from_components(n, components)
+}
+
+/// Builds the condensation DAG: one node per strongly connected component, with an edge
+/// between two components whenever an edge of the original graph joins them.
+/// Duplicate edges are merged and self loops dropped.
+pub fn condensation(graph: &AdjList, components: &Components) -> AdjList {
+    let mut dag = vec![Vec::new(); components.len()];
+    for (u, neighbors) in graph.iter().enumerate() {
+        let cu = components.component_of[u];
+        for &v in neighbors {
+            let cv = components.component_of[v];
+            if cu != cv {
+                dag[cu].push(cv);
+            }
+        }
+    }
+    for neighbors in dag.iter_mut() {
+        neighbors.sort_unstable();
+        neighbors.dedup();
+    }
+    dag
+}
+
+/// Kahn's algorithm. Ties are broken by node index, so the order is deterministic.
+///
+/// When the graph has a cycle, one of them is reported in the error.
+pub fn topological_sort(graph: &AdjList) -> Result<Vec<usize>, CycleError> {
+    let n = graph.len();
+    let mut in_degree = vec![0usize; n];
+    for neighbors in graph.iter() {
+        for &v in neighbors {
+            in_degree[v] += 1;
+        }
+    }
+
+    let mut queue: VecDeque<usize> = (0..n).filter(|&u| in_degree[u] == 0).collect();
+    let mut order = Vec::with_capacity(n);
+    while let Some(u) = queue.pop_front() {
+        order.push(u);
+        for &v in graph[u].iter() {
+            in_degree[v] -= 1;
+            if in_degree[v] == 0 {
+                queue.push_back(v);
+            }
+        }
+    }
+
+    if order.len() == n {
+        Ok(order)
+    } else {
+        Err(CycleError { cycle: find_cycle(graph, &in_degree) })
+    }
+}
+
+/// Walks backwards from a node left over by Kahn's algorithm. Every remaining node has a
+/// remaining predecessor, so the walk must revisit a node, closing a cycle.
+fn find_cycle(graph: &AdjList, in_degree: &[usize]) -> Vec<usize> {
+    let reversed = transpose(graph);
+    let start = (0..graph.len()).find(|&u| in_degree[u] > 0).expect("A cycle must exist.");
+
+    let mut position = vec![usize::MAX; graph.len()];
+    let mut walk = Vec::new();
+    let mut curr = start;
+    while position[curr] == usize::MAX {
+        position[curr] = walk.len();
+        walk.push(curr);
+        curr = *reversed[curr]
+            .iter()
+            .find(|&&prev| in_degree[prev] > 0)
+            .expect("A remaining node must have a remaining predecessor.");
+    }
+
+    let mut cycle = walk[position[curr]..].to_vec();
+    cycle.reverse();
+    cycle
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_graph() -> AdjList {
+        // {0, 1, 2} -> {3, 4} -> {5}, plus 6 on its own.
+        vec![
+            vec![1],
+            vec![2],
+            vec![0, 3],
+            vec![4],
+            vec![3, 5],
+            vec![],
+            vec![],
+        ]
+    }
+
+    #[test]
+    fn test_tarjan_and_kosaraju_agree() {
+        let graph = sample_graph();
+        let tarjan = tarjan_scc(&graph);
+        let kosaraju = kosaraju_scc(&graph);
+
+        let mut expected = vec![vec![0, 1, 2], vec![3, 4], vec![5], vec![6]];
+        for result in [&tarjan, &kosaraju] {
+            let mut components = result.components.clone();
+            components.sort();
+            expected.sort();
+            assert_eq!(expected, components);
+            assert_eq!(result.component_of[0], result.component_of[2]);
+            assert_ne!(result.component_of[2], result.component_of[3]);
+        }
+    }
+
+    #[test]
+    fn test_components_are_topologically_ordered() {
+        let graph = sample_graph();
+        for components in [tarjan_scc(&graph), kosaraju_scc(&graph)] {
+            let dag = condensation(&graph, &components);
+            for (cu, neighbors) in dag.iter().enumerate() {
+                for &cv in neighbors {
+                    assert!(cu < cv, "edge {} -> {} goes backwards", cu, cv);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_condensation() {
+        let graph = sample_graph();
+        let components = tarjan_scc(&graph);
+        let dag = condensation(&graph, &components);
+        assert_eq!(4, dag.len());
+        assert!(topological_sort(&dag).is_ok());
+
+        let c012 = components.component_of[0];
+        let c34 = components.component_of[3];
+        let c5 = components.component_of[5];
+        assert_eq!(vec![c34], dag[c012]);
+        assert_eq!(vec![c5], dag[c34]);
+    }
+
+    #[test]
+    fn test_topological_sort() {
+        let graph: AdjList = vec![vec![1, 2], vec![3], vec![3], vec![]];
+        assert_eq!(vec![0, 1, 2, 3], topological_sort(&graph).unwrap());
+    }
+
+    #[test]
+    fn test_topological_sort_reports_cycle() {
+        let graph: AdjList = vec![vec![1], vec![2], vec![3], vec![1], vec![0]];
+        let error = topological_sort(&graph).unwrap_err();
+
+        let mut nodes = error.cycle.clone();
+        nodes.sort();
+        assert_eq!(vec![1, 2, 3], nodes);
+        for i in 0..error.cycle.len() {
+            let u = error.cycle[i];
+            let v = error.cycle[(i + 1) % error.cycle.len()];
+            assert!(graph[u].contains(&v));
+        }
+    }
+}
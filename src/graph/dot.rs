@@ -0,0 +1,416 @@
+//! Builder for Graphviz DOT documents.
+//!
+//! All ids and attribute values are quoted and escaped when rendered, so labels may contain
+//! any text.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphKind {
+    Graph,
+    Digraph,
+}
+
+impl GraphKind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            GraphKind::Graph => "graph",
+            GraphKind::Digraph => "digraph",
+        }
+    }
+
+    fn edge_operator(&self) -> &'static str {
+        match self {
+            GraphKind::Graph => "--",
+            GraphKind::Digraph => "->",
+        }
+    }
+}
+
+/// Output formats supported by `DotGraph::render`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    Svg,
+    Png,
+    Pdf,
+}
+
+impl RenderFormat {
+    fn flag(&self) -> &'static str {
+        match self {
+            RenderFormat::Svg => "-Tsvg",
+            RenderFormat::Png => "-Tpng",
+            RenderFormat::Pdf => "-Tpdf",
+        }
+    }
+}
+
+/// Ordered list of `key="value"` attributes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    items: Vec<(String, String)>,
+}
+
+impl Attributes {
+    pub fn new() -> Attributes {
+        Attributes::default()
+    }
+
+    /// Sets an attribute, replacing any previous value for the same key.
+    pub fn set<K: ToString, V: ToString>(mut self, key: K, value: V) -> Attributes {
+        let key = key.to_string();
+        let value = value.to_string();
+        match self.items.iter_mut().find(|(k, _)| *k == key) {
+            Some(item) => item.1 = value,
+            None => self.items.push((key, value)),
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn render(&self) -> String {
+        let items: Vec<String> = self.items.iter()
+            .map(|(key, value)| format!("{}={}", quote(key), quote(value)))
+            .collect();
+        items.join(", ")
+    }
+}
+
+/// Quotes a DOT id, escaping backslashes, quotes and line breaks.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => {}
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+    Node { id: String, attributes: Attributes },
+    Edge { start: String, end: String, attributes: Attributes },
+    Subgraph(Subgraph),
+}
+
+/// Statements shared by the top-level graph and its subgraphs.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Body {
+    attributes: Attributes,
+    node_defaults: Attributes,
+    edge_defaults: Attributes,
+    statements: Vec<Statement>,
+}
+
+impl Body {
+    fn render(&self, kind: GraphKind, indent: usize, output: &mut String) {
+        let pad = "\t".repeat(indent);
+        for (key, value) in self.attributes.items.iter() {
+            output.push_str(&format!("{}{}={};\n", pad, quote(key), quote(value)));
+        }
+        if !self.node_defaults.is_empty() {
+            output.push_str(&format!("{}node[{}];\n", pad, self.node_defaults.render()));
+        }
+        if !self.edge_defaults.is_empty() {
+            output.push_str(&format!("{}edge[{}];\n", pad, self.edge_defaults.render()));
+        }
+
+        for statement in self.statements.iter() {
+            match statement {
+                Statement::Node { id, attributes } if attributes.is_empty() => {
+                    output.push_str(&format!("{}{};\n", pad, quote(id)));
+                }
+                Statement::Node { id, attributes } => {
+                    output.push_str(&format!("{}{} [{}];\n", pad, quote(id), attributes.render()));
+                }
+                Statement::Edge { start, end, attributes } => {
+                    output.push_str(&format!("{}{} {} {}", pad, quote(start), kind.edge_operator(), quote(end)));
+                    if !attributes.is_empty() {
+                        output.push_str(&format!(" [{}]", attributes.render()));
+                    }
+                    output.push_str(";\n");
+                }
+                Statement::Subgraph(subgraph) => {
+                    match &subgraph.name {
+                        Some(name) => output.push_str(&format!("{}subgraph {} {{\n", pad, quote(name))),
+                        None => output.push_str(&format!("{}{{\n", pad)),
+                    }
+                    subgraph.body.render(kind, indent + 1, output);
+                    output.push_str(&format!("{}}}\n", pad));
+                }
+            }
+        }
+    }
+}
+
+macro_rules! body_methods {
+    () => {
+        /// Sets a graph-level attribute such as `rankdir` or `label`.
+        pub fn attr<K: ToString, V: ToString>(&mut self, key: K, value: V) -> &mut Self {
+            self.body.attributes = std::mem::take(&mut self.body.attributes).set(key, value);
+            self
+        }
+
+        /// Attributes applied to every node that does not override them.
+        pub fn node_defaults(&mut self, attributes: Attributes) -> &mut Self {
+            self.body.node_defaults = attributes;
+            self
+        }
+
+        /// Attributes applied to every edge that does not override them.
+        pub fn edge_defaults(&mut self, attributes: Attributes) -> &mut Self {
+            self.body.edge_defaults = attributes;
+            self
+        }
+
+        pub fn node<I: ToString>(&mut self, id: I, attributes: Attributes) -> &mut Self {
+            self.body.statements.push(Statement::Node { id: id.to_string(), attributes });
+            self
+        }
+
+        pub fn edge<S: ToString, E: ToString>(&mut self, start: S, end: E, attributes: Attributes) -> &mut Self {
+            self.body.statements.push(Statement::Edge { start: start.to_string(), end: end.to_string(), attributes });
+            self
+        }
+
+        pub fn subgraph(&mut self, subgraph: Subgraph) -> &mut Self {
+            self.body.statements.push(Statement::Subgraph(subgraph));
+            self
+        }
+    };
+}
+
+/// A subgraph. Clusters are drawn as a box around their nodes; anonymous subgraphs are
+/// typically used for layout constraints such as `rank=same`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subgraph {
+    name: Option<String>,
+    body: Body,
+}
+
+impl Subgraph {
+    pub fn new<N: ToString>(name: N) -> Subgraph {
+        Subgraph { name: Some(name.to_string()), body: Body::default() }
+    }
+
+    pub fn anonymous() -> Subgraph {
+        Subgraph { name: None, body: Body::default() }
+    }
+
+    /// Graphviz only draws subgraphs whose name starts with `cluster` as clusters.
+    pub fn cluster<N: ToString>(name: N) -> Subgraph {
+        Subgraph::new(format!("cluster_{}", name.to_string()))
+    }
+
+    body_methods!();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotGraph {
+    kind: GraphKind,
+    strict: bool,
+    name: Option<String>,
+    body: Body,
+}
+
+impl DotGraph {
+    pub fn new(kind: GraphKind) -> DotGraph {
+        DotGraph { kind, strict: false, name: None, body: Body::default() }
+    }
+
+    pub fn graph() -> DotGraph {
+        DotGraph::new(GraphKind::Graph)
+    }
+
+    pub fn digraph() -> DotGraph {
+        DotGraph::new(GraphKind::Digraph)
+    }
+
+    /// A strict graph merges parallel edges; the attributes of the last one win.
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn name<N: ToString>(&mut self, name: N) -> &mut Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    body_methods!();
+
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Renders the graph with the local `dot` binary.
+    ///
+    /// Fails with `io::ErrorKind::NotFound` when Graphviz is not installed; see
+    /// `is_graphviz_available`.
+    pub fn render(&self, output: &Path, format: RenderFormat) -> io::Result<()> {
+        let mut child = Command::new("dot")
+            .arg(format.flag())
+            .arg("-o")
+            .arg(output)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Written from another thread, so that `dot` never waits on a full stderr pipe
+        // while we wait on a full stdin pipe.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let document = self.to_string();
+        let writer = thread::spawn(move || stdin.write_all(document.as_bytes()));
+        let result = child.wait_with_output()?;
+        let written = writer.join().unwrap_or_else(|_| Err(io::Error::other("writing to dot panicked")));
+        if !result.status.success() {
+            return Err(io::Error::other(String::from_utf8_lossy(&result.stderr).into_owned()));
+        }
+        written
+    }
+}
+
+impl fmt::Display for DotGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut output = String::new();
+        if self.strict {
+            output.push_str("strict ");
+        }
+        output.push_str(self.kind.keyword());
+        if let Some(name) = &self.name {
+            output.push(' ');
+            output.push_str(&quote(name));
+        }
+        output.push_str(" {\n");
+        self.body.render(self.kind, 1, &mut output);
+        output.push_str("}\n");
+        f.write_str(&output)
+    }
+}
+
+/// Whether a `dot` binary can be found on the `PATH`.
+pub fn is_graphviz_available() -> bool {
+    Command::new("dot")
+        .arg("-V")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_graph() {
+        let mut graph = DotGraph::digraph();
+        graph.strict(true)
+            .attr("rankdir", "LR")
+            .node_defaults(Attributes::new().set("shape", "circle"))
+            .node("x1", Attributes::new().set("label", 1).set("fillcolor", "white"))
+            .edge("x1", "x2", Attributes::new().set("label", "8[0]"))
+            .edge("x2", "x3", Attributes::new());
+
+        let expected = concat!(
+            "strict digraph {\n",
+            "\t\"rankdir\"=\"LR\";\n",
+            "\tnode[\"shape\"=\"circle\"];\n",
+            "\t\"x1\" [\"label\"=\"1\", \"fillcolor\"=\"white\"];\n",
+            "\t\"x1\" -> \"x2\" [\"label\"=\"8[0]\"];\n",
+            "\t\"x2\" -> \"x3\";\n",
+            "}\n",
+        );
+        assert_eq!(expected, graph.to_string());
+    }
+
+    #[test]
+    fn test_subgraphs() {
+        let mut cluster = Subgraph::cluster("a");
+        cluster.attr("label", "Block A").node("x1", Attributes::new());
+        let mut rank = Subgraph::anonymous();
+        rank.attr("rank", "same").node("x2", Attributes::new()).node("x3", Attributes::new());
+
+        let mut graph = DotGraph::graph();
+        graph.subgraph(cluster).subgraph(rank).edge(1, 2, Attributes::new());
+
+        let expected = concat!(
+            "graph {\n",
+            "\tsubgraph \"cluster_a\" {\n",
+            "\t\t\"label\"=\"Block A\";\n",
+            "\t\t\"x1\";\n",
+            "\t}\n",
+            "\t{\n",
+            "\t\t\"rank\"=\"same\";\n",
+            "\t\t\"x2\";\n",
+            "\t\t\"x3\";\n",
+            "\t}\n",
+            "\t\"1\" -- \"2\";\n",
+            "}\n",
+        );
+        assert_eq!(expected, graph.to_string());
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!("\"say \\\"hi\\\"\\nC:\\\\tmp\"", quote("say \"hi\"\nC:\\tmp"));
+        let attributes = Attributes::new().set("color", "red").set("color", "blue");
+        assert_eq!("\"color\"=\"blue\"", attributes.render());
+    }
+
+    #[test]
+    fn test_builder_escapes_ids_and_values() {
+        let mut cluster = Subgraph::cluster("a\"b");
+        cluster.attr("label", "two\nlines");
+        let mut graph = DotGraph::digraph();
+        graph.name("my \"graph\"")
+            .subgraph(cluster)
+            .node("a\\b", Attributes::new().set("label", "x\r\ny"))
+            .edge("a\\b", "\"c\"", Attributes::new().set("tooltip", "say \"hi\""));
+
+        let expected = concat!(
+            "digraph \"my \\\"graph\\\"\" {\n",
+            "\tsubgraph \"cluster_a\\\"b\" {\n",
+            "\t\t\"label\"=\"two\\nlines\";\n",
+            "\t}\n",
+            "\t\"a\\\\b\" [\"label\"=\"x\\ny\"];\n",
+            "\t\"a\\\\b\" -> \"\\\"c\\\"\" [\"tooltip\"=\"say \\\"hi\\\"\"];\n",
+            "}\n",
+        );
+        assert_eq!(expected, graph.to_string());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut graph = DotGraph::digraph();
+        graph.edge("a", "b", Attributes::new().set("label", "\"quoted\""));
+        let output = std::env::temp_dir().join(format!("dot_render_{}.svg", std::process::id()));
+        match graph.render(&output, RenderFormat::Svg) {
+            Ok(()) => {
+                assert!(fs::read_to_string(&output).unwrap().contains("<svg"));
+                fs::remove_file(&output).unwrap();
+            }
+            Err(error) => {
+                assert!(!is_graphviz_available());
+                assert_eq!(io::ErrorKind::NotFound, error.kind());
+            }
+        }
+    }
+}
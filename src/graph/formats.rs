@@ -0,0 +1,716 @@
+//! Reading and writing graphs in common text formats.
+//!
+//! Node ids are kept exactly as they appear in the file. For the 1-based formats
+//! (adjacency, DIMACS, Matrix Market) index 0 is therefore an unused, isolated node, which
+//! is the layout `graph_lab` expects.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::graph::WeightedGraph;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// `graph_lab` format: a node count `n`, then one line per node `1..=n` holding its
+    /// comma-separated neighbors. A blank line is an isolated node.
+    Adjacency,
+    /// One `start end [weight]` edge per line, separated by whitespace or commas.
+    EdgeList,
+    /// DIMACS shortest-path (`p sp`, `a` lines) or undirected (`p edge`, `e` lines) format.
+    Dimacs,
+    /// Matrix Market coordinate format, `general` (directed) or `symmetric` (undirected).
+    MatrixMarket,
+    GraphML,
+}
+
+impl GraphFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<GraphFormat> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "adj" | "txt" => Some(GraphFormat::Adjacency),
+            "edges" | "el" | "csv" => Some(GraphFormat::EdgeList),
+            "gr" | "dimacs" | "col" => Some(GraphFormat::Dimacs),
+            "mtx" => Some(GraphFormat::MatrixMarket),
+            "graphml" | "xml" => Some(GraphFormat::GraphML),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    MissingHeader,
+    MissingField(&'static str),
+    InvalidNumber(String),
+    NodeOutOfRange { node: usize, node_count: usize },
+    UnknownNode(String),
+    UnexpectedContent(String),
+    Unsupported(String),
+    EdgeCountMismatch { declared: usize, found: usize },
+    /// The graph would need more than `MAX_NODES` nodes.
+    TooManyNodes(usize),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::MissingHeader => write!(f, "missing header"),
+            ParseErrorKind::MissingField(field) => write!(f, "missing field `{}`", field),
+            ParseErrorKind::InvalidNumber(value) => write!(f, "invalid number `{}`", value),
+            ParseErrorKind::NodeOutOfRange { node, node_count } => {
+                write!(f, "node {} is out of range (declared {} nodes)", node, node_count)
+            }
+            ParseErrorKind::UnknownNode(id) => write!(f, "unknown node `{}`", id),
+            ParseErrorKind::UnexpectedContent(content) => write!(f, "unexpected content `{}`", content),
+            ParseErrorKind::Unsupported(feature) => write!(f, "unsupported: {}", feature),
+            ParseErrorKind::EdgeCountMismatch { declared, found } => {
+                write!(f, "header declares {} edges but {} were found", declared, found)
+            }
+            ParseErrorKind::TooManyNodes(node_count) => {
+                write!(f, "{} nodes exceed the limit of {}", node_count, MAX_NODES)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GraphFileError {
+    Io(io::Error),
+    /// `line` is 1-based.
+    Parse { line: usize, kind: ParseErrorKind },
+}
+
+impl fmt::Display for GraphFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphFileError::Io(error) => write!(f, "I/O error: {}", error),
+            GraphFileError::Parse { line, kind } => write!(f, "line {}: {}", line, kind),
+        }
+    }
+}
+
+impl Error for GraphFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GraphFileError::Io(error) => Some(error),
+            GraphFileError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for GraphFileError {
+    fn from(error: io::Error) -> Self {
+        GraphFileError::Io(error)
+    }
+}
+
+fn parse_error(line: usize, kind: ParseErrorKind) -> GraphFileError {
+    GraphFileError::Parse { line, kind }
+}
+
+fn parse_number<T: FromStr>(token: &str, line: usize) -> Result<T, GraphFileError> {
+    token.parse().map_err(|_| parse_error(line, ParseErrorKind::InvalidNumber(token.to_owned())))
+}
+
+fn next_number<'a, T, I>(tokens: &mut I, field: &'static str, line: usize) -> Result<T, GraphFileError>
+where
+    T: FromStr,
+    I: Iterator<Item = &'a str>,
+{
+    let token = tokens.next().ok_or(parse_error(line, ParseErrorKind::MissingField(field)))?;
+    parse_number(token, line)
+}
+
+/// Largest number of nodes of a graph read from a file, node 0 of the 1-based formats
+/// included. Guards against allocating for a mistyped node id or count.
+pub const MAX_NODES: usize = 1 << 24;
+
+/// Size of a graph holding nodes `0..=max_node`.
+fn size_with(max_node: usize, line: usize) -> Result<usize, GraphFileError> {
+    max_node
+        .checked_add(1)
+        .filter(|&size| size <= MAX_NODES)
+        .ok_or(parse_error(line, ParseErrorKind::TooManyNodes(max_node.saturating_add(1))))
+}
+
+/// Size of a graph holding nodes `1..=node_count` of a 1-based format, and the unused
+/// node 0.
+fn one_based_size(node_count: usize, line: usize) -> Result<usize, GraphFileError> {
+    size_with(node_count, line)
+}
+
+fn check_node(node: usize, node_count: usize, line: usize) -> Result<usize, GraphFileError> {
+    if node == 0 || node > node_count {
+        Err(parse_error(line, ParseErrorKind::NodeOutOfRange { node, node_count }))
+    } else {
+        Ok(node)
+    }
+}
+
+pub fn read_graph_file(path: &Path, format: GraphFormat) -> Result<WeightedGraph, GraphFileError> {
+    read_graph(BufReader::new(File::open(path)?), format)
+}
+
+pub fn read_graph<R: BufRead>(reader: R, format: GraphFormat) -> Result<WeightedGraph, GraphFileError> {
+    let lines = reader.lines().collect::<Result<Vec<String>, io::Error>>()?;
+    match format {
+        GraphFormat::Adjacency => read_adjacency(&lines),
+        GraphFormat::EdgeList => read_edge_list(&lines),
+        GraphFormat::Dimacs => read_dimacs(&lines),
+        GraphFormat::MatrixMarket => read_matrix_market(&lines),
+        GraphFormat::GraphML => read_graphml(&lines),
+    }
+}
+
+fn read_adjacency(lines: &[String]) -> Result<WeightedGraph, GraphFileError> {
+    let first_line = lines.first().ok_or(parse_error(1, ParseErrorKind::MissingHeader))?;
+    let n: usize = parse_number(first_line.trim(), 1)?;
+    let mut graph = WeightedGraph::new(one_based_size(n, 1)?);
+
+    for (index, line) in lines.iter().enumerate().skip(1) {
+        let line_number = index + 1;
+        if index > n {
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Err(parse_error(line_number, ParseErrorKind::UnexpectedContent(line.clone())));
+        }
+
+        for item in line.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let neighbor = check_node(parse_number(item, line_number)?, n, line_number)?;
+            graph.add_edge(index, neighbor, 1.0);
+        }
+    }
+
+    Ok(graph)
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#') || line.starts_with('%')
+}
+
+fn read_edge_list(lines: &[String]) -> Result<WeightedGraph, GraphFileError> {
+    let mut graph = WeightedGraph::new(0);
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || is_comment(line) {
+            continue;
+        }
+
+        let mut tokens = line.split(|c: char| c == ',' || c.is_whitespace()).filter(|t| !t.is_empty());
+        let start = next_number(&mut tokens, "start", line_number)?;
+        let end = next_number(&mut tokens, "end", line_number)?;
+        let weight = match tokens.next() {
+            Some(token) => parse_number(token, line_number)?,
+            None => 1.0,
+        };
+        if let Some(token) = tokens.next() {
+            return Err(parse_error(line_number, ParseErrorKind::UnexpectedContent(token.to_owned())));
+        }
+        size_with(usize::max(start, end), line_number)?;
+        graph.add_edge(start, end, weight);
+    }
+    Ok(graph)
+}
+
+fn read_dimacs(lines: &[String]) -> Result<WeightedGraph, GraphFileError> {
+    let mut graph: Option<WeightedGraph> = None;
+    let mut node_count = 0;
+    let mut declared_edges = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            None | Some("c") => continue,
+            Some("p") => {
+                if graph.is_some() {
+                    return Err(parse_error(line_number, ParseErrorKind::UnexpectedContent(line.clone())));
+                }
+                let directed = match tokens.next() {
+                    Some("sp") => true,
+                    Some("edge") | Some("col") => false,
+                    Some(other) => return Err(parse_error(line_number, ParseErrorKind::Unsupported(format!("problem type `{}`", other)))),
+                    None => return Err(parse_error(line_number, ParseErrorKind::MissingField("problem type"))),
+                };
+                node_count = next_number(&mut tokens, "node count", line_number)?;
+                declared_edges = next_number(&mut tokens, "edge count", line_number)?;
+                let size = one_based_size(node_count, line_number)?;
+                graph = Some(if directed { WeightedGraph::new(size) } else { WeightedGraph::new_undirected(size) });
+            }
+            Some(kind @ ("a" | "e")) => {
+                let graph = graph.as_mut().ok_or(parse_error(line_number, ParseErrorKind::MissingHeader))?;
+                let start = check_node(next_number(&mut tokens, "start", line_number)?, node_count, line_number)?;
+                let end = check_node(next_number(&mut tokens, "end", line_number)?, node_count, line_number)?;
+                let weight = match (kind, tokens.next()) {
+                    ("a", None) => return Err(parse_error(line_number, ParseErrorKind::MissingField("weight"))),
+                    (_, Some(token)) => parse_number(token, line_number)?,
+                    (_, None) => 1.0,
+                };
+                graph.add_edge(start, end, weight);
+            }
+            Some(_) => return Err(parse_error(line_number, ParseErrorKind::UnexpectedContent(line.clone()))),
+        }
+    }
+
+    let graph = graph.ok_or(parse_error(lines.len().max(1), ParseErrorKind::MissingHeader))?;
+    if graph.edge_count() != declared_edges {
+        return Err(parse_error(lines.len(), ParseErrorKind::EdgeCountMismatch { declared: declared_edges, found: graph.edge_count() }));
+    }
+    Ok(graph)
+}
+
+fn read_matrix_market(lines: &[String]) -> Result<WeightedGraph, GraphFileError> {
+    let header = lines.first().ok_or(parse_error(1, ParseErrorKind::MissingHeader))?;
+    let fields: Vec<String> = header.split_whitespace().map(str::to_lowercase).collect();
+    if fields.len() != 5 || fields[0] != "%%matrixmarket" || fields[1] != "matrix" {
+        return Err(parse_error(1, ParseErrorKind::MissingHeader));
+    }
+    if fields[2] != "coordinate" {
+        return Err(parse_error(1, ParseErrorKind::Unsupported(format!("`{}` matrices", fields[2]))));
+    }
+    let has_values = match fields[3].as_str() {
+        "real" | "integer" => true,
+        "pattern" => false,
+        other => return Err(parse_error(1, ParseErrorKind::Unsupported(format!("`{}` values", other)))),
+    };
+    let directed = match fields[4].as_str() {
+        "general" => true,
+        "symmetric" => false,
+        other => return Err(parse_error(1, ParseErrorKind::Unsupported(format!("`{}` symmetry", other)))),
+    };
+
+    let mut graph: Option<WeightedGraph> = None;
+    let mut node_count = 0;
+    let mut declared_edges = 0;
+    for (index, line) in lines.iter().enumerate().skip(1) {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('%') {
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace();
+        match graph.as_mut() {
+            None => {
+                let rows: usize = next_number(&mut tokens, "rows", line_number)?;
+                let columns: usize = next_number(&mut tokens, "columns", line_number)?;
+                if rows != columns {
+                    return Err(parse_error(line_number, ParseErrorKind::Unsupported("non-square matrix".to_owned())));
+                }
+                node_count = rows;
+                declared_edges = next_number(&mut tokens, "entries", line_number)?;
+                let size = one_based_size(node_count, line_number)?;
+                graph = Some(if directed { WeightedGraph::new(size) } else { WeightedGraph::new_undirected(size) });
+            }
+            Some(graph) => {
+                let start = check_node(next_number(&mut tokens, "row", line_number)?, node_count, line_number)?;
+                let end = check_node(next_number(&mut tokens, "column", line_number)?, node_count, line_number)?;
+                let weight = if has_values { next_number(&mut tokens, "value", line_number)? } else { 1.0 };
+                graph.add_edge(start, end, weight);
+            }
+        }
+    }
+
+    let graph = graph.ok_or(parse_error(lines.len(), ParseErrorKind::MissingField("size")))?;
+    if graph.edge_count() != declared_edges {
+        return Err(parse_error(lines.len(), ParseErrorKind::EdgeCountMismatch { declared: declared_edges, found: graph.edge_count() }));
+    }
+    Ok(graph)
+}
+
+/// A start or empty-element tag, with its attributes and the line it starts on.
+struct XmlTag {
+    name: String,
+    attributes: HashMap<String, String>,
+    closing: bool,
+    line: usize,
+    /// Text between this tag and the next one.
+    text: String,
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Splits a document into tags. Only the subset of XML used by GraphML is understood:
+/// declarations and comments are skipped, CDATA and DTDs are not supported.
+fn scan_xml(lines: &[String]) -> Result<Vec<XmlTag>, GraphFileError> {
+    let document = lines.join("\n");
+    let mut tags: Vec<XmlTag> = Vec::new();
+    let mut rest = document.as_str();
+    let mut line = 1;
+
+    while let Some(open) = rest.find('<') {
+        if let Some(tag) = tags.last_mut() {
+            tag.text.push_str(&unescape_xml(&rest[..open]));
+        }
+        line += rest[..open].matches('\n').count();
+        rest = &rest[open..];
+
+        let terminator = if rest.starts_with("<!--") { "-->" } else { ">" };
+        let close = rest.find(terminator).ok_or(parse_error(line, ParseErrorKind::UnexpectedContent("unterminated tag".to_owned())))?;
+        let body = &rest[1..close];
+        let tag_line = line;
+        line += body.matches('\n').count();
+        rest = &rest[close + terminator.len()..];
+
+        if body.starts_with('?') || body.starts_with("!--") {
+            continue;
+        }
+        if body.starts_with('!') {
+            return Err(parse_error(tag_line, ParseErrorKind::Unsupported("DTD and CDATA sections".to_owned())));
+        }
+
+        let closing = body.starts_with('/');
+        let body = body.trim_start_matches('/').trim_end_matches('/');
+        let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+        let mut attributes = HashMap::new();
+        let mut attribute_text = body[name_end..].trim();
+        while !attribute_text.is_empty() {
+            let equals = attribute_text.find('=').ok_or(parse_error(tag_line, ParseErrorKind::UnexpectedContent(attribute_text.to_owned())))?;
+            let key = attribute_text[..equals].trim();
+            let value_text = attribute_text[equals + 1..].trim_start();
+            let quote = value_text.chars().next().filter(|&c| c == '"' || c == '\'')
+                .ok_or(parse_error(tag_line, ParseErrorKind::UnexpectedContent(value_text.to_owned())))?;
+            let value_end = value_text[1..].find(quote)
+                .ok_or(parse_error(tag_line, ParseErrorKind::UnexpectedContent(value_text.to_owned())))?;
+            attributes.insert(key.to_owned(), unescape_xml(&value_text[1..value_end + 1]));
+            attribute_text = value_text[value_end + 2..].trim_start();
+        }
+
+        tags.push(XmlTag { name: body[..name_end].to_owned(), attributes, closing, line: tag_line, text: String::new() });
+    }
+
+    Ok(tags)
+}
+
+fn read_graphml(lines: &[String]) -> Result<WeightedGraph, GraphFileError> {
+    let tags = scan_xml(lines)?;
+
+    let graph_tag = tags.iter().find(|tag| tag.name == "graph" && !tag.closing)
+        .ok_or(parse_error(1, ParseErrorKind::MissingField("graph")))?;
+    let directed = match graph_tag.attributes.get("edgedefault").map(String::as_str) {
+        Some("directed") => true,
+        Some("undirected") => false,
+        Some(other) => return Err(parse_error(graph_tag.line, ParseErrorKind::Unsupported(format!("edgedefault `{}`", other)))),
+        None => return Err(parse_error(graph_tag.line, ParseErrorKind::MissingField("edgedefault"))),
+    };
+
+    let weight_keys: Vec<&String> = tags.iter()
+        .filter(|tag| tag.name == "key" && tag.attributes.get("attr.name").map(String::as_str) == Some("weight"))
+        .filter_map(|tag| tag.attributes.get("id"))
+        .collect();
+
+    let mut node_ids: HashMap<&str, usize> = HashMap::new();
+    for tag in tags.iter().filter(|tag| tag.name == "node" && !tag.closing) {
+        let id = tag.attributes.get("id").ok_or(parse_error(tag.line, ParseErrorKind::MissingField("id")))?;
+        let index = node_ids.len();
+        node_ids.entry(id).or_insert(index);
+    }
+
+    let mut graph = if directed { WeightedGraph::new(node_ids.len()) } else { WeightedGraph::new_undirected(node_ids.len()) };
+    for (position, tag) in tags.iter().enumerate() {
+        if tag.name != "edge" || tag.closing {
+            continue;
+        }
+        if tag.attributes.get("directed").is_some_and(|value| (value == "true") != directed) {
+            return Err(parse_error(tag.line, ParseErrorKind::Unsupported("mixed directed and undirected edges".to_owned())));
+        }
+
+        let endpoint = |field: &'static str| -> Result<usize, GraphFileError> {
+            let id = tag.attributes.get(field).ok_or(parse_error(tag.line, ParseErrorKind::MissingField(field)))?;
+            node_ids.get(id.as_str()).copied().ok_or(parse_error(tag.line, ParseErrorKind::UnknownNode(id.clone())))
+        };
+        let start = endpoint("source")?;
+        let end = endpoint("target")?;
+
+        let mut weight = 1.0;
+        for data in tags[position + 1..].iter().take_while(|t| t.name != "edge") {
+            let is_weight = data.name == "data" && !data.closing
+                && data.attributes.get("key").is_some_and(|key| weight_keys.contains(&key));
+            if is_weight {
+                weight = parse_number(data.text.trim(), data.line)?;
+            }
+        }
+        graph.add_edge(start, end, weight);
+    }
+
+    Ok(graph)
+}
+
+pub fn write_graph_file(graph: &WeightedGraph, path: &Path, format: GraphFormat) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_graph(graph, &mut writer, format)?;
+    writer.flush()
+}
+
+/// Writes `graph` in the given format.
+///
+/// The 1-based formats fail with `InvalidInput` if an edge starts or ends at node 0, and
+/// write a graph without nodes as one with no node besides the unused node 0. Undirected
+/// DIMACS (`e` lines) has no weights, so they are dropped.
+pub fn write_graph<W: Write>(graph: &WeightedGraph, writer: &mut W, format: GraphFormat) -> io::Result<()> {
+    let one_based = matches!(format, GraphFormat::Adjacency | GraphFormat::Dimacs | GraphFormat::MatrixMarket);
+    if one_based && graph.edges().iter().any(|edge| edge.start == 0 || edge.end == 0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "node 0 cannot be written in a 1-based format"));
+    }
+    let n = if graph.node_count() == 0 { 0 } else { graph.node_count() - 1 };
+
+    match format {
+        GraphFormat::Adjacency => {
+            writeln!(writer, "{}", n)?;
+            for node in 1..=n {
+                let items: Vec<String> = graph.neighbors(node).iter().map(|edge| edge.end.to_string()).collect();
+                writeln!(writer, "{}", items.join(","))?;
+            }
+        }
+        GraphFormat::EdgeList => {
+            for edge in graph.edges() {
+                writeln!(writer, "{} {} {}", edge.start, edge.end, edge.weight)?;
+            }
+        }
+        GraphFormat::Dimacs => {
+            let (problem, prefix) = if graph.is_directed() { ("sp", "a") } else { ("edge", "e") };
+            writeln!(writer, "p {} {} {}", problem, n, graph.edge_count())?;
+            for edge in graph.edges() {
+                if graph.is_directed() {
+                    writeln!(writer, "{} {} {} {}", prefix, edge.start, edge.end, edge.weight)?;
+                } else {
+                    writeln!(writer, "{} {} {}", prefix, edge.start, edge.end)?;
+                }
+            }
+        }
+        GraphFormat::MatrixMarket => {
+            let symmetry = if graph.is_directed() { "general" } else { "symmetric" };
+            writeln!(writer, "%%MatrixMarket matrix coordinate real {}", symmetry)?;
+            writeln!(writer, "{} {} {}", n, n, graph.edge_count())?;
+            for edge in graph.edges() {
+                // Symmetric matrices only store the lower triangle.
+                let (row, column) = if graph.is_directed() || edge.start >= edge.end {
+                    (edge.start, edge.end)
+                } else {
+                    (edge.end, edge.start)
+                };
+                writeln!(writer, "{} {} {}", row, column, edge.weight)?;
+            }
+        }
+        GraphFormat::GraphML => {
+            let edge_default = if graph.is_directed() { "directed" } else { "undirected" };
+            writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+            writeln!(writer, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+            writeln!(writer, "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>")?;
+            writeln!(writer, "  <graph id=\"G\" edgedefault=\"{}\">", edge_default)?;
+            for node in 0..graph.node_count() {
+                writeln!(writer, "    <node id=\"{}\"/>", escape_xml(&format!("n{}", node)))?;
+            }
+            for edge in graph.edges() {
+                writeln!(writer, "    <edge source=\"n{}\" target=\"n{}\">", edge.start, edge.end)?;
+                writeln!(writer, "      <data key=\"weight\">{}</data>", edge.weight)?;
+                writeln!(writer, "    </edge>")?;
+            }
+            writeln!(writer, "  </graph>")?;
+            writeln!(writer, "</graphml>")?;
+        }
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::WeightedEdge;
+
+    fn read_str(content: &str, format: GraphFormat) -> Result<WeightedGraph, GraphFileError> {
+        read_graph(content.as_bytes(), format)
+    }
+
+    fn parse_error_line(result: Result<WeightedGraph, GraphFileError>) -> (usize, ParseErrorKind) {
+        match result {
+            Err(GraphFileError::Parse { line, kind }) => (line, kind),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    fn round_trip(graph: &WeightedGraph, format: GraphFormat) -> WeightedGraph {
+        let mut buffer = Vec::new();
+        write_graph(graph, &mut buffer, format).unwrap();
+        read_graph(buffer.as_slice(), format).unwrap()
+    }
+
+    #[test]
+    fn test_adjacency_with_isolated_node_and_trailing_comma() {
+        let graph = read_str("4\n2,3,\n1\n\n1,\n", GraphFormat::Adjacency).unwrap();
+        assert_eq!(vec![vec![], vec![2, 3], vec![1], vec![], vec![1]], graph.to_adj_list());
+    }
+
+    #[test]
+    fn test_adjacency_missing_trailing_lines_are_isolated() {
+        let graph = read_str("3\n2\n1", GraphFormat::Adjacency).unwrap();
+        assert_eq!(4, graph.node_count());
+        assert!(graph.neighbors(3).is_empty());
+    }
+
+    #[test]
+    fn test_adjacency_errors_report_line_numbers() {
+        assert_eq!(
+            (3, ParseErrorKind::InvalidNumber("x".to_owned())),
+            parse_error_line(read_str("3\n2\n1,x\n", GraphFormat::Adjacency)),
+        );
+        assert_eq!(
+            (2, ParseErrorKind::NodeOutOfRange { node: 5, node_count: 3 }),
+            parse_error_line(read_str("3\n5\n", GraphFormat::Adjacency)),
+        );
+        assert_eq!((1, ParseErrorKind::MissingHeader), parse_error_line(read_str("", GraphFormat::Adjacency)));
+    }
+
+    #[test]
+    fn test_edge_list() {
+        let graph = read_str("# comment\n0 1 2.5\n1,2\n\n", GraphFormat::EdgeList).unwrap();
+        assert_eq!(&[WeightedEdge::new(0, 1, 2.5), WeightedEdge::new(1, 2, 1.0)], graph.edges());
+        assert_eq!((1, ParseErrorKind::MissingField("end")), parse_error_line(read_str("0\n", GraphFormat::EdgeList)));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let too_many = ParseErrorKind::TooManyNodes;
+        assert_eq!(
+            (2, too_many(usize::MAX)),
+            parse_error_line(read_str("0 1\n0 18446744073709551615\n", GraphFormat::EdgeList)),
+        );
+        assert_eq!((1, too_many(4_000_000_000_001)), parse_error_line(read_str("0 4000000000000\n", GraphFormat::EdgeList)));
+        assert_eq!((1, too_many(MAX_NODES + 1)), parse_error_line(read_str(&format!("{}\n", MAX_NODES), GraphFormat::Adjacency)));
+        assert_eq!((1, too_many(usize::MAX)), parse_error_line(read_str("p sp 18446744073709551615 0\n", GraphFormat::Dimacs)));
+        assert_eq!(MAX_NODES, read_str(&format!("0 {}\n", MAX_NODES - 1), GraphFormat::EdgeList).unwrap().node_count());
+    }
+
+    #[test]
+    fn test_dimacs() {
+        let content = "c sample\np sp 3 2\na 1 2 4\na 2 3 1\n";
+        let graph = read_str(content, GraphFormat::Dimacs).unwrap();
+        assert!(graph.is_directed());
+        assert_eq!(&[WeightedEdge::new(1, 2, 4.0), WeightedEdge::new(2, 3, 1.0)], graph.edges());
+
+        assert_eq!(
+            (3, ParseErrorKind::EdgeCountMismatch { declared: 2, found: 1 }),
+            parse_error_line(read_str("p sp 3 2\na 1 2 4\n\n", GraphFormat::Dimacs)),
+        );
+        assert_eq!((1, ParseErrorKind::MissingHeader), parse_error_line(read_str("a 1 2 4\n", GraphFormat::Dimacs)));
+    }
+
+    #[test]
+    fn test_matrix_market() {
+        let content = "%%MatrixMarket matrix coordinate real symmetric\n% comment\n3 3 2\n2 1 1.5\n3 2 2.5\n";
+        let graph = read_str(content, GraphFormat::MatrixMarket).unwrap();
+        assert!(!graph.is_directed());
+        assert_eq!(vec![vec![], vec![2], vec![1, 3], vec![2]], graph.to_adj_list());
+
+        let content = "%%MatrixMarket matrix array real general\n";
+        assert_eq!(
+            (1, ParseErrorKind::Unsupported("`array` matrices".to_owned())),
+            parse_error_line(read_str(content, GraphFormat::MatrixMarket)),
+        );
+    }
+
+    #[test]
+    fn test_graphml() {
+        let content = r#"<?xml version="1.0"?>
+<graphml>
+  <key id="d0" for="edge" attr.name="weight" attr.type="double"/>
+  <graph edgedefault="directed">
+    <!-- nodes -->
+    <node id="a"/>
+    <node id="b"/>
+    <edge source="a" target="b"><data key="d0">2.5</data></edge>
+    <edge source="b" target="a"/>
+  </graph>
+</graphml>"#;
+        let graph = read_str(content, GraphFormat::GraphML).unwrap();
+        assert_eq!(&[WeightedEdge::new(0, 1, 2.5), WeightedEdge::new(1, 0, 1.0)], graph.edges());
+
+        let content = "<graphml>\n<graph edgedefault=\"directed\">\n<node id=\"a\"/>\n<edge source=\"a\" target=\"c\"/>\n</graph></graphml>";
+        assert_eq!(
+            (4, ParseErrorKind::UnknownNode("c".to_owned())),
+            parse_error_line(read_str(content, GraphFormat::GraphML)),
+        );
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let directed = WeightedGraph::from_edges(4, true, vec![(1, 2, 1.5), (2, 3, 2.0), (3, 1, 0.5)]);
+        let undirected = WeightedGraph::from_edges(4, false, vec![(1, 2, 1.5), (2, 3, 2.0)]);
+
+        for format in [GraphFormat::EdgeList, GraphFormat::Dimacs, GraphFormat::MatrixMarket, GraphFormat::GraphML] {
+            let result = round_trip(&directed, format);
+            assert_eq!(directed.edges(), result.edges(), "{:?}", format);
+        }
+        for format in [GraphFormat::MatrixMarket, GraphFormat::GraphML] {
+            let result = round_trip(&undirected, format);
+            assert_eq!(undirected.to_adj_list(), result.to_adj_list(), "{:?}", format);
+        }
+
+        let unit = WeightedGraph::from_adj_list(&vec![vec![], vec![2, 3], vec![1], vec![1]]);
+        assert_eq!(unit.to_adj_list(), round_trip(&unit, GraphFormat::Adjacency).to_adj_list());
+    }
+
+    #[test]
+    fn test_write_rejects_node_zero_in_one_based_format() {
+        for edge in [(0, 1, 1.0), (1, 0, 1.0)] {
+            let graph = WeightedGraph::from_edges(2, true, vec![edge]);
+            for format in [GraphFormat::Adjacency, GraphFormat::Dimacs, GraphFormat::MatrixMarket] {
+                let error = write_graph(&graph, &mut Vec::new(), format).unwrap_err();
+                assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_graph_round_trips() {
+        let empty = read_str("", GraphFormat::EdgeList).unwrap();
+        assert_eq!(0, empty.node_count());
+        for format in [GraphFormat::Adjacency, GraphFormat::EdgeList, GraphFormat::Dimacs, GraphFormat::MatrixMarket, GraphFormat::GraphML] {
+            assert_eq!(0, round_trip(&empty, format).edge_count(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_headers() {
+        assert_eq!(
+            (1, ParseErrorKind::Unsupported("problem type `max`".to_owned())),
+            parse_error_line(read_str("p max 2 1
+n 1 s
+n 2 t
+a 1 2 3
+", GraphFormat::Dimacs)),
+        );
+        let header = usize::MAX.to_string();
+        assert_eq!((1, ParseErrorKind::TooManyNodes(usize::MAX)), parse_error_line(read_str(&header, GraphFormat::Adjacency)));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Some(GraphFormat::MatrixMarket), GraphFormat::from_path(Path::new("data/graph.mtx")));
+        assert_eq!(Some(GraphFormat::Adjacency), GraphFormat::from_path(Path::new("graph_5.txt")));
+        assert_eq!(None, GraphFormat::from_path(Path::new("graph")));
+    }
+}
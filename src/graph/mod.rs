@@ -0,0 +1,24 @@
+pub mod directed;
+pub mod dot;
+pub mod formats;
+pub mod mst;
+pub mod shortest_path;
+mod union_find;
+mod weighted;
+
+pub use union_find::UnionFind;
+pub use weighted::{WeightedEdge, WeightedGraph};
+
+/// Adjacency list: `neighbors[u]` holds the nodes reachable from `u` through a single edge.
+pub type AdjList = Vec<Vec<usize>>;
+
+/// Builds the transposed graph, i.e. the graph with every edge reversed.
+pub fn transpose(graph: &AdjList) -> AdjList {
+    let mut reversed = vec![Vec::new(); graph.len()];
+    for (u, neighbors) in graph.iter().enumerate() {
+        for &v in neighbors {
+            reversed[v].push(u);
+        }
+    }
+    reversed
+}
@@ -0,0 +1,258 @@
+//! Minimum spanning trees.
+//!
+//! Every algorithm treats the graph as undirected. When the graph is not connected the
+//! result is a minimum spanning forest, with one tree per connected component.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::thread;
+
+use crate::graph::{UnionFind, WeightedEdge, WeightedGraph};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanningTree {
+    pub edges: Vec<WeightedEdge>,
+    pub total_weight: f64,
+}
+
+impl SpanningTree {
+    fn from_edges(edges: Vec<WeightedEdge>) -> SpanningTree {
+        let total_weight = edges.iter().map(|edge| edge.weight).sum();
+        SpanningTree { edges, total_weight }
+    }
+}
+
+/// Orders edges by weight, then by position in `graph.edges()`, so that every algorithm
+/// breaks ties the same way.
+fn compare_edges(edges: &[WeightedEdge], a: usize, b: usize) -> Ordering {
+    edges[a].weight.total_cmp(&edges[b].weight).then(a.cmp(&b))
+}
+
+/// Kruskal's algorithm: add edges by increasing weight unless they close a cycle.
+pub fn kruskal(graph: &WeightedGraph) -> SpanningTree {
+    let edges = graph.edges();
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by(|&a, &b| compare_edges(edges, a, b));
+
+    let mut sets = UnionFind::new(graph.node_count());
+    let mut tree = Vec::new();
+    for index in order {
+        let edge = edges[index];
+        if sets.union(edge.start, edge.end) {
+            tree.push(edge);
+            if sets.set_count() == 1 {
+                break;
+            }
+        }
+    }
+    SpanningTree::from_edges(tree)
+}
+
+/// Min-heap entry for Prim's algorithm.
+struct HeapEntry {
+    weight: f64,
+    edge_index: usize,
+    node: usize,
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.weight.total_cmp(&self.weight).then_with(|| other.edge_index.cmp(&self.edge_index))
+    }
+}
+
+/// Prim's algorithm with a binary heap, grown from every node not yet in the forest.
+pub fn prim(graph: &WeightedGraph) -> SpanningTree {
+    let n = graph.node_count();
+    let edges = graph.edges();
+
+    // Incident edges of every node, as indices into `edges`.
+    let mut incident = vec![Vec::new(); n];
+    for (index, edge) in edges.iter().enumerate() {
+        incident[edge.start].push(index);
+        if edge.start != edge.end {
+            incident[edge.end].push(index);
+        }
+    }
+
+    let mut in_tree = vec![false; n];
+    let mut tree = Vec::new();
+    let mut heap = BinaryHeap::new();
+    for root in 0..n {
+        if in_tree[root] {
+            continue;
+        }
+
+        in_tree[root] = true;
+        for &edge_index in incident[root].iter() {
+            let edge = edges[edge_index];
+            let node = if edge.start == root { edge.end } else { edge.start };
+            heap.push(HeapEntry { weight: edge.weight, edge_index, node });
+        }
+
+        while let Some(HeapEntry { edge_index, node: curr, .. }) = heap.pop() {
+            if in_tree[curr] {
+                continue;
+            }
+            in_tree[curr] = true;
+            tree.push(edges[edge_index]);
+
+            for &next_index in incident[curr].iter() {
+                let edge = edges[next_index];
+                let next_node = if edge.start == curr { edge.end } else { edge.start };
+                if !in_tree[next_node] {
+                    heap.push(HeapEntry { weight: edge.weight, edge_index: next_index, node: next_node });
+                }
+            }
+        }
+    }
+    SpanningTree::from_edges(tree)
+}
+
+/// Borůvka's algorithm. Each round, worker threads scan disjoint chunks of the edge list
+/// for the cheapest edge leaving every component; the chunk results are then merged and
+/// the selected edges added to the forest.
+pub fn boruvka(graph: &WeightedGraph) -> SpanningTree {
+    let thread_count = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    boruvka_with_threads(graph, thread_count)
+}
+
+pub fn boruvka_with_threads(graph: &WeightedGraph, thread_count: usize) -> SpanningTree {
+    let n = graph.node_count();
+    let edges = graph.edges();
+    let chunk_size = edges.len().div_ceil(thread_count.max(1)).max(1);
+
+    let mut sets = UnionFind::new(n);
+    let mut tree = Vec::new();
+    loop {
+        let components: Vec<usize> = (0..n).map(|node| sets.find(node)).collect();
+
+        let cheapest_by_chunk: Vec<Vec<Option<usize>>> = thread::scope(|scope| {
+            let workers: Vec<_> = edges
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(chunk_index, chunk)| {
+                    let components = &components;
+                    scope.spawn(move || {
+                        let mut cheapest: Vec<Option<usize>> = vec![None; n];
+                        for (offset, edge) in chunk.iter().enumerate() {
+                            let index = chunk_index * chunk_size + offset;
+                            let (a, b) = (components[edge.start], components[edge.end]);
+                            if a == b {
+                                continue;
+                            }
+                            for component in [a, b] {
+                                let better = match cheapest[component] {
+                                    Some(current) => compare_edges(edges, index, current) == Ordering::Less,
+                                    None => true,
+                                };
+                                if better {
+                                    cheapest[component] = Some(index);
+                                }
+                            }
+                        }
+                        cheapest
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|worker| worker.join().expect("Borůvka worker panicked.")).collect()
+        });
+
+        let mut cheapest: Vec<Option<usize>> = vec![None; n];
+        for chunk in cheapest_by_chunk {
+            for (component, candidate) in chunk.into_iter().enumerate() {
+                if let Some(index) = candidate {
+                    let better = match cheapest[component] {
+                        Some(current) => compare_edges(edges, index, current) == Ordering::Less,
+                        None => true,
+                    };
+                    if better {
+                        cheapest[component] = Some(index);
+                    }
+                }
+            }
+        }
+
+        let mut merged = false;
+        for index in cheapest.into_iter().flatten() {
+            let edge = edges[index];
+            if sets.union(edge.start, edge.end) {
+                tree.push(edge);
+                merged = true;
+            }
+        }
+        if !merged {
+            break;
+        }
+    }
+    SpanningTree::from_edges(tree)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compare_utils::assert_eq_f64;
+
+    fn sample_graph() -> WeightedGraph {
+        WeightedGraph::from_edges(7, false, vec![
+            (0, 1, 7.), (0, 3, 5.),
+            (1, 2, 8.), (1, 3, 9.), (1, 4, 7.),
+            (2, 4, 5.),
+            (3, 4, 15.), (3, 5, 6.),
+            (4, 5, 8.), (4, 6, 9.),
+            (5, 6, 11.),
+        ])
+    }
+
+    fn sorted_edges(tree: &SpanningTree) -> Vec<(usize, usize)> {
+        let mut edges: Vec<(usize, usize)> = tree.edges.iter()
+            .map(|edge| (edge.start.min(edge.end), edge.start.max(edge.end)))
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn test_algorithms_agree() {
+        let graph = sample_graph();
+        let expected = vec![(0, 1), (0, 3), (1, 4), (2, 4), (3, 5), (4, 6)];
+
+        for tree in [kruskal(&graph), prim(&graph), boruvka_with_threads(&graph, 3)] {
+            assert_eq_f64(tree.total_weight, 39., 1e-12);
+            assert_eq!(expected, sorted_edges(&tree));
+        }
+    }
+
+    #[test]
+    fn test_spanning_forest() {
+        let graph = WeightedGraph::from_edges(5, false, vec![(0, 1, 1.), (1, 2, 2.), (0, 2, 3.), (3, 4, 4.)]);
+        for tree in [kruskal(&graph), prim(&graph), boruvka(&graph)] {
+            assert_eq!(vec![(0, 1), (1, 2), (3, 4)], sorted_edges(&tree));
+            assert_eq_f64(tree.total_weight, 7., 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_equal_weights() {
+        // A 4-cycle with equal weights: ties must not create a cycle in Borůvka.
+        let graph = WeightedGraph::from_edges(4, false, vec![(0, 1, 1.), (1, 2, 1.), (2, 3, 1.), (3, 0, 1.)]);
+        for tree in [kruskal(&graph), prim(&graph), boruvka_with_threads(&graph, 2)] {
+            assert_eq!(3, tree.edges.len());
+        }
+    }
+}
@@ -0,0 +1,447 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt;
+
+use crate::graph::WeightedGraph;
+
+/// Single-source shortest paths: distances plus the predecessor tree.
+///
+/// Unreachable nodes have an infinite distance and no predecessor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortestPaths {
+    pub source: usize,
+    pub distances: Vec<f64>,
+    pub predecessors: Vec<Option<usize>>,
+}
+
+impl ShortestPaths {
+    fn new(n: usize, source: usize) -> ShortestPaths {
+        let mut distances = vec![f64::INFINITY; n];
+        distances[source] = 0.0;
+        ShortestPaths { source, distances, predecessors: vec![None; n] }
+    }
+
+    pub fn distance(&self, target: usize) -> Option<f64> {
+        let distance = self.distances[target];
+        if distance.is_finite() { Some(distance) } else { None }
+    }
+
+    /// Nodes on the shortest path from the source to `target`, both included.
+    pub fn path_to(&self, target: usize) -> Option<Vec<usize>> {
+        self.distance(target)?;
+        let mut path = vec![target];
+        let mut curr = target;
+        while let Some(prev) = self.predecessors[curr] {
+            path.push(prev);
+            curr = prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// All-pairs shortest paths. `predecessors[i][j]` is the node before `j` on the shortest
+/// path from `i` to `j`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllPairsShortestPaths {
+    pub distances: Vec<Vec<f64>>,
+    pub predecessors: Vec<Vec<Option<usize>>>,
+}
+
+impl AllPairsShortestPaths {
+    pub fn distance(&self, start: usize, end: usize) -> Option<f64> {
+        let distance = self.distances[start][end];
+        if distance.is_finite() { Some(distance) } else { None }
+    }
+
+    pub fn path(&self, start: usize, end: usize) -> Option<Vec<usize>> {
+        self.distance(start, end)?;
+        let mut path = vec![end];
+        let mut curr = end;
+        while curr != start {
+            curr = self.predecessors[start][curr]?;
+            path.push(curr);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShortestPathError {
+    /// Dijkstra and A* need weights that are non-negative numbers.
+    InvalidWeight { start: usize, end: usize, weight: f64 },
+    /// A cycle of negative total weight makes shortest distances undefined. Nodes of the
+    /// cycle are in edge order, and the last one has an edge back to the first.
+    NegativeCycle { cycle: Vec<usize> },
+    /// A negative cycle was detected at `node`, but following the predecessors from it
+    /// did not lead to the cycle.
+    UntraceableCycle { node: usize },
+}
+
+impl fmt::Display for ShortestPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShortestPathError::InvalidWeight { start, end, weight } => {
+                write!(f, "edge {} -> {} has weight {}, which must be non-negative", start, end, weight)
+            }
+            ShortestPathError::NegativeCycle { cycle } => {
+                let path: Vec<String> = cycle.iter().map(|node| node.to_string()).collect();
+                write!(f, "graph contains a negative cycle: {} -> {}", path.join(" -> "), cycle[0])
+            }
+            ShortestPathError::UntraceableCycle { node } => {
+                write!(f, "graph contains a negative cycle, which cannot be traced from node {}", node)
+            }
+        }
+    }
+}
+
+impl Error for ShortestPathError {}
+
+/// Min-heap entry ordered by `priority`.
+#[derive(Debug)]
+struct HeapEntry {
+    priority: f64,
+    node: usize,
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority).then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+/// Dijkstra's algorithm with a binary heap.
+///
+/// Fails if an edge reachable from `source` has a negative or NaN weight.
+pub fn dijkstra(graph: &WeightedGraph, source: usize) -> Result<ShortestPaths, ShortestPathError> {
+    astar_search(graph, source, None, |_| 0.0)
+}
+
+/// A* search from `source` to `target`. `heuristic(node)` must never overestimate the
+/// remaining distance to `target`, otherwise the returned path may not be the shortest.
+/// Nodes are reopened when a shorter path to them is found, so the heuristic need not
+/// be consistent, although a consistent one avoids reopening.
+///
+/// The search stops as soon as `target` is settled, so distances of other nodes may be
+/// left unset. Fails like `dijkstra` on negative weights.
+pub fn astar<H>(
+    graph: &WeightedGraph,
+    source: usize,
+    target: usize,
+    heuristic: H,
+) -> Result<ShortestPaths, ShortestPathError>
+where
+    H: Fn(usize) -> f64,
+{
+    astar_search(graph, source, Some(target), heuristic)
+}
+
+fn astar_search<H>(
+    graph: &WeightedGraph,
+    source: usize,
+    target: Option<usize>,
+    heuristic: H,
+) -> Result<ShortestPaths, ShortestPathError>
+where
+    H: Fn(usize) -> f64,
+{
+    let mut result = ShortestPaths::new(graph.node_count(), source);
+    let mut settled = vec![false; graph.node_count()];
+    let mut heap = BinaryHeap::from([HeapEntry { priority: heuristic(source), node: source }]);
+
+    while let Some(HeapEntry { node: curr, .. }) = heap.pop() {
+        if settled[curr] {
+            continue;
+        }
+        settled[curr] = true;
+        if Some(curr) == target {
+            break;
+        }
+
+        for edge in graph.neighbors(curr) {
+            if edge.weight.is_nan() || edge.weight < 0.0 {
+                return Err(ShortestPathError::InvalidWeight { start: edge.start, end: edge.end, weight: edge.weight });
+            }
+            let new_distance = result.distances[curr] + edge.weight;
+            if new_distance < result.distances[edge.end] {
+                result.distances[edge.end] = new_distance;
+                result.predecessors[edge.end] = Some(curr);
+                // Only happens with an inconsistent heuristic.
+                settled[edge.end] = false;
+                heap.push(HeapEntry { priority: new_distance + heuristic(edge.end), node: edge.end });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Bellman-Ford algorithm. Supports negative weights and reports a negative cycle reachable
+/// from `source`.
+pub fn bellman_ford(graph: &WeightedGraph, source: usize) -> Result<ShortestPaths, ShortestPathError> {
+    let n = graph.node_count();
+    let mut result = ShortestPaths::new(n, source);
+
+    for _ in 1..n {
+        let mut updated = false;
+        for u in 0..n {
+            if !result.distances[u].is_finite() {
+                continue;
+            }
+            for edge in graph.neighbors(u) {
+                let new_distance = result.distances[u] + edge.weight;
+                if new_distance < result.distances[edge.end] {
+                    result.distances[edge.end] = new_distance;
+                    result.predecessors[edge.end] = Some(u);
+                    updated = true;
+                }
+            }
+        }
+        if !updated {
+            return Ok(result);
+        }
+    }
+
+    for u in 0..n {
+        if !result.distances[u].is_finite() {
+            continue;
+        }
+        for edge in graph.neighbors(u) {
+            if result.distances[u] + edge.weight < result.distances[edge.end] {
+                result.predecessors[edge.end] = Some(u);
+                return Err(extract_cycle(edge.end, n, |node| result.predecessors[node]));
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Follows predecessors from `start` until a node repeats. `n` steps are enough to be
+/// sure the walk has entered the cycle.
+fn extract_cycle<P>(start: usize, n: usize, predecessor: P) -> ShortestPathError
+where
+    P: Fn(usize) -> Option<usize>,
+{
+    let untraceable = ShortestPathError::UntraceableCycle { node: start };
+    let mut curr = start;
+    for _ in 0..n {
+        match predecessor(curr) {
+            Some(prev) => curr = prev,
+            None => return untraceable,
+        }
+    }
+
+    let mut cycle = vec![curr];
+    let mut prev = curr;
+    loop {
+        match predecessor(prev) {
+            Some(node) if node == curr => break,
+            Some(node) if cycle.len() < n => {
+                cycle.push(node);
+                prev = node;
+            }
+            _ => return untraceable,
+        }
+    }
+    cycle.reverse();
+    ShortestPathError::NegativeCycle { cycle }
+}
+
+/// Floyd-Warshall algorithm. Supports negative weights and reports any negative cycle.
+pub fn floyd_warshall(graph: &WeightedGraph) -> Result<AllPairsShortestPaths, ShortestPathError> {
+    let n = graph.node_count();
+    let mut distances = vec![vec![f64::INFINITY; n]; n];
+    let mut predecessors = vec![vec![None; n]; n];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[i] = 0.0;
+    }
+    for u in 0..n {
+        for edge in graph.neighbors(u) {
+            if edge.weight < distances[u][edge.end] {
+                distances[u][edge.end] = edge.weight;
+                predecessors[u][edge.end] = Some(u);
+            }
+        }
+    }
+
+    for k in 0..n {
+        for i in 0..n {
+            if !distances[i][k].is_finite() {
+                continue;
+            }
+            for j in 0..n {
+                let new_distance = distances[i][k] + distances[k][j];
+                if new_distance < distances[i][j] {
+                    distances[i][j] = new_distance;
+                    predecessors[i][j] = predecessors[k][j];
+                }
+            }
+        }
+    }
+
+    if let Some(i) = (0..n).find(|&i| distances[i][i] < 0.0) {
+        return Err(extract_cycle(i, n, |node| predecessors[i][node]));
+    }
+
+    Ok(AllPairsShortestPaths { distances, predecessors })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compare_utils::assert_eq_f64;
+
+    fn sample_graph() -> WeightedGraph {
+        WeightedGraph::from_edges(6, true, vec![
+            (0, 1, 7.), (0, 2, 9.), (0, 5, 14.),
+            (1, 2, 10.), (1, 3, 15.),
+            (2, 3, 11.), (2, 5, 2.),
+            (3, 4, 6.),
+            (5, 4, 9.),
+        ])
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let result = dijkstra(&sample_graph(), 0).unwrap();
+        assert_eq!(vec![0., 7., 9., 20., 20., 11.], result.distances);
+        assert_eq!(Some(vec![0, 2, 5, 4]), result.path_to(4));
+        assert_eq!(Some(vec![0]), result.path_to(0));
+    }
+
+    #[test]
+    fn test_unreachable_node() {
+        let mut graph = sample_graph();
+        graph.add_edge(6, 0, 1.);
+        let result = dijkstra(&graph, 0).unwrap();
+        assert_eq!(None, result.distance(6));
+        assert_eq!(None, result.path_to(6));
+    }
+
+    #[test]
+    fn test_bellman_ford_matches_dijkstra() {
+        let graph = sample_graph();
+        assert_eq!(dijkstra(&graph, 0).unwrap().distances, bellman_ford(&graph, 0).unwrap().distances);
+    }
+
+    #[test]
+    fn test_dijkstra_rejects_negative_weights() {
+        let graph = WeightedGraph::from_edges(3, true, vec![(0, 1, 4.), (1, 2, -3.)]);
+        let error = ShortestPathError::InvalidWeight { start: 1, end: 2, weight: -3. };
+        assert_eq!(Err(error.clone()), dijkstra(&graph, 0));
+        assert_eq!(Err(error), astar(&graph, 0, 2, |_| 0.0));
+    }
+
+    #[test]
+    fn test_bellman_ford_negative_weights() {
+        let graph = WeightedGraph::from_edges(4, true, vec![
+            (0, 1, 4.), (0, 2, 5.), (2, 1, -3.), (1, 3, 2.),
+        ]);
+        let result = bellman_ford(&graph, 0).unwrap();
+        assert_eq!(vec![0., 2., 5., 4.], result.distances);
+        assert_eq!(Some(vec![0, 2, 1, 3]), result.path_to(3));
+    }
+
+    #[test]
+    fn test_bellman_ford_negative_cycle() {
+        let graph = WeightedGraph::from_edges(5, true, vec![
+            (0, 1, 1.), (1, 2, 1.), (2, 3, -4.), (3, 1, 1.), (3, 4, 1.),
+        ]);
+        let Err(ShortestPathError::NegativeCycle { cycle: mut nodes }) = bellman_ford(&graph, 0) else {
+            panic!("the graph has a negative cycle");
+        };
+        nodes.sort();
+        assert_eq!(vec![1, 2, 3], nodes);
+    }
+
+    #[test]
+    fn test_astar_on_grid() {
+        // 4x4 grid with unit weights, node = 4 * row + col.
+        let width = 4;
+        let mut graph = WeightedGraph::new_undirected(width * width);
+        for row in 0..width {
+            for col in 0..width {
+                let node = width * row + col;
+                if col + 1 < width {
+                    graph.add_edge(node, node + 1, 1.);
+                }
+                if row + 1 < width {
+                    graph.add_edge(node, node + width, 1.);
+                }
+            }
+        }
+
+        let target = width * width - 1;
+        let manhattan = |node: usize| ((width - 1 - node / width) + (width - 1 - node % width)) as f64;
+        let result = astar(&graph, 0, target, manhattan).unwrap();
+
+        assert_eq!(Some(6.), result.distance(target));
+        let path = result.path_to(target).unwrap();
+        assert_eq!(7, path.len());
+        assert_eq!((0, target), (path[0], path[6]));
+    }
+
+    #[test]
+    fn test_astar_reopens_nodes() {
+        // The heuristic never overestimates but is not consistent: it draws the search to
+        // node 2 through the long edge first.
+        let graph = WeightedGraph::from_edges(5, true, vec![
+            (0, 1, 1.), (1, 2, 1.), (0, 2, 3.), (2, 3, 1.), (3, 4, 1.),
+        ]);
+        let heuristic = |node: usize| [4., 3., 0., 0., 0.][node];
+        let result = astar(&graph, 0, 4, heuristic).unwrap();
+        assert_eq!(Some(4.), result.distance(4));
+        assert_eq!(Some(vec![0, 1, 2, 3, 4]), result.path_to(4));
+    }
+
+    #[test]
+    fn test_floyd_warshall() {
+        let graph = sample_graph();
+        let result = floyd_warshall(&graph).unwrap();
+        for source in 0..graph.node_count() {
+            let single_source = dijkstra(&graph, source).unwrap();
+            for target in 0..graph.node_count() {
+                assert_eq!(single_source.distance(target), result.distance(source, target));
+            }
+        }
+        assert_eq!(Some(vec![0, 2, 5, 4]), result.path(0, 4));
+        assert_eq!(None, result.path(4, 0));
+    }
+
+    #[test]
+    fn test_floyd_warshall_negative_cycle() {
+        let graph = WeightedGraph::from_edges(3, true, vec![(0, 1, 1.), (1, 2, -1.), (2, 1, -1.)]);
+        let Err(ShortestPathError::NegativeCycle { cycle: mut nodes }) = floyd_warshall(&graph) else {
+            panic!("the graph has a negative cycle");
+        };
+        nodes.sort();
+        assert_eq!(vec![1, 2], nodes);
+    }
+
+    #[test]
+    fn test_undirected_graph() {
+        let graph = WeightedGraph::from_edges(3, false, vec![(0, 1, 1.5), (1, 2, 2.5)]);
+        let result = dijkstra(&graph, 2).unwrap();
+        assert_eq_f64(result.distances[0], 4.0, 1e-12);
+        assert_eq!(Some(vec![2, 1, 0]), result.path_to(0));
+    }
+}
@@ -0,0 +1,88 @@
+/// Disjoint-set forest with path compression and union by rank.
+#[derive(Debug, Clone)]
+pub struct UnionFind {
+    parents: Vec<usize>,
+    ranks: Vec<u8>,
+    set_count: usize,
+}
+
+impl UnionFind {
+    /// Creates `n` singleton sets `{0}, {1}, ..., {n - 1}`.
+    pub fn new(n: usize) -> UnionFind {
+        UnionFind { parents: (0..n).collect(), ranks: vec![0; n], set_count: n }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// Number of disjoint sets.
+    pub fn set_count(&self) -> usize {
+        self.set_count
+    }
+
+    /// Representative of the set containing `x`.
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+
+        let mut curr = x;
+        while self.parents[curr] != root {
+            let next = self.parents[curr];
+            self.parents[curr] = root;
+            curr = next;
+        }
+        root
+    }
+
+    /// Merges the sets containing `a` and `b`. Returns false if they were already merged.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a == root_b {
+            return false;
+        }
+
+        if self.ranks[root_a] < self.ranks[root_b] {
+            self.parents[root_a] = root_b;
+        } else {
+            self.parents[root_b] = root_a;
+            if self.ranks[root_a] == self.ranks[root_b] {
+                self.ranks[root_a] += 1;
+            }
+        }
+        self.set_count -= 1;
+        true
+    }
+
+    pub fn connected(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_union_find() {
+        let mut sets = UnionFind::new(6);
+        assert_eq!(6, sets.set_count());
+
+        assert!(sets.union(0, 1));
+        assert!(sets.union(2, 3));
+        assert!(sets.union(1, 3));
+        assert!(!sets.union(0, 2));
+
+        assert!(sets.connected(0, 3));
+        assert!(!sets.connected(0, 4));
+        assert_eq!(3, sets.set_count());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::graph::AdjList;
+
+/// A weighted edge, using the same `start`/`end` naming as the edge lists of the graph binaries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightedEdge {
+    pub start: usize,
+    pub end: usize,
+    pub weight: f64,
+}
+
+impl WeightedEdge {
+    pub fn new(start: usize, end: usize, weight: f64) -> WeightedEdge {
+        WeightedEdge { start, end, weight }
+    }
+
+    pub fn reversed(&self) -> WeightedEdge {
+        WeightedEdge { start: self.end, end: self.start, weight: self.weight }
+    }
+}
+
+/// Weighted graph stored as an edge list plus an adjacency list of outgoing edges.
+///
+/// In an undirected graph every edge is listed once in `edges()` and appears in the
+/// neighbors of both of its end points.
+#[derive(Debug, Clone)]
+pub struct WeightedGraph {
+    directed: bool,
+    edges: Vec<WeightedEdge>,
+    neighbors: Vec<Vec<WeightedEdge>>,
+}
+
+impl WeightedGraph {
+    pub fn new(n: usize) -> WeightedGraph {
+        WeightedGraph { directed: true, edges: Vec::new(), neighbors: vec![Vec::new(); n] }
+    }
+
+    pub fn new_undirected(n: usize) -> WeightedGraph {
+        WeightedGraph { directed: false, edges: Vec::new(), neighbors: vec![Vec::new(); n] }
+    }
+
+    pub fn from_edges<I>(n: usize, directed: bool, edges: I) -> WeightedGraph
+    where
+        I: IntoIterator<Item = (usize, usize, f64)>,
+    {
+        let mut graph = if directed { WeightedGraph::new(n) } else { WeightedGraph::new_undirected(n) };
+        for (start, end, weight) in edges {
+            graph.add_edge(start, end, weight);
+        }
+        graph
+    }
+
+    /// Builds a directed graph with unit weights from an adjacency list.
+    pub fn from_adj_list(neighbors: &AdjList) -> WeightedGraph {
+        let mut graph = WeightedGraph::new(neighbors.len());
+        for (start, list) in neighbors.iter().enumerate() {
+            for &end in list {
+                graph.add_edge(start, end, 1.0);
+            }
+        }
+        graph
+    }
+
+    /// Adds an edge, growing the node set if `start` or `end` is out of range.
+    ///
+    /// Panics if `start` or `end` is `usize::MAX`.
+    pub fn add_edge(&mut self, start: usize, end: usize, weight: f64) {
+        let required = start.max(end).checked_add(1).expect("node id is too large");
+        if self.neighbors.len() < required {
+            self.neighbors.resize(required, Vec::new());
+        }
+
+        let edge = WeightedEdge::new(start, end, weight);
+        self.edges.push(edge);
+        self.neighbors[start].push(edge);
+        if !self.directed && start != end {
+            self.neighbors[end].push(edge.reversed());
+        }
+    }
+
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn edges(&self) -> &[WeightedEdge] {
+        &self.edges
+    }
+
+    /// Outgoing edges of `node`. Every returned edge has `start == node`.
+    pub fn neighbors(&self, node: usize) -> &[WeightedEdge] {
+        &self.neighbors[node]
+    }
+
+    pub fn to_adj_list(&self) -> AdjList {
+        self.neighbors
+            .iter()
+            .map(|list| list.iter().map(|edge| edge.end).collect())
+            .collect()
+    }
+}
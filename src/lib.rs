@@ -7,4 +7,8 @@ pub mod numerical_utils;
 
 pub mod logging;
 
-pub mod event_sim;
\ No newline at end of file
+pub mod event_sim;
+
+pub mod graph;
+
+pub mod actors;
\ No newline at end of file